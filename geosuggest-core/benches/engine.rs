@@ -0,0 +1,93 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use geosuggest_core::{Engine, SourceFileContentOptions};
+
+// number of cities in generated indexes
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn read_fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/misc/{name}", env!("CARGO_MANIFEST_DIR")))
+        .expect("On read fixture")
+}
+
+/// Build index of `size` cities: fixture cities plus their copies scattered around.
+fn build_engine(size: usize) -> Engine {
+    let fixture = read_fixture("cities.txt");
+    let rows = fixture.lines().collect::<Vec<_>>();
+
+    let mut cities = Vec::with_capacity(size);
+    for i in 0..size {
+        let row = rows[i % rows.len()];
+        if i < rows.len() {
+            cities.push(row.to_owned());
+            continue;
+        }
+
+        let mut fields = row.split('\t').map(String::from).collect::<Vec<_>>();
+        let lat_shift = (i * 7919 % 2000) as f32 / 100.0 - 10.0;
+        let lng_shift = (i * 104729 % 4000) as f32 / 100.0 - 20.0;
+        fields[0] = (100_000_000 + i).to_string();
+        fields[1] = format!("{} {i}", fields[1]);
+        fields[2] = format!("{} {i}", fields[2]);
+        // keep alternate names of copies short to limit entries count
+        fields[3] = fields[3].split(',').take(5).collect::<Vec<_>>().join(",");
+        fields[4] = (fields[4].parse::<f32>().unwrap() + lat_shift)
+            .clamp(-90.0, 90.0)
+            .to_string();
+        fields[5] = (fields[5].parse::<f32>().unwrap() + lng_shift).to_string();
+        fields[14] = (i * 31 % 1_000_000).to_string();
+        cities.push(fields.join("\t"));
+    }
+
+    Engine::new_from_files_content(SourceFileContentOptions {
+        cities: cities.join("\n"),
+        names: Some(read_fixture("names.txt")),
+        countries: Some(read_fixture("country-info.txt")),
+        admin1_codes: Some(read_fixture("admin1-codes.txt")),
+        admin2_codes: Some(read_fixture("admin2-codes.txt")),
+        filter_languages: vec!["ru"],
+        ..Default::default()
+    })
+    .expect("On build engine")
+}
+
+fn suggest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suggest");
+    for size in SIZES {
+        let engine = build_engine(size);
+
+        group.bench_with_input(BenchmarkId::new("prefix", size), &engine, |b, engine| {
+            b.iter(|| engine.suggest::<&str>("voronezh", 10, None, None))
+        });
+        group.bench_with_input(BenchmarkId::new("fuzzy", size), &engine, |b, engine| {
+            b.iter(|| engine.suggest::<&str>("vronezh", 10, None, None))
+        });
+        group.bench_with_input(BenchmarkId::new("countries", size), &engine, |b, engine| {
+            b.iter(|| engine.suggest("vronezh", 10, None, Some(&["RU"])))
+        });
+    }
+    group.finish();
+}
+
+fn reverse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reverse");
+    let loc = (51.6372, 39.1937);
+    for size in SIZES {
+        let engine = build_engine(size);
+
+        group.bench_with_input(BenchmarkId::new("nearest", size), &engine, |b, engine| {
+            b.iter(|| engine.reverse::<&str>(loc, 10, None, None))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("population", size),
+            &engine,
+            |b, engine| b.iter(|| engine.reverse::<&str>(loc, 10, Some(0.000000005), None)),
+        );
+        group.bench_with_input(BenchmarkId::new("countries", size), &engine, |b, engine| {
+            b.iter(|| engine.reverse(loc, 10, None, Some(&["GB"])))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, suggest, reverse);
+criterion_main!(benches);
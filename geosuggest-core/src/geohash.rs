@@ -0,0 +1,64 @@
+use crate::BBox;
+
+const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Encode location to geohash of `len` chars
+pub fn encode(lat: f32, lng: f32, len: usize) -> String {
+    let (mut lat_range, mut lng_range) = ((-90.0f64, 90.0f64), (-180.0f64, 180.0f64));
+    let (lat, lng) = (lat as f64, lng as f64);
+
+    let mut result = String::with_capacity(len);
+    let mut is_lng = true;
+    while result.len() < len {
+        let mut index = 0;
+        for _ in 0..5 {
+            let (range, value) = if is_lng {
+                (&mut lng_range, lng)
+            } else {
+                (&mut lat_range, lat)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lng = !is_lng;
+        }
+        result.push(BASE32[index] as char);
+    }
+    result
+}
+
+/// Bounding box of geohash cell, `None` on invalid geohash
+pub fn decode_bbox(geohash: &str) -> Option<BBox> {
+    let (mut lat_range, mut lng_range) = ((-90.0f64, 90.0f64), (-180.0f64, 180.0f64));
+
+    let mut is_lng = true;
+    for c in geohash.bytes() {
+        let index = BASE32.iter().position(|b| *b == c.to_ascii_lowercase())?;
+        for bit in (0..5).rev() {
+            let range = if is_lng {
+                &mut lng_range
+            } else {
+                &mut lat_range
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            if index & (1 << bit) != 0 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lng = !is_lng;
+        }
+    }
+
+    Some(BBox {
+        min_lat: lat_range.0 as f32,
+        min_lng: lng_range.0 as f32,
+        max_lat: lat_range.1 as f32,
+        max_lng: lng_range.1 as f32,
+    })
+}
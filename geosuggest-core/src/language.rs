@@ -0,0 +1,97 @@
+//! Language tags of translations (geonames `isolanguage`).
+
+use std::borrow::Cow;
+
+/// Geonames pseudo languages of alternate names
+const PSEUDO_LANGUAGES: [&str; 9] = [
+    "post", "iata", "icao", "faac", "abbr", "link", "wkdt", "unlc", "fr_1793",
+];
+
+/// ISO 639-2 (terminology and bibliographic) codes of languages having ISO 639-1 code
+const ISO_639_2: [(&str, &str); 52] = [
+    ("ara", "ar"),
+    ("arm", "hy"),
+    ("aze", "az"),
+    ("bel", "be"),
+    ("ben", "bn"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("chi", "zh"),
+    ("cze", "cs"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("dut", "nl"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("est", "et"),
+    ("fas", "fa"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("fre", "fr"),
+    ("geo", "ka"),
+    ("ger", "de"),
+    ("gre", "el"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hun", "hu"),
+    ("hye", "hy"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("jpn", "ja"),
+    ("kat", "ka"),
+    ("kaz", "kk"),
+    ("kor", "ko"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("nld", "nl"),
+    ("nor", "no"),
+    ("per", "fa"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rum", "ro"),
+    ("rus", "ru"),
+    ("slk", "sk"),
+    ("slo", "sk"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tha", "th"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("zho", "zh"),
+];
+
+/// Canonical isolanguage code of translations for language tag.
+///
+/// Tag is lowercased, region and script subtags are dropped (`en-US` -> `en`) and ISO 639-2
+/// codes are mapped to ISO 639-1 (`eng` -> `en`). Geonames pseudo languages (`post`, `iata`,
+/// `abbr`, ...) are kept as is.
+///
+/// Returns error for malformed tag, e.g. `english` or `e1`.
+pub fn canonical_language(tag: &str) -> Result<Cow<'_, str>, String> {
+    let tag = tag.trim();
+    if PSEUDO_LANGUAGES.iter().any(|l| l.eq_ignore_ascii_case(tag)) {
+        return Ok(to_lowercase(tag));
+    }
+
+    let primary = tag.split(['-', '_']).next().unwrap_or_default();
+    if !(2..=3).contains(&primary.len()) || !primary.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("Invalid language tag: {tag:?}"));
+    }
+
+    let primary = to_lowercase(primary);
+    match ISO_639_2.iter().find(|(code, _)| *code == primary) {
+        Some((_, code)) => Ok(Cow::Borrowed(code)),
+        None => Ok(primary),
+    }
+}
+
+fn to_lowercase(value: &str) -> Cow<'_, str> {
+    if value.chars().any(|c| c.is_ascii_uppercase()) {
+        Cow::Owned(value.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(value)
+    }
+}
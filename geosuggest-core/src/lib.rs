@@ -1,9 +1,9 @@
 #![doc = include_str!("../README.md")]
-use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
-
-#[cfg(feature = "tracing")]
-use std::time::Instant;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 use itertools::Itertools;
 
@@ -11,21 +11,44 @@ use kiddo::{self, SquaredEuclidean};
 
 use kiddo::immutable::float::kdtree::ImmutableKdTree;
 
-use rayon::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use rayon::{iter::Either, prelude::*};
 use serde::{Deserialize, Serialize};
-use strsim::jaro_winkler;
 
 #[cfg(feature = "geoip2_support")]
 use std::net::IpAddr;
 
 #[cfg(feature = "geoip2_support")]
 use geoip2::{City, Reader};
+#[cfg(feature = "geoip2_support")]
+use lru::LruCache;
+#[cfg(feature = "geoip2_support")]
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 
 #[cfg(feature = "oaph_support")]
 use oaph::schemars::{self, JsonSchema};
 
+mod geohash;
+mod language;
+mod memory;
+mod normalize;
+mod ordered;
 pub mod storage;
 
+pub use language::canonical_language;
+pub use memory::MemoryUsage;
+pub use normalize::{
+    match_span, match_span_with, CaseFolding, MatchSpan, NormalizedQuery, ADMIN1_ABBREVIATIONS,
+    DEFAULT_ABBREVIATIONS, NORMALIZATION_FEATURES, NORMALIZATION_VERSION,
+};
+
+#[derive(Default)]
 pub struct SourceFileOptions<'a, P: AsRef<std::path::Path>> {
     pub cities: P,
     pub names: Option<P>,
@@ -33,8 +56,16 @@ pub struct SourceFileOptions<'a, P: AsRef<std::path::Path>> {
     pub admin1_codes: Option<P>,
     pub admin2_codes: Option<P>,
     pub filter_languages: Vec<&'a str>,
+    pub case_folding: CaseFolding,
+    /// Fail build when malformed rows of any source exceed this count
+    pub max_malformed_rows: Option<usize>,
+    /// Cleanup of alternate names before indexing
+    pub entry_filter: EntryFilter,
+    /// Resolution of cities rows sharing geonameid
+    pub duplicates: DuplicatePolicy,
 }
 
+#[derive(Default)]
 pub struct SourceFileContentOptions<'a> {
     pub cities: String,
     pub names: Option<String>,
@@ -42,6 +73,128 @@ pub struct SourceFileContentOptions<'a> {
     pub admin1_codes: Option<String>,
     pub admin2_codes: Option<String>,
     pub filter_languages: Vec<&'a str>,
+    pub case_folding: CaseFolding,
+    /// Fail build when malformed rows of any source exceed this count
+    pub max_malformed_rows: Option<usize>,
+    /// Cleanup of alternate names before indexing
+    pub entry_filter: EntryFilter,
+    /// Resolution of cities rows sharing geonameid
+    pub duplicates: DuplicatePolicy,
+}
+
+/// Cleanup rules of cities alternate names (search entries), junk values pollute matching
+/// and grow index.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EntryFilter {
+    /// Drop values longer than this count of chars
+    pub max_length: Option<usize>,
+    /// Drop URL-like values, e.g. `https://en.wikipedia.org/wiki/Voronezh`
+    pub drop_urls: bool,
+    /// Strip parenthetical qualifiers, e.g. `Frankfurt (Oder)` -> `Frankfurt`
+    pub strip_parentheses: bool,
+    /// Move historic names of names file (e.g. `Leningrad`) out of search entries to
+    /// [`CitiesRecord::former_names`], see [`SuggestOptions::former_names`]
+    pub former_names: bool,
+}
+
+impl EntryFilter {
+    /// Cleaned up value, `None` if value should be dropped (empty values are always dropped).
+    pub fn apply<'a>(&self, value: &'a str) -> Option<Cow<'a, str>> {
+        if self.drop_urls && is_url_like(value) {
+            return None;
+        }
+
+        let value = if self.strip_parentheses && value.contains('(') {
+            let mut stripped = String::with_capacity(value.len());
+            let mut depth = 0usize;
+            for c in value.chars() {
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth > 0 => depth -= 1,
+                    c if depth == 0 => stripped.push(c),
+                    _ => {}
+                }
+            }
+            Cow::Owned(stripped.split_whitespace().join(" "))
+        } else {
+            Cow::Borrowed(value.trim())
+        };
+
+        if value.is_empty()
+            || self
+                .max_length
+                .is_some_and(|max| value.chars().count() > max)
+        {
+            return None;
+        }
+
+        Some(value)
+    }
+}
+
+/// Resolution of cities rows sharing geonameid, e.g. of merged custom sources or delta updates.
+///
+/// Kept row is chosen deterministically, the dropped ones are counted by
+/// [`EngineMetadata::duplicate_rows`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicatePolicy {
+    /// Row with the newest modification date wins (the later row on the same date)
+    #[default]
+    Newest,
+    /// The first row wins, e.g. sources are concatenated in priority order
+    First,
+    /// The last row wins, e.g. delta update appended to the full dump
+    Last,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "newest" => Ok(DuplicatePolicy::Newest),
+            "first" => Ok(DuplicatePolicy::First),
+            "last" => Ok(DuplicatePolicy::Last),
+            _ => Err(format!("Unknown duplicate policy: {value}")),
+        }
+    }
+}
+
+/// Resolve rows sharing geonameid by policy, keeps order of first occurrences.
+///
+/// Returns count of dropped rows.
+fn dedup_cities(records: &mut Vec<CitiesRecordRaw>, policy: DuplicatePolicy) -> usize {
+    let total = records.len();
+    let mut position: HashMap<u32, usize> = HashMap::with_capacity(total);
+    let mut kept: Vec<CitiesRecordRaw> = Vec::with_capacity(total);
+    for record in records.drain(..) {
+        match position.get(&record.geonameid) {
+            None => {
+                position.insert(record.geonameid, kept.len());
+                kept.push(record);
+            }
+            Some(&index) => {
+                // `yyyy-MM-dd` dates are ordered as strings
+                let replace = match policy {
+                    DuplicatePolicy::Newest => {
+                        record.modification_date >= kept[index].modification_date
+                    }
+                    DuplicatePolicy::First => false,
+                    DuplicatePolicy::Last => true,
+                };
+                if replace {
+                    kept[index] = record;
+                }
+            }
+        }
+    }
+    *records = kept;
+    total - records.len()
+}
+
+fn is_url_like(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    value.contains("://") || value.starts_with("www.")
 }
 
 // code, name, name ascii, geonameid
@@ -112,7 +265,7 @@ struct CitiesRecordRaw {
     _elevation: String,
     _dem: String,
     timezone: String,
-    _modification_date: String,
+    modification_date: String,
 }
 
 // CounntryInfo
@@ -147,10 +300,57 @@ pub struct CountryRecord {
     pub info: CountryRecordRaw,
 
     /// Country name translation
+    #[serde(serialize_with = "ordered::option_map")]
     pub names: Option<HashMap<String, String>>,
 
     /// Capital name translation
+    #[serde(serialize_with = "ordered::option_map")]
     pub capital_names: Option<HashMap<String, String>>,
+
+    /// Bounding box of indexed country cities
+    pub bbox: Option<BBox>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct BBox {
+    pub min_lat: f32,
+    pub min_lng: f32,
+    pub max_lat: f32,
+    pub max_lng: f32,
+}
+
+impl BBox {
+    pub fn from_point(lat: f32, lng: f32) -> Self {
+        BBox {
+            min_lat: lat,
+            min_lng: lng,
+            max_lat: lat,
+            max_lng: lng,
+        }
+    }
+
+    pub fn extend(&mut self, lat: f32, lng: f32) {
+        self.min_lat = self.min_lat.min(lat);
+        self.min_lng = self.min_lng.min(lng);
+        self.max_lat = self.max_lat.max(lat);
+        self.max_lng = self.max_lng.max(lng);
+    }
+
+    pub fn contains(&self, lat: f32, lng: f32) -> bool {
+        lat >= self.min_lat && lat <= self.max_lat && lng >= self.min_lng && lng <= self.max_lng
+    }
+
+    /// Squared euclidean distance from point to the farthest corner of the box.
+    pub fn max_distance_squared(&self, loc: (f32, f32)) -> f32 {
+        let lat = (loc.0 - self.min_lat)
+            .abs()
+            .max((loc.0 - self.max_lat).abs());
+        let lng = (loc.1 - self.min_lng)
+            .abs()
+            .max((loc.1 - self.max_lng).abs());
+        lat * lat + lng * lng
+    }
 }
 
 // The table 'alternate names' :
@@ -208,305 +408,3812 @@ pub struct CitiesRecord {
     pub admin_division: Option<AdminDivision>,
     pub admin2_division: Option<AdminDivision>,
     pub timezone: String,
+    #[serde(serialize_with = "ordered::option_map")]
     pub names: Option<HashMap<String, String>>,
-    // todo try reuse country info
-    pub country_names: Option<HashMap<String, String>>,
-    pub admin1_names: Option<HashMap<String, String>>,
-    pub admin2_names: Option<HashMap<String, String>>,
     pub population: u32,
+    /// Geonames feature code, e.g. `PPLC` for capital or `PPLA` for admin division seat
+    pub feature_code: String,
+    /// Historic names, see [`EntryFilter::former_names`]
+    pub former_names: Option<Vec<String>>,
 }
 
+/// City near the route, see [`Engine::nearest_along`]
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
-pub struct ReverseItem<'a> {
+pub struct RouteItem<'a> {
     pub city: &'a CitiesRecord,
-    pub distance: f32,
-    pub score: f32,
+    /// Distance from the route
+    pub offset_km: f32,
+    /// Distance along the route to the closest point to the city
+    pub progress_km: f32,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-pub struct EngineSourceMetadata {
-    pub cities: String,
-    pub names: Option<String>,
-    pub countries: Option<String>,
-    pub admin1_codes: Option<String>,
-    pub admin2_codes: Option<String>,
-    pub filter_languages: Vec<String>,
-    pub etag: HashMap<String, String>,
+/// Query reported to [`QueryHook`] after each [`Engine::suggest_with`] or [`Engine::reverse_with`] call
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryEvent<'a> {
+    /// `suggest`, `places` or `reverse`
+    pub kind: &'static str,
+    /// Suggest pattern
+    pub pattern: Option<&'a str>,
+    /// Reverse location `(lat, lng)`
+    pub loc: Option<(f32, f32)>,
+    /// Countries filter
+    pub countries: Option<Vec<&'a str>>,
+    /// Bounding box filter
+    pub bbox: Option<BBox>,
+    pub limit: usize,
+    /// Count of found cities
+    pub results: usize,
+    /// Serialized as `elapsed_ms` (fractional milliseconds)
+    #[serde(rename = "elapsed_ms", serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EngineMetadata {
-    /// Index was built on version
-    pub geosuggest_version: String,
-    /// Creation time
-    pub created_at: std::time::SystemTime,
-    /// Sources metadata
-    pub source: EngineSourceMetadata,
-    /// Custom metadata info
-    pub extra: HashMap<String, String>,
+fn serialize_millis<S: serde::Serializer>(
+    value: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.as_secs_f64() * 1000.0)
 }
 
-impl Default for EngineMetadata {
-    fn default() -> Self {
-        Self {
-            created_at: std::time::SystemTime::now(),
-            geosuggest_version: env!("CARGO_PKG_VERSION").to_owned(),
-            source: EngineSourceMetadata::default(),
-            extra: HashMap::default(),
-        }
-    }
+/// Callback invoked per query, e.g. to feed analytics pipeline (top failed patterns,
+/// zero-result queries), see [`Engine::set_query_hook`]
+pub trait QueryHook: Send + Sync {
+    fn on_query(&self, event: &QueryEvent<'_>);
 }
 
-#[derive(Deserialize)]
-struct EngineDump {
-    entries: Vec<Entry>,
-    geonames: HashMap<u32, CitiesRecord>,
-    capitals: HashMap<String, u32>,
-    country_info_by_code: HashMap<String, CountryRecord>,
-    metadata: Option<EngineMetadata>,
+impl<F: Fn(&QueryEvent<'_>) + Send + Sync> QueryHook for F {
+    fn on_query(&self, event: &QueryEvent<'_>) {
+        self(event)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Entry {
-    id: u32,                 // geoname id
-    value: String,           // searchable value
-    country_id: Option<u32>, // geoname country id
+/// Custom ranking of suggested cities, e.g. to boost cities of downstream app priority list,
+/// see [`Engine::set_ranker`]
+pub trait Ranker: Send + Sync {
+    /// Adjusted rank of city passed similarity filter, `rank` is similarity score weighted
+    /// with city prominence (cities are sorted by rank desc)
+    fn rank(&self, city: &CitiesRecord, rank: f32) -> f32;
 }
 
-#[derive(Serialize)]
-pub struct Engine {
-    entries: Vec<Entry>,
-    geonames: HashMap<u32, CitiesRecord>,
-    capitals: HashMap<String, u32>,
-    country_info_by_code: HashMap<String, CountryRecord>,
-    pub metadata: Option<EngineMetadata>,
+impl<F: Fn(&CitiesRecord, f32) -> f32 + Send + Sync> Ranker for F {
+    fn rank(&self, city: &CitiesRecord, rank: f32) -> f32 {
+        self(city, rank)
+    }
+}
 
-    #[serde(skip_serializing)]
-    tree_index_to_geonameid: HashMap<usize, u32>,
-    #[serde(skip_serializing)]
-    tree: ImmutableKdTree<f32, u32, 2, 32>,
+/// Options of [`Engine::suggest_with`]
+///
+/// ```
+/// # use geosuggest_core::SuggestOptions;
+/// let options = SuggestOptions::new(5).min_score(0.9).countries(&["RU", "BY"]);
+/// let options = SuggestOptions::default().limit(5).min_score(0.9);
+/// ```
+///
+/// New options may be added in minor releases, build it with [`SuggestOptions::new`] and
+/// the builder methods.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SuggestOptions<'a, T: AsRef<str> = &'a str> {
+    /// Max count of cities
+    pub limit: usize,
+    /// Min similarity (by `metric`), 0.8 by default
+    pub min_score: Option<f32>,
+    /// Prefilter by countries codes
+    pub countries: Option<&'a [T]>,
+    /// Skip cities of countries codes
+    pub exclude_countries: Option<&'a [T]>,
+    /// Prefilter by admin division (first level) code, e.g. `US.CA`
+    pub admin1: Option<&'a str>,
+    /// Prefilter by min city population
+    pub min_population: Option<u32>,
+    /// Prefilter by geonames feature codes, e.g. `PPLC` and `PPLA`
+    pub feature_codes: Option<&'a [&'a str]>,
+    /// Match only alternate names of language (and default names), e.g. `de`
+    ///
+    /// Language of alternate name is known only for translations of the names file
+    /// (see `filter_languages` of index build), other alternate names are skipped.
+    pub lang: Option<&'a str>,
+    /// Prefilter by city coordinates, e.g. current map viewport
+    pub bbox: Option<BBox>,
+    /// Fuzzy matching algorithm
+    pub metric: SimilarityMetric,
+    /// Abbreviations (short and full forms) expanded in pattern, e.g. `St. Petersburg` also
+    /// matches `Saint Petersburg`, [`DEFAULT_ABBREVIATIONS`] by default
+    pub abbreviations: &'a [(&'a str, &'a str)],
+    /// Also match historic names and suggest current cities, e.g. `Leningrad` suggests
+    /// Saint Petersburg (see [`EntryFilter::former_names`])
+    pub former_names: bool,
+    /// Retry empty result by transliteration of pattern and names to latin, e.g. `moskva`
+    /// matches `Москва` and `Беверли` matches `Beverley` (requires `translit` feature)
+    pub transliterate: bool,
+    /// Skip cities by geonameid, e.g. duplicates or known-bad records of the index (pass sorted
+    /// ids to avoid sorting them per query)
+    pub blocked_ids: &'a [u32],
+    /// Order of results, by similarity by default
+    pub ranking: SuggestRanking,
+    /// Accept names within this count of Levenshtein edits of pattern instead of `min_score`,
+    /// e.g. `1` for `pari` matches `bari` and `paris`, easier to reason about for short patterns
+    pub max_edits: Option<usize>,
+}
 
-    #[cfg(feature = "geoip2_support")]
-    #[serde(skip_serializing)]
-    geoip2_reader: Option<(&'static Vec<u8>, &'static Reader<'static, City<'static>>)>,
+impl<'a> SuggestOptions<'a> {
+    pub fn new(limit: usize) -> Self {
+        SuggestOptions {
+            limit,
+            min_score: None,
+            countries: None,
+            exclude_countries: None,
+            admin1: None,
+            min_population: None,
+            feature_codes: None,
+            lang: None,
+            bbox: None,
+            metric: SimilarityMetric::default(),
+            abbreviations: DEFAULT_ABBREVIATIONS,
+            former_names: false,
+            transliterate: false,
+            blocked_ids: &[],
+            ranking: SuggestRanking::Similarity,
+            max_edits: None,
+        }
+    }
 }
 
-pub fn skip_comment_lines(content: &str) -> String {
-    content.lines().filter(|l| !l.starts_with('#')).join("\n")
+/// 10 cities without filters
+impl Default for SuggestOptions<'_> {
+    fn default() -> Self {
+        SuggestOptions::new(10)
+    }
 }
 
-impl Engine {
-    pub fn get(&self, id: &u32) -> Option<&CitiesRecord> {
-        self.geonames.get(id)
+impl<'a, T: AsRef<str>> SuggestOptions<'a, T> {
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
     }
 
-    pub fn capital(&self, country_code: &str) -> Option<&CitiesRecord> {
-        if let Some(city_id) = self.capitals.get(&country_code.to_uppercase()) {
-            self.get(city_id)
-        } else {
-            None
-        }
+    pub fn min_score(mut self, min_score: f32) -> Self {
+        self.min_score = Some(min_score);
+        self
     }
 
-    /// Suggest cities by pattern (multilang).
+    /// Sets type of country codes, so set [`SuggestOptions::exclude_countries`] after it
     ///
-    /// Optional: filter by Jaro–Winkler distance via min_score
+    /// # Panics
     ///
-    /// Optional: prefilter by countries
-    pub fn suggest<T: AsRef<str>>(
-        &self,
-        pattern: &str,
-        limit: usize,
-        min_score: Option<f32>,
-        countries: Option<&[T]>,
-    ) -> Vec<&CitiesRecord> {
-        if limit == 0 {
-            return Vec::new();
+    /// Panics if excluded countries are already set
+    pub fn countries<C: AsRef<str>>(self, countries: &'a [C]) -> SuggestOptions<'a, C> {
+        self.with_countries(Some(countries))
+    }
+
+    /// Same options with countries filter of other type of country codes
+    fn with_countries<C: AsRef<str>>(self, countries: Option<&'a [C]>) -> SuggestOptions<'a, C> {
+        assert!(
+            self.exclude_countries.is_none(),
+            "countries are set after exclude_countries"
+        );
+        SuggestOptions {
+            limit: self.limit,
+            min_score: self.min_score,
+            countries,
+            exclude_countries: None,
+            admin1: self.admin1,
+            min_population: self.min_population,
+            feature_codes: self.feature_codes,
+            lang: self.lang,
+            bbox: self.bbox,
+            metric: self.metric,
+            abbreviations: self.abbreviations,
+            former_names: self.former_names,
+            transliterate: self.transliterate,
+            blocked_ids: self.blocked_ids,
+            ranking: self.ranking,
+            max_edits: self.max_edits,
         }
+    }
 
-        let min_score = min_score.unwrap_or(0.8);
-        let normalized_pattern = pattern.to_lowercase();
+    pub fn exclude_countries(mut self, countries: &'a [T]) -> Self {
+        self.exclude_countries = Some(countries);
+        self
+    }
 
-        let filter_by_pattern = |item: &Entry| -> Option<(&CitiesRecord, f32)> {
-            let score = if item.value.starts_with(&normalized_pattern) {
-                1.0
-            } else {
-                jaro_winkler(&item.value, &normalized_pattern) as f32
-            };
-            if score >= min_score {
-                self.geonames.get(&item.id).map(|city| (city, score))
-            } else {
-                None
-            }
-        };
+    pub fn blocked_ids(mut self, ids: &'a [u32]) -> Self {
+        self.blocked_ids = ids;
+        self
+    }
 
-        let mut result: Vec<(&CitiesRecord, f32)> = match &countries {
-            Some(countries) => {
-                let country_ids = countries
-                    .iter()
-                    .filter_map(|code| {
-                        self.country_info_by_code
-                            .get(&code.as_ref().to_uppercase())
-                            .map(|c| &c.info.geonameid)
-                    })
-                    .collect::<Vec<&u32>>();
-                self.entries
-                    .par_iter()
-                    .filter(|item| {
-                        if let Some(country_id) = &item.country_id {
-                            country_ids.contains(&country_id)
-                        } else {
-                            false
-                        }
-                    })
-                    .filter_map(filter_by_pattern)
-                    .collect()
-            }
-            None => self
-                .entries
-                .par_iter()
-                .filter_map(filter_by_pattern)
-                .collect(),
-        };
+    pub fn admin1(mut self, code: &'a str) -> Self {
+        self.admin1 = Some(code);
+        self
+    }
 
-        // sort by score desc, population desc
-        result.sort_unstable_by(|lhs, rhs| {
-            if (lhs.1 - rhs.1).abs() < f32::EPSILON {
-                rhs.0
-                    .population
-                    .partial_cmp(&lhs.0.population)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            } else {
-                rhs.1
-                    .partial_cmp(&lhs.1)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            }
-        });
+    pub fn min_population(mut self, min_population: u32) -> Self {
+        self.min_population = Some(min_population);
+        self
+    }
 
-        result
-            .iter()
-            .unique_by(|item| item.0.id)
-            .take(limit)
-            .map(|item| item.0)
-            .collect::<Vec<&CitiesRecord>>()
+    pub fn feature_codes(mut self, feature_codes: &'a [&'a str]) -> Self {
+        self.feature_codes = Some(feature_codes);
+        self
     }
 
-    /// Find the nearest cities by coordinates.
-    ///
-    /// Optional: score results by `k` as `distance - k * city.population` and sort by score.
-    ///
-    /// Optional: prefilter by countries. It's a very expensive case; consider building an index for concrete countries and not applying this filter at all.
-    pub fn reverse<T: AsRef<str>>(
-        &self,
-        loc: (f32, f32),
-        limit: usize,
-        k: Option<f32>,
-        countries: Option<&[T]>,
-    ) -> Option<Vec<ReverseItem>> {
-        if limit == 0 {
-            return None;
-        }
+    pub fn lang(mut self, lang: &'a str) -> Self {
+        self.lang = Some(lang);
+        self
+    }
 
-        let nearest_limit = std::num::NonZero::new(if countries.is_some() {
-            // ugly hack try to fetch nearest cities in requested countries
-            // much better is to build index for concrete countries
-            self.geonames.len()
-        } else {
-            limit
-        })?;
+    pub fn bbox(mut self, bbox: BBox) -> Self {
+        self.bbox = Some(bbox);
+        self
+    }
 
-        let mut i1;
-        let mut i2;
+    pub fn metric(mut self, metric: SimilarityMetric) -> Self {
+        self.metric = metric;
+        self
+    }
 
-        let items = &mut self
-            .tree
-            .nearest_n::<SquaredEuclidean>(&[loc.0, loc.1], nearest_limit);
+    pub fn abbreviations(mut self, abbreviations: &'a [(&'a str, &'a str)]) -> Self {
+        self.abbreviations = abbreviations;
+        self
+    }
 
-        let items: &mut dyn Iterator<Item = (_, &CitiesRecord)> = if let Some(countries) = countries
-        {
-            // normalize
-            let countries = countries
-                .iter()
-                .map(|code| code.as_ref().to_uppercase())
-                .collect::<Vec<_>>();
+    pub fn former_names(mut self, former_names: bool) -> Self {
+        self.former_names = former_names;
+        self
+    }
 
-            i1 = items.iter_mut().filter_map(move |nearest| {
-                let geonameid = self.tree_index_to_geonameid.get(&(nearest.item as usize))?;
-                let city = self.geonames.get(geonameid)?;
-                let country = city.country.as_ref()?;
-                if countries.contains(&country.code) {
-                    Some((nearest, city))
-                } else {
-                    None
-                }
-            });
-            &mut i1
-        } else {
-            i2 = items.iter_mut().filter_map(|nearest| {
-                let geonameid = self.tree_index_to_geonameid.get(&(nearest.item as usize))?;
-                let city = self.geonames.get(geonameid)?;
-                Some((nearest, city))
-            });
-            &mut i2
-        };
+    pub fn transliterate(mut self, transliterate: bool) -> Self {
+        self.transliterate = transliterate;
+        self
+    }
 
-        if let Some(k) = k {
-            let mut points = items
-                .map(|item| {
-                    (
-                        item.0.distance,
-                        item.0.distance - k * item.1.population as f32,
-                        item.1,
-                    )
-                })
-                .take(limit)
-                .collect::<Vec<_>>();
+    pub fn ranking(mut self, ranking: SuggestRanking) -> Self {
+        self.ranking = ranking;
+        self
+    }
 
-            points.sort_unstable_by(|a, b| {
-                a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal)
-            });
+    pub fn max_edits(mut self, max_edits: usize) -> Self {
+        self.max_edits = Some(max_edits);
+        self
+    }
+}
 
-            Some(
-                points
-                    .iter()
-                    .map(|p| ReverseItem {
-                        distance: p.0,
-                        score: p.1,
-                        city: p.2,
-                    })
-                    .collect(),
-            )
-        } else {
-            Some(
-                items
-                    .map(|item| ReverseItem {
-                        distance: item.0.distance,
-                        score: item.0.distance,
-                        city: item.1,
-                    })
-                    .take(limit)
-                    .collect(),
-            )
+/// Order of suggested cities, see [`SuggestOptions::ranking`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SuggestRanking {
+    /// By similarity, close scores are ordered by city prominence (population, capital status)
+    #[default]
+    Similarity,
+    /// By `alpha * similarity + beta * population`, where population is `ln(1 + population)`
+    /// scaled to `0..=1`, so big cities with typos in pattern outrank exactly matched villages
+    PopulationBlend { alpha: f32, beta: f32 },
+    /// By weights of profile (usually embedded into index, see
+    /// [`EngineMetadata::ranking_profiles`]), `bias` is `(lat, lng)` point of proximity bonus
+    Profile {
+        profile: RankingProfile,
+        bias: Option<(f32, f32)>,
+    },
+}
+
+/// Named weights of suggest ranking embedded into index at build time, so ranking changes are
+/// A/B tested by profile name of request without code deploys, see [`SuggestRanking::Profile`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingProfile {
+    /// Weight of similarity score
+    pub similarity: f32,
+    /// Weight of population, `ln(1 + population)` scaled to `0..=1`
+    pub population: f32,
+    /// Rank bonus of capitals (`PPLC`)
+    pub capital_boost: f32,
+    /// Distance (km) to bias point at which proximity bonus (up to 1) halves, 0 disables it
+    pub bias_distance: f32,
+}
+
+impl Default for RankingProfile {
+    fn default() -> Self {
+        RankingProfile {
+            similarity: 1.0,
+            population: 0.0,
+            capital_boost: 0.0,
+            bias_distance: 0.0,
         }
     }
+}
 
-    /// Get country info by iso 2-letter country code.
-    pub fn country_info(&self, country_code: &str) -> Option<&CountryRecord> {
-        self.country_info_by_code.get(&country_code.to_uppercase())
+impl RankingProfile {
+    /// Rank of city by its similarity score (cities are sorted by rank desc)
+    pub fn rank(&self, similarity: f32, city: &CitiesRecord, bias: Option<(f32, f32)>) -> f32 {
+        let mut rank =
+            self.similarity * similarity + self.population * population_weight(city.population);
+        if city.feature_code == "PPLC" {
+            rank += self.capital_boost;
+        }
+        if let Some(bias) = bias.filter(|_| self.bias_distance > 0.0) {
+            let distance = distance_km(bias, (city.latitude, city.longitude));
+            rank += self.bias_distance / (self.bias_distance + distance);
+        }
+        rank
     }
+}
 
-    pub fn new_from_files<P: AsRef<std::path::Path>>(
-        SourceFileOptions {
-            cities,
-            names,
-            countries,
-            filter_languages,
-            admin1_codes,
-            admin2_codes,
-        }: SourceFileOptions<P>,
-    ) -> Result<Self, Box<dyn Error>> {
-        Engine::new_from_files_content(SourceFileContentOptions {
+impl SuggestRanking {
+    /// Population blend of `0.8 * similarity + 0.2 * population`
+    pub const POPULATION_BLEND: SuggestRanking = SuggestRanking::PopulationBlend {
+        alpha: 0.8,
+        beta: 0.2,
+    };
+}
+
+/// String similarity of suggest pattern and index entries, all metrics are in `0..=1`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMetric {
+    /// Favors matching prefixes, good for short names
+    #[default]
+    JaroWinkler,
+    /// Normalized Levenshtein distance
+    Levenshtein,
+    /// Normalized Damerau–Levenshtein distance (transpositions are single edit)
+    NormalizedDamerau,
+    /// Sørensen–Dice coefficient of character bigrams, tolerant to words order
+    #[serde(rename = "ngram")]
+    NGram,
+}
+
+impl SimilarityMetric {
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        let score = match self {
+            SimilarityMetric::JaroWinkler => strsim::jaro_winkler(a, b),
+            SimilarityMetric::Levenshtein => strsim::normalized_levenshtein(a, b),
+            SimilarityMetric::NormalizedDamerau => strsim::normalized_damerau_levenshtein(a, b),
+            SimilarityMetric::NGram => strsim::sorensen_dice(a, b),
+        };
+        score as f32
+    }
+}
+
+impl std::str::FromStr for SimilarityMetric {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "jaro_winkler" => Ok(SimilarityMetric::JaroWinkler),
+            "levenshtein" => Ok(SimilarityMetric::Levenshtein),
+            "normalized_damerau" => Ok(SimilarityMetric::NormalizedDamerau),
+            "ngram" => Ok(SimilarityMetric::NGram),
+            _ => Err(format!("Unknown similarity metric: {value}")),
+        }
+    }
+}
+
+/// Score of the nearest cities by distance and population (lower is better), see
+/// [`ReverseOptions::scoring`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReverseScoring {
+    /// `distance - k * population` by squared degrees, applied when `k` is set; very sensitive
+    /// to `k`, megacities outrank everything around
+    #[default]
+    Linear,
+    /// `distance_m / ln(e + population)`, population matters by orders of magnitude, `k` is
+    /// not used
+    LogPopulation,
+    /// `distance_m² / (1 + population)`, inverse attraction of gravity model, `k` is not used
+    Gravity,
+}
+
+/// Options of [`Engine::reverse_with`]
+///
+/// ```
+/// # use geosuggest_core::ReverseOptions;
+/// let options = ReverseOptions::new(5).k(0.000000005).countries(&["RU"]);
+/// ```
+///
+/// New options may be added in minor releases, build it with [`ReverseOptions::new`] and
+/// the builder methods.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ReverseOptions<'a, T: AsRef<str> = &'a str> {
+    /// Max count of cities
+    pub limit: usize,
+    /// Score results as `distance - k * city.population`
+    pub k: Option<f32>,
+    /// Prefilter by countries codes
+    pub countries: Option<&'a [T]>,
+    /// Skip cities of countries codes
+    pub exclude_countries: Option<&'a [T]>,
+    /// Skip cities by geonameid, e.g. duplicates or known-bad records of the index (pass sorted
+    /// ids to avoid sorting them per query)
+    pub blocked_ids: &'a [u32],
+    /// Scale population term of `k` by local density of cities around the queried location
+    /// (precomputed by 1 degree cells), so the same `k` fits dense and sparse regions
+    pub adaptive_k: bool,
+    /// Nearest cities rescored by `k` (`limit` by default), wider pool lets populated cities
+    /// outrank nearer small ones; `limit` of them are returned
+    pub nearest_limit: Option<usize>,
+    /// Skip cities with smaller population, e.g. hamlets of `cities500` for "you are near X"
+    pub min_population: Option<u32>,
+    /// Score formula of the nearest cities (`nearest_limit` pool), alternatives to `k` apply
+    /// without it
+    pub scoring: ReverseScoring,
+}
+
+// not derived, borrowed country codes don't need to be `Clone`
+impl<T: AsRef<str>> Clone for ReverseOptions<'_, T> {
+    fn clone(&self) -> Self {
+        ReverseOptions { ..*self }
+    }
+}
+
+impl<'a> ReverseOptions<'a> {
+    pub fn new(limit: usize) -> Self {
+        ReverseOptions {
+            limit,
+            nearest_limit: None,
+            k: None,
+            countries: None,
+            exclude_countries: None,
+            blocked_ids: &[],
+            adaptive_k: false,
+            min_population: None,
+            scoring: ReverseScoring::Linear,
+        }
+    }
+}
+
+impl<'a, T: AsRef<str>> ReverseOptions<'a, T> {
+    pub fn k(mut self, k: f32) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    /// Sets type of country codes, so set [`ReverseOptions::exclude_countries`] after it
+    ///
+    /// # Panics
+    ///
+    /// Panics if excluded countries are already set
+    pub fn countries<C: AsRef<str>>(self, countries: &'a [C]) -> ReverseOptions<'a, C> {
+        self.with_countries(Some(countries))
+    }
+
+    /// Same options with countries filter of other type of country codes
+    fn with_countries<C: AsRef<str>>(self, countries: Option<&'a [C]>) -> ReverseOptions<'a, C> {
+        assert!(
+            self.exclude_countries.is_none(),
+            "countries are set after exclude_countries"
+        );
+        ReverseOptions {
+            limit: self.limit,
+            nearest_limit: self.nearest_limit,
+            k: self.k,
+            countries,
+            exclude_countries: None,
+            blocked_ids: self.blocked_ids,
+            adaptive_k: self.adaptive_k,
+            min_population: self.min_population,
+            scoring: self.scoring,
+        }
+    }
+
+    pub fn exclude_countries(mut self, countries: &'a [T]) -> Self {
+        self.exclude_countries = Some(countries);
+        self
+    }
+
+    pub fn blocked_ids(mut self, ids: &'a [u32]) -> Self {
+        self.blocked_ids = ids;
+        self
+    }
+
+    pub fn adaptive_k(mut self, adaptive_k: bool) -> Self {
+        self.adaptive_k = adaptive_k;
+        self
+    }
+
+    pub fn nearest_limit(mut self, nearest_limit: usize) -> Self {
+        self.nearest_limit = Some(nearest_limit);
+        self
+    }
+
+    pub fn min_population(mut self, min_population: u32) -> Self {
+        self.min_population = Some(min_population);
+        self
+    }
+
+    pub fn scoring(mut self, scoring: ReverseScoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+}
+
+/// Filters of [`Engine::within_radius`]
+///
+/// ```
+/// # use geosuggest_core::RadiusFilters;
+/// let filters = RadiusFilters::new().countries(&["RU"]).min_population(100_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RadiusFilters<'a, T: AsRef<str> = &'a str> {
+    /// Prefilter by countries codes
+    pub countries: Option<&'a [T]>,
+    /// Skip cities of countries codes
+    pub exclude_countries: Option<&'a [T]>,
+    /// Skip cities with smaller population
+    pub min_population: Option<u32>,
+    /// Skip cities by geonameid (pass sorted ids to avoid sorting them per query)
+    pub blocked_ids: &'a [u32],
+}
+
+impl<'a> RadiusFilters<'a> {
+    pub fn new() -> Self {
+        RadiusFilters {
+            countries: None,
+            exclude_countries: None,
+            min_population: None,
+            blocked_ids: &[],
+        }
+    }
+}
+
+impl Default for RadiusFilters<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: AsRef<str>> RadiusFilters<'a, T> {
+    /// Sets type of country codes, so set [`RadiusFilters::exclude_countries`] after it
+    ///
+    /// # Panics
+    ///
+    /// Panics if excluded countries are already set
+    pub fn countries<C: AsRef<str>>(self, countries: &'a [C]) -> RadiusFilters<'a, C> {
+        assert!(
+            self.exclude_countries.is_none(),
+            "countries are set after exclude_countries"
+        );
+        RadiusFilters {
+            countries: Some(countries),
+            exclude_countries: None,
+            min_population: self.min_population,
+            blocked_ids: self.blocked_ids,
+        }
+    }
+
+    pub fn exclude_countries(mut self, countries: &'a [T]) -> Self {
+        self.exclude_countries = Some(countries);
+        self
+    }
+
+    pub fn min_population(mut self, min_population: u32) -> Self {
+        self.min_population = Some(min_population);
+        self
+    }
+
+    pub fn blocked_ids(mut self, ids: &'a [u32]) -> Self {
+        self.blocked_ids = ids;
+        self
+    }
+}
+
+/// Center of points of [`Engine::reverse_centroid`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CentroidMode {
+    /// Geographic mean of points (antimeridian safe)
+    #[default]
+    Centroid,
+    /// Point minimizing total distance to other points (robust to outliers), `O(n^2)`
+    Medoid,
+    /// City minimizing total distance to all points instead of the nearest city to centroid,
+    /// results are sorted by total distance (km) as score, `k` of options is ignored
+    MinTotalDistance,
+}
+
+/// City population percentiles: share (percents) of indexed cities with the same or smaller population
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct PopulationRank {
+    /// Within city country
+    pub country: f32,
+    /// Within all indexed cities
+    pub global: f32,
+}
+
+/// Runtime corrections of loaded index, see [`Engine::apply_overrides`]
+///
+/// ```json
+/// {"cities": {"703448": {"name": "Kyiv", "names": {"en": "Kyiv"}}, "524901": {"suppress": true}}}
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Overrides {
+    /// Overrides by city geonameid
+    #[serde(default)]
+    pub cities: HashMap<u32, CityOverride>,
+}
+
+impl Overrides {
+    /// Read overrides from JSON file.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct CityOverride {
+    /// New name (previous name stays searchable)
+    pub name: Option<String>,
+    /// New or replaced translations by isolanguage code
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+    /// Corrected population
+    pub population: Option<u32>,
+    /// Exclude city from all queries
+    #[serde(default)]
+    pub suppress: bool,
+}
+
+/// Aggregates of country indexed cities
+#[derive(Debug, Serialize)]
+pub struct CountryStats<'a> {
+    /// Count of indexed cities
+    pub cities: usize,
+    /// Total population of indexed cities
+    pub population: u64,
+    /// Largest cities (population desc)
+    pub largest_cities: Vec<&'a CitiesRecord>,
+    /// Count of cities with name translation by language
+    pub translations: HashMap<String, usize>,
+}
+
+/// Admin division with translations and containing country
+#[derive(Debug, Serialize)]
+pub struct AdminDivisionInfo<'a> {
+    pub division: &'a AdminDivision,
+    pub names: Option<&'a HashMap<String, String>>,
+    pub country: Option<&'a CountryRecord>,
+}
+
+/// Country info with resolved capital city
+#[derive(Debug, Serialize)]
+pub struct CountryInfo<'a> {
+    pub country: &'a CountryRecord,
+    pub capital: Option<&'a CitiesRecord>,
+}
+
+/// Suggested city with similarity score, see [`Engine::suggest_with_scores`]
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct SuggestItem<'a> {
+    pub city: &'a CitiesRecord,
+    pub score: f32,
+    /// Best matched normalized name or alternate name, e.g. `воронеж`
+    pub matched: &'a str,
+    /// [`SuggestItem::matched`] as in source (original casing), e.g. `Воронеж`
+    pub matched_name: &'a str,
+}
+
+/// Incremental suggest of typeahead input, see [`Engine::suggest_session`].
+///
+/// Entries matched by the previous pattern are kept, so when the pattern is extended by typed
+/// chars only these candidates are scored instead of all entries. Other patterns (erased or
+/// pasted chars) rescan all entries.
+///
+/// Prefix matches are the same as of full scan, but fuzzy matches are refined approximately:
+/// cities similar only to the longer pattern are missed until [`SuggestSession::reset`].
+pub struct SuggestSession<'a, T: AsRef<str> + Clone = &'a str> {
+    engine: &'a Engine,
+    options: SuggestOptions<'a, T>,
+    /// Normalized pattern of previous call
+    pattern: String,
+    /// Indexes of entries matched by previous pattern
+    candidates: Option<Vec<usize>>,
+}
+
+impl<'a, T: AsRef<str> + Clone> SuggestSession<'a, T> {
+    /// Suggest cities by current input
+    pub fn suggest(&mut self, pattern: &str) -> Vec<SuggestItem<'a>> {
+        let normalized_pattern = normalize::normalize(pattern, self.engine.case_folding);
+        let refine = !self.pattern.is_empty() && normalized_pattern.starts_with(&self.pattern);
+        let candidates = self.candidates.as_deref().filter(|_| refine);
+
+        let now = Instant::now();
+        let (items, matched) =
+            self.engine
+                .find_suggest_in(pattern, self.options.clone(), candidates);
+
+        if let Some(hook) = &self.engine.query_hook {
+            hook.on_query(&QueryEvent {
+                kind: "suggest",
+                pattern: Some(pattern),
+                loc: None,
+                countries: self
+                    .options
+                    .countries
+                    .map(|c| c.iter().map(AsRef::as_ref).collect()),
+                bbox: self.options.bbox,
+                limit: self.options.limit,
+                results: items.len(),
+                elapsed: now.elapsed(),
+            });
+        }
+
+        self.pattern = normalized_pattern;
+        self.candidates = Some(matched);
+        items
+    }
+
+    /// Forget candidates, next call scans all entries
+    pub fn reset(&mut self) {
+        self.pattern.clear();
+        self.candidates = None;
+    }
+}
+
+/// Weights of place kinds (multiplied by similarity score) in [`Engine::suggest_places`],
+/// zero weight excludes the kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaceWeights {
+    pub city: f32,
+    pub admin1: f32,
+    /// Second level admin divisions, excluded by default (many of them are named as their cities)
+    pub admin2: f32,
+    pub country: f32,
+}
+
+impl Default for PlaceWeights {
+    fn default() -> Self {
+        PlaceWeights {
+            city: 1.0,
+            admin1: 1.0,
+            admin2: 0.0,
+            country: 1.0,
+        }
+    }
+}
+
+impl PlaceWeights {
+    /// Equal weights of `kinds`, other kinds are excluded, e.g. countries and admin divisions
+    /// autocompletion of address forms
+    ///
+    /// ```
+    /// # use geosuggest_core::{PlaceKind, PlaceWeights};
+    /// let weights = PlaceWeights::only(&[PlaceKind::Country, PlaceKind::Admin1]);
+    /// assert_eq!(weights.city, 0.0);
+    /// ```
+    pub fn only(kinds: &[PlaceKind]) -> Self {
+        let weight = |kind| if kinds.contains(&kind) { 1.0 } else { 0.0 };
+        PlaceWeights {
+            city: weight(PlaceKind::City),
+            admin1: weight(PlaceKind::Admin1),
+            admin2: weight(PlaceKind::Admin2),
+            country: weight(PlaceKind::Country),
+        }
+    }
+}
+
+/// Kind of [`Place`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceKind {
+    City,
+    Admin1,
+    Admin2,
+    Country,
+}
+
+impl std::str::FromStr for PlaceKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "city" => Ok(PlaceKind::City),
+            "admin1" => Ok(PlaceKind::Admin1),
+            "admin2" => Ok(PlaceKind::Admin2),
+            "country" => Ok(PlaceKind::Country),
+            _ => Err(format!("Unknown place kind: {value}")),
+        }
+    }
+}
+
+/// Suggested place of any kind, see [`Engine::suggest_places`]
+///
+/// New kinds of places may be added in minor releases.
+#[derive(Debug, Serialize)]
+#[non_exhaustive]
+pub enum Place<'a> {
+    City(&'a CitiesRecord),
+    Admin1(AdminDivisionInfo<'a>),
+    Admin2(AdminDivisionInfo<'a>),
+    Country(&'a CountryRecord),
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaceItem<'a> {
+    pub place: Place<'a>,
+    /// Similarity score multiplied by weight of place kind
+    pub score: f32,
+}
+
+/// Found city of [`Engine::reverse_with`], construct it with [`ReverseItem::new`]
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+#[non_exhaustive]
+pub struct ReverseItem<'a> {
+    pub city: &'a CitiesRecord,
+    /// Squared euclidean distance in degrees, longitude is wrapped at antimeridian (metric of
+    /// `k` score)
+    pub distance: f32,
+    /// Great-circle distance in meters
+    pub distance_m: f32,
+    /// Rank of city, lower is better, unit depends on [`ReverseScoring`]: squared degrees for
+    /// `Linear` (`distance` without `k`), meters for `LogPopulation` and squared meters per
+    /// inhabitant for `Gravity`
+    pub score: f32,
+}
+
+impl<'a> ReverseItem<'a> {
+    /// Reverse result for implementors of [`GeoEngine`]
+    pub fn new(city: &'a CitiesRecord, distance: f32, distance_m: f32, score: f32) -> Self {
+        ReverseItem {
+            city,
+            distance,
+            distance_m,
+            score,
+        }
+    }
+}
+
+/// Reverse results of one admin division (first level), see [`rollup_admin1`]
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct Admin1ReverseItem<'a> {
+    /// `None` for cities without admin division
+    pub division: Option<&'a AdminDivision>,
+    /// The best scored city of division
+    pub city: &'a CitiesRecord,
+    pub distance: f32,
+    /// Great-circle distance in meters
+    pub distance_m: f32,
+    pub score: f32,
+    /// Combined population of division cities within reverse results
+    pub population: u64,
+    /// Count of division cities within reverse results
+    pub cities: usize,
+}
+
+/// Aggregate reverse results by admin division (first level): each division is returned once
+/// with its best scored city, in order of reverse results.
+pub fn rollup_admin1<'a>(items: &[ReverseItem<'a>]) -> Vec<Admin1ReverseItem<'a>> {
+    let mut result: Vec<Admin1ReverseItem> = Vec::new();
+    for item in items {
+        let division = item.city.admin_division.as_ref();
+        let id = division.map(|d| d.id);
+        match result.iter_mut().find(|r| r.division.map(|d| d.id) == id) {
+            Some(rollup) => {
+                rollup.population += item.city.population as u64;
+                rollup.cities += 1;
+            }
+            None => result.push(Admin1ReverseItem {
+                division,
+                city: item.city,
+                distance: item.distance,
+                distance_m: item.distance_m,
+                score: item.score,
+                population: item.city.population as u64,
+                cities: 1,
+            }),
+        }
+    }
+    result
+}
+
+/// Admin division (second level, county) of the reverse point by votes of reverse results,
+/// so a nearest city just across the border doesn't decide the county alone.
+///
+/// Each city votes for its division by inverse great-circle distance (1 km floor), the nearest
+/// city of the winning division is returned, `None` when no city has second level division.
+pub fn vote_admin2<'a>(items: &[ReverseItem<'a>]) -> Option<&'a CitiesRecord> {
+    // division id, votes, nearest city and its distance
+    let mut votes: Vec<(u32, f32, &CitiesRecord, f32)> = Vec::new();
+    for item in items {
+        let Some(division) = item.city.admin2_division.as_ref() else {
+            continue;
+        };
+        let weight = 1.0 / (item.distance_m / 1000.0).max(1.0);
+        match votes.iter_mut().find(|vote| vote.0 == division.id) {
+            Some(vote) => {
+                vote.1 += weight;
+                if item.distance_m < vote.3 {
+                    (vote.2, vote.3) = (item.city, item.distance_m);
+                }
+            }
+            None => votes.push((division.id, weight, item.city, item.distance_m)),
+        }
+    }
+    votes
+        .into_iter()
+        .reduce(|best, vote| if vote.1 > best.1 { vote } else { best })
+        .map(|vote| vote.2)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EngineSourceMetadata {
+    pub cities: String,
+    pub names: Option<String>,
+    pub countries: Option<String>,
+    pub admin1_codes: Option<String>,
+    pub admin2_codes: Option<String>,
+    pub filter_languages: Vec<String>,
+    #[serde(serialize_with = "ordered::map")]
+    pub etag: HashMap<String, String>,
+}
+
+/// Version of index layout, index built with other version can't be loaded.
+/// Bincode reads fields by position, so bump it on any change of stored structs
+/// (`EngineDump`, [`EngineMetadata`], [`CitiesRecord`], etc.)
+pub const INDEX_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineMetadata {
+    /// Index was built on version
+    pub geosuggest_version: String,
+    /// Index was built with layout version, see [`INDEX_FORMAT_VERSION`]
+    pub index_format_version: u32,
+    /// Search entries were normalized with rules version, see [`NORMALIZATION_VERSION`]
+    pub normalization_version: u32,
+    /// Search entries were built with features, see [`NORMALIZATION_FEATURES`]
+    pub normalization_features: Vec<String>,
+    /// Creation time
+    pub created_at: std::time::SystemTime,
+    /// Sources metadata
+    pub source: EngineSourceMetadata,
+    /// Custom metadata info
+    #[serde(serialize_with = "ordered::map")]
+    pub extra: HashMap<String, String>,
+    /// Rows failed to parse by source (`cities`, `names`, `countries`, `admin1_codes`, `admin2_codes`)
+    #[serde(serialize_with = "ordered::map")]
+    pub malformed_rows: HashMap<String, MalformedRows>,
+    /// Country code when all indexed cities belong to one country
+    /// (countries filters are resolved without scanning)
+    pub single_country: Option<String>,
+    /// Invalid or canonicalized `filter_languages` tags, see [`canonical_language`]
+    pub language_warnings: Vec<String>,
+    /// Cities rows dropped as duplicates of geonameid, see [`DuplicatePolicy`]
+    pub duplicate_rows: usize,
+    /// Suggest ranking profiles by name, see [`Engine::ranking_profile`]
+    #[serde(serialize_with = "ordered::map")]
+    pub ranking_profiles: HashMap<String, RankingProfile>,
+    /// Duration of the last index load, see [`storage::IndexStorage::load_from`] (not stored in index)
+    #[serde(skip)]
+    pub load_duration: Option<Duration>,
+}
+
+const MALFORMED_ROWS_SAMPLES: usize = 10;
+
+/// Count and first errors of source rows failed to parse
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MalformedRows {
+    pub count: usize,
+    pub samples: Vec<String>,
+}
+
+impl MalformedRows {
+    fn push(&mut self, error: impl std::fmt::Display) {
+        self.count += 1;
+        if self.samples.len() < MALFORMED_ROWS_SAMPLES {
+            self.samples.push(error.to_string());
+        }
+    }
+
+    fn merge(mut self, other: MalformedRows) -> Self {
+        self.count += other.count;
+        self.samples.extend(other.samples);
+        self.samples.truncate(MALFORMED_ROWS_SAMPLES);
+        self
+    }
+}
+
+impl Default for EngineMetadata {
+    fn default() -> Self {
+        Self {
+            created_at: std::time::SystemTime::now(),
+            geosuggest_version: env!("CARGO_PKG_VERSION").to_owned(),
+            index_format_version: INDEX_FORMAT_VERSION,
+            normalization_version: NORMALIZATION_VERSION,
+            normalization_features: NORMALIZATION_FEATURES
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            source: EngineSourceMetadata::default(),
+            extra: HashMap::default(),
+            malformed_rows: HashMap::default(),
+            single_country: None,
+            language_warnings: Vec::new(),
+            duplicate_rows: 0,
+            ranking_profiles: HashMap::default(),
+            load_duration: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EngineDump {
+    entries: Vec<Entry>,
+    former_entries: Vec<Entry>,
+    translit_entries: Vec<Entry>,
+    display_names: HashMap<u32, Vec<String>>,
+    languages: Vec<String>,
+    geonames: HashMap<u32, CitiesRecord>,
+    capitals: HashMap<String, u32>,
+    country_info_by_code: HashMap<String, CountryRecord>,
+    admin_names: HashMap<u32, HashMap<String, String>>,
+    admin1_divisions: HashMap<String, AdminDivision>,
+    admin2_divisions: HashMap<String, AdminDivision>,
+    compressed_names: HashMap<u32, Vec<u8>>,
+    population_ranks: HashMap<u32, PopulationRank>,
+    density_scales: HashMap<u32, f32>,
+    case_folding: CaseFolding,
+    metadata: Option<EngineMetadata>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    id: u32,                 // geoname id
+    value: String,           // searchable value
+    display: Option<u32>,    // city display name index (original casing), none if same as value
+    country_id: Option<u32>, // geoname country id
+    admin1_id: Option<u32>,  // geoname admin division (first level) id
+    langs: u64,              // alternate name languages, see `Engine::languages`, zero if default
+    prominence: f32,         // static rank of city and value, see `entry_prominence`
+}
+
+/// Hash maps are serialized in key order, so dumps of the same index are byte-identical,
+/// see [`Engine::make_reproducible`].
+#[derive(Serialize)]
+pub struct Engine {
+    entries: Vec<Entry>,
+    /// Historic names, searched only on demand (out of live entries)
+    former_entries: Vec<Entry>,
+    /// Latin transliteration of non-latin entries, searched only by fallback of
+    /// [`SuggestOptions::transliterate`]
+    translit_entries: Vec<Entry>,
+    /// Values of entries as in source (original casing) by city, shared by entries of the same
+    /// spelling (e.g. alternate name of several languages)
+    #[serde(serialize_with = "ordered::map")]
+    display_names: HashMap<u32, Vec<String>>,
+    /// Languages of alternate names (unknown one is empty), bit of entry languages mask by index
+    languages: Vec<String>,
+    #[serde(serialize_with = "ordered::map")]
+    geonames: HashMap<u32, CitiesRecord>,
+    #[serde(serialize_with = "ordered::map")]
+    capitals: HashMap<String, u32>,
+    #[serde(serialize_with = "ordered::map")]
+    country_info_by_code: HashMap<String, CountryRecord>,
+    /// Translations of admin divisions (shared by all cities of division)
+    #[serde(serialize_with = "ordered::nested_map")]
+    admin_names: HashMap<u32, HashMap<String, String>>,
+    /// Admin divisions (of indexed cities) by code, e.g. `US.CA`
+    #[serde(serialize_with = "ordered::map")]
+    admin1_divisions: HashMap<String, AdminDivision>,
+    /// Second level admin divisions (of indexed cities) by code, e.g. `US.CA.037`
+    #[serde(serialize_with = "ordered::map")]
+    admin2_divisions: HashMap<String, AdminDivision>,
+    /// Cities translations moved out by [`Engine::compress_names`] (lz4 compressed)
+    #[serde(serialize_with = "ordered::map")]
+    compressed_names: HashMap<u32, Vec<u8>>,
+    /// Population percentiles of cities, see [`Engine::population_rank`]
+    #[serde(serialize_with = "ordered::map")]
+    population_ranks: HashMap<u32, PopulationRank>,
+    /// Population term scales of reverse by grid cells, see [`ReverseOptions::adaptive_k`]
+    #[serde(serialize_with = "ordered::map")]
+    density_scales: HashMap<u32, f32>,
+    /// Case folding rules of entries and patterns
+    case_folding: CaseFolding,
+    pub metadata: Option<EngineMetadata>,
+
+    #[serde(skip_serializing)]
+    tree_index_to_geonameid: HashMap<usize, u32>,
+    #[serde(skip_serializing)]
+    tree: ImmutableKdTree<f32, u32, 2, 32>,
+    /// Normalized names and translations of admin divisions and countries by geonameid,
+    /// see [`Engine::suggest_places`]
+    #[serde(skip_serializing)]
+    place_names: HashMap<u32, Vec<String>>,
+    /// Compact code (first) and abbreviations of admin divisions (first level) by geonameid,
+    /// see [`Engine::find_admin1`]
+    #[serde(skip_serializing)]
+    admin1_abbreviations: HashMap<u32, Vec<String>>,
+    /// Memory usage without per-country kd-trees, see [`Engine::memory_usage`]
+    #[serde(skip_serializing)]
+    memory_usage: MemoryUsage,
+    /// See [`Engine::language_coverage`]
+    #[serde(skip_serializing)]
+    language_coverage: HashMap<String, usize>,
+    /// Kd-trees of cities by country code, built by [`Engine::warm_up`] or on first country
+    /// filtered reverse
+    #[serde(skip_serializing)]
+    country_trees: OnceLock<HashMap<String, CountryTree>>,
+    /// Country code when all indexed cities belong to one country
+    #[serde(skip_serializing)]
+    single_country: Option<String>,
+    /// See [`Engine::set_query_hook`]
+    #[serde(skip_serializing)]
+    query_hook: Option<Arc<dyn QueryHook>>,
+    /// See [`Engine::set_ranker`]
+    #[serde(skip_serializing)]
+    ranker: Option<Arc<dyn Ranker>>,
+
+    #[cfg(feature = "geoip2_support")]
+    #[serde(skip_serializing)]
+    geoip2_reader: Option<(&'static Vec<u8>, &'static Reader<'static, City<'static>>)>,
+    /// Bounded cache of ip -> geonameid lookups, see [`Engine::set_geoip2_cache`]
+    #[cfg(feature = "geoip2_support")]
+    #[serde(skip_serializing)]
+    geoip2_cache: Option<Mutex<LruCache<IpAddr, Option<u32>>>>,
+    /// See [`Engine::geoip2_cache_hits`]
+    #[cfg(feature = "geoip2_support")]
+    #[serde(skip_serializing)]
+    geoip2_cache_hits: AtomicU64,
+}
+
+/// Kd-tree of country cities, items are indexes of the whole tree
+struct CountryTree {
+    indexes: Vec<u32>,
+    tree: ImmutableKdTree<f32, u32, 2, 32>,
+}
+
+/// Filters of reverse resolved once per query or batch, see [`Engine::reverse_batch`]
+struct ReverseFilter<'a> {
+    /// Normalized codes of requested countries
+    countries: Option<Vec<String>>,
+    /// Normalized codes of excluded countries
+    excluded: Option<Vec<String>>,
+    /// Sorted, see [`sorted_ids`]
+    blocked_ids: Cow<'a, [u32]>,
+    min_population: Option<u32>,
+}
+
+/// Scoring options of reverse
+struct ReverseScore {
+    k: Option<f32>,
+    adaptive_k: bool,
+    scoring: ReverseScoring,
+}
+
+// guard against f32 rounding on the bounding box corners
+const BBOX_RADIUS_MARGIN: f32 = 1.001;
+
+// mean earth radius based
+const KM_PER_DEGREE: f32 = 111.195;
+
+// cities around centroid checked by `CentroidMode::MinTotalDistance`
+const CENTROID_CANDIDATES: usize = 50;
+
+// sampled cities and results per probe of `Engine::verify_against`
+const VERIFY_PROBES: usize = 100;
+const VERIFY_LIMIT: usize = 5;
+
+// weight of entry prominence in suggest ranking, keeps it below noticeable similarity difference
+const PROMINENCE_WEIGHT: f32 = 0.01;
+
+// ln(1 + population) of the largest cities
+const MAX_POPULATION_LN: f32 = 17.5;
+
+/// Great-circle distance of `(lat, lng)` point to city in meters
+fn distance_m(loc: (f32, f32), city: &CitiesRecord) -> f32 {
+    distance_km(loc, (city.latitude, city.longitude)) * 1000.0
+}
+
+/// Squared euclidean distance of `(lat, lng)` point to city in degrees, longitude is wrapped
+/// at antimeridian
+fn degrees_squared(loc: (f32, f32), city: &CitiesRecord) -> f32 {
+    let dlng = (city.longitude - loc.1).abs() % 360.0;
+    (city.latitude - loc.0).powi(2) + dlng.min(360.0 - dlng).powi(2)
+}
+
+/// Great-circle distance of `(lat, lng)` points
+fn distance_km(a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (lat1, lat2) = ((a.0 as f64).to_radians(), (b.0 as f64).to_radians());
+    let dlat = lat2 - lat1;
+    let dlng = ((b.1 - a.1) as f64).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+    (2.0 * h.sqrt().min(1.0).asin() * (KM_PER_DEGREE as f64).to_degrees()) as f32
+}
+
+/// Geographic mean of `(lat, lng)` points by their unit vectors, `None` when undefined
+/// (no points or points cancel each other out, e.g. antipodes).
+fn centroid(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    let (x, y, z) = points.iter().fold((0.0, 0.0, 0.0), |(x, y, z), p| {
+        let (lat, lng) = ((p.0 as f64).to_radians(), (p.1 as f64).to_radians());
+        (
+            x + lat.cos() * lng.cos(),
+            y + lat.cos() * lng.sin(),
+            z + lat.sin(),
+        )
+    });
+    if x.hypot(y).hypot(z) < 1e-9 {
+        return None;
+    }
+    Some((
+        z.atan2(x.hypot(y)).to_degrees() as f32,
+        y.atan2(x).to_degrees() as f32,
+    ))
+}
+
+/// City population in `0..=1` by log scale
+fn population_weight(population: u32) -> f32 {
+    ((population as f32).ln_1p() / MAX_POPULATION_LN).min(1.0)
+}
+
+/// Max count of entry languages (bits of [`Entry::langs`])
+const MAX_LANGUAGES: usize = 64;
+
+/// Mask of language in entry languages, zero if language isn't interned
+fn language_mask(languages: &[String], lang: &str) -> u64 {
+    languages
+        .iter()
+        .position(|l| l == lang)
+        .map_or(0, |index| 1 << index)
+}
+
+/// Mask of language in entry languages, language is interned if there is room for it
+/// (otherwise entry is treated as default name of any language)
+fn intern_language(languages: &mut Vec<String>, lang: &str) -> u64 {
+    if !languages.iter().any(|l| l == lang) && languages.len() < MAX_LANGUAGES {
+        languages.push(lang.to_owned());
+    }
+    language_mask(languages, lang)
+}
+
+/// Merge city entries (pushed from `first`) of the same spelling, their languages are joined
+fn merge_entries(entries: &mut Vec<Entry>, first: usize) {
+    let mut index = first + 1;
+    while index < entries.len() {
+        let entry = &entries[index];
+        match entries[first..index]
+            .iter()
+            .position(|e| e.value == entry.value && e.display == entry.display)
+        {
+            Some(position) => {
+                let langs = entries.remove(index).langs;
+                let merged = &mut entries[first + position];
+                // default name matches any language
+                merged.langs = if merged.langs == 0 || langs == 0 {
+                    0
+                } else {
+                    merged.langs | langs
+                };
+            }
+            None => index += 1,
+        }
+    }
+}
+
+/// Push transliterated copies of non-latin city `entries`, matched names of copies are
+/// spellings of the original entries
+#[cfg(feature = "translit")]
+fn push_translit_entries(
+    entries: &[Entry],
+    displays: &mut Vec<String>,
+    translit_entries: &mut Vec<Entry>,
+) {
+    let first = translit_entries.len();
+    for entry in entries {
+        let Cow::Owned(value) = normalize::transliterate(&entry.value) else {
+            continue;
+        };
+        let display = entry
+            .display
+            .unwrap_or_else(|| display_index(displays, &entry.value));
+        translit_entries.push(Entry {
+            value,
+            display: Some(display),
+            ..entry.clone()
+        });
+    }
+    merge_entries(translit_entries, first);
+}
+
+/// Normalized value without dots and spaces, abbreviations and codes are compared so,
+/// e.g. `n.y.` is `ny`
+fn compact_token(value: &str, case_folding: CaseFolding) -> String {
+    normalize::normalize(value, case_folding)
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .collect()
+}
+
+/// Index of spelling in city display names, entries of the same spelling share it
+fn display_index(displays: &mut Vec<String>, display: &str) -> u32 {
+    let index = displays
+        .iter()
+        .position(|d| d == display)
+        .unwrap_or_else(|| {
+            displays.push(display.to_owned());
+            displays.len() - 1
+        });
+    index as u32
+}
+
+/// Static rank of search entry in `0..=1` by city population, capital or admin seat status
+/// and value length (shorter is better), precomputed on index build.
+fn entry_prominence(population: u32, feature_code: &str, value: &str) -> f32 {
+    let population = population_weight(population);
+    let seat = match feature_code {
+        "PPLC" => 1.0,
+        "PPLG" | "PPLA" => 0.5,
+        "PPLA2" => 0.25,
+        _ => 0.0,
+    };
+    let length = 1.0 - value.chars().count().min(50) as f32 / 50.0;
+    0.7 * population + 0.2 * seat + 0.1 * length
+}
+
+/// Build kd-tree of `(geonameid, [lat, lng])` items and tree index to geonameid map in parallel.
+///
+/// Items are ordered by geonameid, so tree indexes are deterministic for the same cities,
+/// see [`Engine::tree_ids`].
+fn build_tree(
+    mut items: Vec<(u32, [f32; 2])>,
+) -> (HashMap<usize, u32>, ImmutableKdTree<f32, u32, 2, 32>) {
+    items.par_sort_unstable_by_key(|item| item.0);
+    items.dedup_by_key(|item| item.0);
+
+    rayon::join(
+        || {
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| (index, item.0))
+                .collect()
+        },
+        || {
+            ImmutableKdTree::new_from_slice(
+                items
+                    .par_iter()
+                    .map(|item| item.1)
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )
+        },
+    )
+}
+
+/// Bounding boxes of H3 cell vertices: cell crossing the antimeridian is split by it into two
+/// boxes, cell of a pole spans all longitudes up to the pole.
+#[cfg(feature = "h3")]
+fn h3_bboxes(cell: h3o::CellIndex) -> Vec<BBox> {
+    let points = cell
+        .boundary()
+        .iter()
+        .map(|v| (v.lat() as f32, v.lng() as f32))
+        .collect::<Vec<_>>();
+    let bbox = |points: &mut dyn Iterator<Item = &(f32, f32)>| {
+        let (lat, lng) = points.next()?;
+        let mut bbox = BBox::from_point(*lat, *lng);
+        for (lat, lng) in points {
+            bbox.extend(*lat, *lng);
+        }
+        bbox.min_lat = bbox.min_lat.max(-90.0);
+        bbox.max_lat = bbox.max_lat.min(90.0);
+        Some(bbox)
+    };
+    let Some(mut all) = bbox(&mut points.iter()) else {
+        return Vec::new();
+    };
+
+    let is_pole = |lat: f64| {
+        h3o::LatLng::new(lat, 0.0).is_ok_and(|pole| pole.to_cell(cell.resolution()) == cell)
+    };
+    if is_pole(90.0) || is_pole(-90.0) {
+        if is_pole(90.0) {
+            all.max_lat = 90.0;
+        }
+        if is_pole(-90.0) {
+            all.min_lat = -90.0;
+        }
+        all.min_lng = -180.0;
+        all.max_lng = 180.0;
+        return vec![all];
+    }
+
+    if all.max_lng - all.min_lng <= 180.0 {
+        return vec![all];
+    }
+    // vertices on both sides of the antimeridian
+    let east = bbox(&mut points.iter().filter(|(_, lng)| *lng >= 0.0));
+    let west = bbox(&mut points.iter().filter(|(_, lng)| *lng < 0.0));
+    east.map(|bbox| BBox {
+        max_lng: 180.0,
+        ..bbox
+    })
+    .into_iter()
+    .chain(west.map(|bbox| BBox {
+        min_lng: -180.0,
+        ..bbox
+    }))
+    .collect()
+}
+
+/// Kd-trees of cities by country code, items of trees are indexes of the whole tree
+fn build_country_trees(
+    tree_index_to_geonameid: &HashMap<usize, u32>,
+    geonames: &HashMap<u32, CitiesRecord>,
+) -> HashMap<String, CountryTree> {
+    let mut items = HashMap::<&str, Vec<(u32, [f32; 2])>>::new();
+    for (index, geonameid) in tree_index_to_geonameid {
+        let Some(city) = geonames.get(geonameid) else {
+            continue;
+        };
+        let Some(country) = city.country.as_ref() else {
+            continue;
+        };
+        items
+            .entry(country.code.as_str())
+            .or_default()
+            .push((*index as u32, [city.latitude, city.longitude]));
+    }
+
+    items
+        .into_par_iter()
+        .map(|(code, mut items)| {
+            items.sort_unstable_by_key(|item| item.0);
+            let tree = ImmutableKdTree::new_from_slice(
+                items
+                    .iter()
+                    .map(|item| item.1)
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            );
+            let indexes = items.into_iter().map(|item| item.0).collect();
+            (code.to_owned(), CountryTree { indexes, tree })
+        })
+        .collect()
+}
+
+/// Items of kd-tree (in degrees) within great-circle `radius_m` of `loc`, and some more
+/// outside of it (the caller checks exact distance).
+///
+/// The query circle covers the widest parallel of the radius (longitude degrees shrink towards
+/// poles) and is repeated on the other side of antimeridian when it's crossed.
+fn tree_within(
+    tree: &ImmutableKdTree<f32, u32, 2, 32>,
+    loc: (f32, f32),
+    radius_m: f32,
+) -> Vec<u32> {
+    let lat_pad = radius_m / 1000.0 / KM_PER_DEGREE;
+    let max_lat = loc.0.abs() + lat_pad;
+    let lng_pad = if max_lat >= 90.0 {
+        180.0
+    } else {
+        (lat_pad / max_lat.to_radians().cos()).min(180.0)
+    };
+    let radius = if max_lat >= 90.0 {
+        f32::MAX
+    } else {
+        lng_pad.powi(2) * BBOX_RADIUS_MARGIN + f32::EPSILON
+    };
+
+    let mut centers = vec![loc.1];
+    if loc.1 - lng_pad < -180.0 {
+        centers.push(loc.1 + 360.0);
+    }
+    if loc.1 + lng_pad > 180.0 {
+        centers.push(loc.1 - 360.0);
+    }
+
+    let mut seen = HashSet::new();
+    centers
+        .into_iter()
+        .flat_map(|lng| tree.within::<SquaredEuclidean>(&[loc.0, lng], radius))
+        .map(|nearest| nearest.item)
+        .filter(|item| seen.insert(*item))
+        .collect()
+}
+
+/// Geonameids to look up by binary search, sorted ones (e.g. parsed once by caller) are used as is
+fn sorted_ids(ids: &[u32]) -> Cow<'_, [u32]> {
+    if ids.is_sorted() {
+        Cow::Borrowed(ids)
+    } else {
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        Cow::Owned(ids)
+    }
+}
+
+fn single_country<'a>(mut cities: impl Iterator<Item = &'a CitiesRecord>) -> Option<String> {
+    let code = &cities.next()?.country.as_ref()?.code;
+    cities
+        .all(|city| city.country.as_ref().is_some_and(|c| &c.code == code))
+        .then(|| code.to_owned())
+}
+
+fn decompress_names(compressed: &[u8]) -> Result<HashMap<String, String>, String> {
+    lz4_flex::decompress_size_prepended(compressed)
+        .map_err(|e| e.to_string())
+        .and_then(|data| bincode::deserialize(&data).map_err(|e| e.to_string()))
+}
+
+/// Canonical unique language codes, invalid tags are dropped
+fn canonical_languages(tags: &[&str], warnings: &mut Vec<String>) -> Vec<String> {
+    let mut languages: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let lang = match canonical_language(tag) {
+            Ok(lang) => lang,
+            Err(e) => {
+                warnings.push(e);
+                continue;
+            }
+        };
+        if lang != *tag {
+            warnings.push(format!("Language tag {tag:?} is canonicalized to {lang:?}"));
+        }
+        if !languages.iter().any(|l| *l == lang) {
+            languages.push(lang.into_owned());
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    for warning in warnings.iter() {
+        tracing::warn!("{warning}");
+    }
+
+    languages
+}
+
+/// Cell of 1 degree grid of location
+fn density_cell(lat: f32, lng: f32) -> u32 {
+    let row = (lat + 90.0).floor().clamp(0.0, 179.0) as u32;
+    let col = (lng + 180.0).floor().clamp(0.0, 359.0) as u32;
+    row * 360 + col
+}
+
+/// Scales of population term of reverse score by grid cells: median of cities count around
+/// cells (3x3 cells) divided by count around cell, i.e. below 1 in dense regions and above 1 in
+/// sparse ones, where cities are far from each other.
+fn density_scales<'a>(cities: impl Iterator<Item = &'a CitiesRecord>) -> HashMap<u32, f32> {
+    let mut counts: HashMap<u32, u32> = HashMap::new();
+    for city in cities {
+        *counts
+            .entry(density_cell(city.latitude, city.longitude))
+            .or_default() += 1;
+    }
+
+    let around = |cell: u32| {
+        let (row, col) = ((cell / 360) as i32, (cell % 360) as i32);
+        (row - 1..=row + 1)
+            .filter(|row| (0..180).contains(row))
+            .flat_map(|row| (col - 1..=col + 1).map(move |col| row * 360 + col.rem_euclid(360)))
+            .filter_map(|cell| counts.get(&(cell as u32)))
+            .sum::<u32>()
+    };
+    let densities = counts
+        .keys()
+        .map(|cell| (*cell, around(*cell)))
+        .collect::<Vec<_>>();
+
+    let mut sorted = densities.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let Some(median) = sorted.get(sorted.len() / 2).copied() else {
+        return HashMap::new();
+    };
+
+    densities
+        .into_iter()
+        .map(|(cell, n)| (cell, median as f32 / n as f32))
+        .collect()
+}
+
+fn population_ranks<'a>(
+    cities: impl Iterator<Item = &'a CitiesRecord> + Clone,
+) -> HashMap<u32, PopulationRank> {
+    let percentile = |sorted: &[u32], population: u32| {
+        sorted.partition_point(|p| *p <= population) as f32 * 100.0 / sorted.len() as f32
+    };
+
+    let mut global = cities.clone().map(|c| c.population).collect::<Vec<_>>();
+    global.sort_unstable();
+
+    let mut by_country: HashMap<&str, Vec<u32>> = HashMap::new();
+    for city in cities.clone() {
+        let code = city.country.as_ref().map(|c| c.code.as_str()).unwrap_or("");
+        by_country.entry(code).or_default().push(city.population);
+    }
+    for populations in by_country.values_mut() {
+        populations.sort_unstable();
+    }
+
+    cities
+        .map(|city| {
+            let code = city.country.as_ref().map(|c| c.code.as_str()).unwrap_or("");
+            (
+                city.id,
+                PopulationRank {
+                    country: percentile(&by_country[code], city.population),
+                    global: percentile(&global, city.population),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Country codes of filters are case-insensitive, e.g. `gb` is `GB`
+fn country_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+pub fn skip_comment_lines(content: &str) -> String {
+    content.lines().filter(|l| !l.starts_with('#')).join("\n")
+}
+
+impl Engine {
+    pub fn get(&self, id: &u32) -> Option<&CitiesRecord> {
+        self.geonames.get(id)
+    }
+
+    /// All indexed cities (in arbitrary order).
+    pub fn cities(&self) -> impl ExactSizeIterator<Item = &CitiesRecord> {
+        self.geonames.values()
+    }
+
+    /// Geonameids by kd-tree item index, e.g. to key external ANN indexes or embeddings.
+    ///
+    /// Tree items are ordered by geonameid, so the mapping is the same for the same set of
+    /// cities and is preserved by dump/load of index.
+    pub fn tree_ids(&self) -> Vec<u32> {
+        let mut ids = vec![0; self.tree_index_to_geonameid.len()];
+        for (index, id) in &self.tree_index_to_geonameid {
+            ids[*index] = *id;
+        }
+        ids
+    }
+
+    /// All countries info (in arbitrary order).
+    pub fn countries(&self) -> impl ExactSizeIterator<Item = &CountryRecord> {
+        self.country_info_by_code.values()
+    }
+
+    pub fn capital(&self, code: &str) -> Option<&CitiesRecord> {
+        if let Some(city_id) = self.capitals.get(&country_code(code)) {
+            self.get(city_id)
+        } else {
+            None
+        }
+    }
+
+    /// Suggest cities by pattern (multilang).
+    ///
+    /// Optional: filter by Jaro–Winkler distance via min_score
+    ///
+    /// Optional: prefilter by countries
+    pub fn suggest<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        limit: usize,
+        min_score: Option<f32>,
+        countries: Option<&[T]>,
+    ) -> Vec<&CitiesRecord> {
+        self.suggest_with(
+            pattern,
+            SuggestOptions {
+                min_score,
+                ..SuggestOptions::new(limit)
+            }
+            .with_countries(countries),
+        )
+    }
+
+    /// Suggest cities by pattern (multilang), see [`SuggestOptions`].
+    pub fn suggest_with<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<&CitiesRecord> {
+        self.suggest_with_scores(pattern, options)
+            .into_iter()
+            .map(|item| item.city)
+            .collect()
+    }
+
+    /// Suggest cities by pattern with similarity scores and matched names, e.g. to show
+    /// that city was found by alternate name, see [`Engine::suggest_with`].
+    pub fn suggest_with_scores<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        let Some(hook) = &self.query_hook else {
+            return self.find_suggest(pattern, options);
+        };
+
+        let now = Instant::now();
+        let (limit, countries, bbox) = (options.limit, options.countries, options.bbox);
+        let result = self.find_suggest(pattern, options);
+        hook.on_query(&QueryEvent {
+            kind: "suggest",
+            pattern: Some(pattern),
+            loc: None,
+            countries: countries.map(|c| c.iter().map(AsRef::as_ref).collect()),
+            bbox,
+            limit,
+            results: result.len(),
+            elapsed: now.elapsed(),
+        });
+        result
+    }
+
+    /// Top scored candidates ignoring `min_score` of options, e.g. to explain why
+    /// suggest result is empty.
+    ///
+    /// Scores every entry, so it's much slower than [`Engine::suggest_with`].
+    pub fn suggest_candidates<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        self.find_suggest(
+            pattern,
+            SuggestOptions {
+                min_score: Some(0.0),
+                max_edits: None,
+                ..options
+            },
+        )
+    }
+
+    /// Start typeahead session: cities are suggested by each keystroke with the same options,
+    /// see [`SuggestSession`].
+    pub fn suggest_session<'a, T: AsRef<str> + Clone>(
+        &'a self,
+        options: SuggestOptions<'a, T>,
+    ) -> SuggestSession<'a, T> {
+        SuggestSession {
+            engine: self,
+            options,
+            pattern: String::new(),
+            candidates: None,
+        }
+    }
+
+    /// Suggest cities, admin divisions and countries in a single list ranked by weighted
+    /// similarity, e.g. for `Berlin` or `Bavaria` input, see [`PlaceWeights::only`] to suggest
+    /// some kinds of places.
+    ///
+    /// Admin divisions and countries are matched by name and translations, only `limit`,
+    /// `min_score`, `countries` and `metric` of options are applied to them.
+    pub fn suggest_places<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+        weights: PlaceWeights,
+    ) -> Vec<PlaceItem<'_>> {
+        let Some(hook) = &self.query_hook else {
+            return self.find_places(pattern, options, weights);
+        };
+
+        let now = Instant::now();
+        let (limit, countries, bbox) = (options.limit, options.countries, options.bbox);
+        let result = self.find_places(pattern, options, weights);
+        hook.on_query(&QueryEvent {
+            kind: "places",
+            pattern: Some(pattern),
+            loc: None,
+            countries: countries.map(|c| c.iter().map(AsRef::as_ref).collect()),
+            bbox,
+            limit,
+            results: result.len(),
+            elapsed: now.elapsed(),
+        });
+        result
+    }
+
+    fn find_places<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+        weights: PlaceWeights,
+    ) -> Vec<PlaceItem<'_>> {
+        let (limit, metric) = (options.limit, options.metric);
+        let min_score = options.min_score.unwrap_or(0.8);
+        let countries = options.countries.map(|countries| {
+            countries
+                .iter()
+                .map(|code| country_code(code.as_ref()))
+                .collect::<Vec<_>>()
+        });
+        let excluded = options
+            .exclude_countries
+            .unwrap_or_default()
+            .iter()
+            .map(|code| country_code(code.as_ref()))
+            .collect::<Vec<_>>();
+        let in_countries = |code: &str| {
+            countries
+                .as_ref()
+                .is_none_or(|c| c.iter().any(|c| c == code))
+                && !excluded.iter().any(|c| c == code)
+        };
+        let normalized_pattern = normalize::normalize(pattern, self.case_folding);
+
+        let mut items = Vec::new();
+
+        if weights.city > 0.0 {
+            items.extend(
+                self.find_suggest(pattern, options)
+                    .into_iter()
+                    .map(|item| PlaceItem {
+                        place: Place::City(item.city),
+                        score: item.score * weights.city,
+                    }),
+            );
+        }
+
+        let admin_divisions: [(_, _, fn(_) -> _); 2] = [
+            (&self.admin1_divisions, weights.admin1, Place::Admin1),
+            (&self.admin2_divisions, weights.admin2, Place::Admin2),
+        ];
+        for (divisions, weight, place) in admin_divisions {
+            if weight <= 0.0 {
+                continue;
+            }
+            for division in divisions.values() {
+                if !in_countries(division.code.split('.').next().unwrap_or_default()) {
+                    continue;
+                }
+                let score = self.place_score(division.id, &normalized_pattern, metric);
+                if score < min_score {
+                    continue;
+                }
+                if let Some(info) = self.admin_division_info(division) {
+                    items.push(PlaceItem {
+                        place: place(info),
+                        score: score * weight,
+                    });
+                }
+            }
+        }
+
+        if weights.country > 0.0 {
+            for (code, country) in &self.country_info_by_code {
+                if !in_countries(code) {
+                    continue;
+                }
+                let score = self.place_score(country.info.geonameid, &normalized_pattern, metric);
+                if score >= min_score {
+                    items.push(PlaceItem {
+                        place: Place::Country(country),
+                        score: score * weights.country,
+                    });
+                }
+            }
+        }
+
+        // stable sort keeps cities (ranked by prominence on equal score) first
+        items.sort_by(|lhs, rhs| {
+            rhs.score
+                .partial_cmp(&lhs.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        items.truncate(limit);
+        items
+    }
+
+    /// Best similarity of normalized place names (admin division or country by geonameid)
+    fn place_score(&self, id: u32, normalized_pattern: &str, metric: SimilarityMetric) -> f32 {
+        self.place_names
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|name| {
+                if name.starts_with(normalized_pattern) {
+                    1.0
+                } else {
+                    metric.similarity(name, normalized_pattern)
+                }
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Normalize names of admin divisions and countries once, instead of per query
+    fn index_place_names(&mut self) {
+        let normalized = |name: &String| normalize::normalize(name, self.case_folding);
+        let divisions = self
+            .admin1_divisions
+            .values()
+            .chain(self.admin2_divisions.values())
+            .map(|division| {
+                let names = self.admin_names.get(&division.id).into_iter().flatten();
+                let names = std::iter::once(&division.name)
+                    .chain(names.map(|(_, name)| name))
+                    .map(normalized)
+                    .unique()
+                    .collect();
+                (division.id, names)
+            });
+        let countries = self.country_info_by_code.values().map(|country| {
+            let names = std::iter::once(&country.info.name)
+                .chain(country.names.iter().flat_map(|n| n.values()))
+                .map(normalized)
+                .unique()
+                .collect();
+            (country.info.geonameid, names)
+        });
+        let place_names = divisions.chain(countries).collect();
+
+        let admin1_abbreviations = self
+            .admin1_divisions
+            .values()
+            .map(|division| {
+                let (_, suffix) = division.code.split_once('.').unwrap_or_default();
+                let abbreviations = std::iter::once(suffix)
+                    .chain(
+                        ADMIN1_ABBREVIATIONS
+                            .iter()
+                            .filter(|(_, code)| *code == division.code)
+                            .map(|(abbreviation, _)| *abbreviation),
+                    )
+                    .map(|value| compact_token(value, self.case_folding))
+                    .collect();
+                (division.id, abbreviations)
+            })
+            .collect();
+
+        self.place_names = place_names;
+        self.admin1_abbreviations = admin1_abbreviations;
+    }
+
+    fn find_suggest<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        self.find_suggest_in(pattern, options, None).0
+    }
+
+    /// Suggest among entries `candidates` (indexes of all entries if `None`), also returns
+    /// indexes of matched entries to refine them, see [`SuggestSession`].
+    fn find_suggest_in<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        SuggestOptions {
+            limit,
+            min_score,
+            countries,
+            exclude_countries,
+            admin1,
+            min_population,
+            feature_codes,
+            lang,
+            bbox,
+            metric,
+            abbreviations,
+            former_names,
+            transliterate,
+            blocked_ids,
+            ranking,
+            max_edits,
+        }: SuggestOptions<'_, T>,
+        candidates: Option<&[usize]>,
+    ) -> (Vec<SuggestItem<'_>>, Vec<usize>) {
+        if limit == 0 {
+            return (Vec::new(), Vec::new());
+        }
+
+        let Some(countries) = self.resolve_countries_filter(countries) else {
+            return (Vec::new(), Vec::new());
+        };
+
+        let Some(excluded) = self.resolve_excluded_countries(exclude_countries) else {
+            return (Vec::new(), Vec::new());
+        };
+        let blocked_ids = sorted_ids(blocked_ids);
+        let excluded_ids = excluded.map(|excluded| {
+            excluded
+                .iter()
+                .filter_map(|code| self.country_info_by_code.get(code))
+                .map(|c| c.info.geonameid)
+                .collect::<Vec<u32>>()
+        });
+
+        let admin1_id = match admin1 {
+            Some(code) => match self.admin1_divisions.get(&code.to_uppercase()) {
+                Some(division) => Some(division.id),
+                None => return (Vec::new(), Vec::new()),
+            },
+            None => None,
+        };
+
+        let min_score = min_score.unwrap_or(0.8);
+        let NormalizedQuery {
+            pattern: normalized_pattern,
+            expanded: expanded_pattern,
+        } = self.normalize_query_with(pattern, abbreviations);
+        let lang = lang.map(|lang| canonical_language(lang).unwrap_or(Cow::Borrowed(lang)));
+        let lang_mask = lang
+            .as_deref()
+            .map(|lang| language_mask(&self.languages, lang));
+
+        // similarity of accepted value (by `min_score` or `max_edits`)
+        let pattern_score = |value: &str, pattern: &str| {
+            if value.starts_with(pattern) {
+                return Some(1.0);
+            }
+            let score = metric.similarity(value, pattern);
+            let accepted = match max_edits {
+                Some(max_edits) => strsim::levenshtein(value, pattern) <= max_edits,
+                None => score >= min_score,
+            };
+            accepted.then_some(score)
+        };
+
+        let score_pattern = |value: &str| match &expanded_pattern {
+            Some(expanded) => match (
+                pattern_score(value, &normalized_pattern),
+                pattern_score(value, expanded),
+            ) {
+                (Some(lhs), Some(rhs)) => Some(lhs.max(rhs)),
+                (lhs, rhs) => lhs.or(rhs),
+            },
+            None => pattern_score(value, &normalized_pattern),
+        };
+
+        let country_ids = countries.map(|countries| {
+            countries
+                .iter()
+                .filter_map(|code| {
+                    self.country_info_by_code
+                        .get(&country_code(code.as_ref()))
+                        .map(|c| c.info.geonameid)
+                })
+                .collect::<Vec<u32>>()
+        });
+
+        // spatial prefilter
+        let bbox_ids = bbox.map(|bbox| {
+            self.cities_in_bbox_with(&bbox, |city| bbox.contains(city.latitude, city.longitude))
+                .into_iter()
+                .map(|city| city.id)
+                .collect::<HashSet<u32>>()
+        });
+
+        let former_entries = if former_names {
+            self.former_entries.as_slice()
+        } else {
+            &[]
+        };
+
+        let entry_at = |index: usize| match self.entries.get(index) {
+            Some(entry) => entry,
+            None => &former_entries[index - self.entries.len()],
+        };
+
+        // transliterated entries are scanned all (candidates are indexes of entries)
+        let scan = |translit: bool, score_value: &(dyn Fn(&str) -> Option<f32> + Sync)| {
+            let indexes = match (translit, candidates) {
+                (true, _) => Either::Right((0..self.translit_entries.len()).into_par_iter()),
+                (false, Some(candidates)) => Either::Left(candidates.par_iter().copied()),
+                (false, None) => {
+                    Either::Right((0..self.entries.len() + former_entries.len()).into_par_iter())
+                }
+            };
+            indexes
+                .map(|index| match translit {
+                    true => (index, &self.translit_entries[index]),
+                    false => (index, entry_at(index)),
+                })
+                .filter(|(_, item)| {
+                    country_ids.as_ref().is_none_or(|country_ids| {
+                        item.country_id
+                            .is_some_and(|country_id| country_ids.contains(&country_id))
+                    })
+                })
+                .filter(|(_, item)| {
+                    excluded_ids.as_ref().is_none_or(|excluded_ids| {
+                        item.country_id
+                            .is_none_or(|country_id| !excluded_ids.contains(&country_id))
+                    })
+                })
+                .filter(|(_, item)| admin1_id.is_none_or(|id| item.admin1_id == Some(id)))
+                .filter(|(_, item)| {
+                    lang_mask.is_none_or(|mask| item.langs == 0 || item.langs & mask != 0)
+                })
+                .filter(|(_, item)| {
+                    if min_population.is_none() && feature_codes.is_none() {
+                        return true;
+                    }
+                    self.geonames.get(&item.id).is_some_and(|city| {
+                        min_population
+                            .is_none_or(|min_population| city.population >= min_population)
+                            && feature_codes.is_none_or(|codes| {
+                                codes
+                                    .iter()
+                                    .any(|c| c.eq_ignore_ascii_case(&city.feature_code))
+                            })
+                    })
+                })
+                .filter(|(_, item)| bbox_ids.as_ref().is_none_or(|ids| ids.contains(&item.id)))
+                .filter(|(_, item)| blocked_ids.binary_search(&item.id).is_err())
+                .filter_map(|(index, item)| {
+                    let score = score_value(&item.value)?;
+                    let city = self.geonames.get(&item.id)?;
+                    let rank = match ranking {
+                        SuggestRanking::Similarity => score + PROMINENCE_WEIGHT * item.prominence,
+                        SuggestRanking::PopulationBlend { alpha, beta } => {
+                            alpha * score + beta * population_weight(city.population)
+                        }
+                        SuggestRanking::Profile { profile, bias } => {
+                            profile.rank(score, city, bias)
+                        }
+                    };
+                    let rank = match &self.ranker {
+                        Some(ranker) => ranker.rank(city, rank),
+                        None => rank,
+                    };
+                    Some((city, score, item, rank, index))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut result = scan(false, &score_pattern);
+
+        // retry across scripts, e.g. `moskva` matches `москва` and `athena` matches `αθηνα`
+        #[cfg(feature = "translit")]
+        let transliterated = transliterate && result.is_empty();
+        #[cfg(feature = "translit")]
+        if transliterated {
+            // latin entries as is and precomputed transliteration of others
+            let pattern = normalize::transliterate(&normalized_pattern);
+            result = scan(false, &|value: &str| {
+                value
+                    .is_ascii()
+                    .then(|| pattern_score(value, &pattern))
+                    .flatten()
+            });
+            result.extend(scan(true, &|value: &str| pattern_score(value, &pattern)));
+        }
+        #[cfg(not(feature = "translit"))]
+        let transliterated = {
+            let _ = transliterate;
+            false
+        };
+
+        // transliterated matches aren't refined, next pattern rescans all entries
+        let matched = if transliterated {
+            Vec::new()
+        } else {
+            result.iter().map(|item| item.4).collect()
+        };
+
+        // sort by score weighted with precomputed prominence (or blended or custom rank) desc
+        result.sort_unstable_by(|lhs, rhs| {
+            rhs.3
+                .partial_cmp(&lhs.3)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let items = result
+            .into_iter()
+            .unique_by(|item| item.0.id)
+            .take(limit)
+            .map(|(city, score, item, _, _)| SuggestItem {
+                city,
+                score,
+                matched: &item.value,
+                matched_name: self.display_name(item),
+            })
+            .collect();
+
+        (items, matched)
+    }
+
+    /// Find the nearest cities by coordinates (by great-circle distance, across antimeridian
+    /// and poles too).
+    ///
+    /// Optional: score results by `k` as `distance - k * city.population` and sort by score.
+    ///
+    /// Optional: prefilter by countries, looked up in kd-trees of the countries (built on the
+    /// first country filtered query).
+    pub fn reverse<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        limit: usize,
+        k: Option<f32>,
+        countries: Option<&[T]>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        self.reverse_with(
+            loc,
+            ReverseOptions {
+                k,
+                ..ReverseOptions::new(limit)
+            }
+            .with_countries(countries),
+        )
+    }
+
+    /// Find the nearest cities by coordinates, see [`ReverseOptions`].
+    pub fn reverse_with<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        options: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        let Some(hook) = &self.query_hook else {
+            return self.find_reverse(loc, options);
+        };
+
+        let now = Instant::now();
+        let (limit, countries) = (options.limit, options.countries);
+        let result = self.find_reverse(loc, options);
+        hook.on_query(&QueryEvent {
+            kind: "reverse",
+            pattern: None,
+            loc: Some(loc),
+            countries: countries.map(|c| c.iter().map(AsRef::as_ref).collect()),
+            bbox: None,
+            limit,
+            results: result.as_ref().map_or(0, Vec::len),
+            elapsed: now.elapsed(),
+        });
+        result
+    }
+
+    /// Reverse geocode many locations in parallel, e.g. to enrich logs. Results are in order of
+    /// `locations`, each one is the same as of [`Engine::reverse_with`].
+    ///
+    /// Filters of options are resolved once for the whole batch and [`QueryHook`] is notified
+    /// once per batch (`reverse_batch` kind).
+    pub fn reverse_batch<T: AsRef<str>>(
+        &self,
+        locations: &[(f32, f32)],
+        options: ReverseOptions<'_, T>,
+    ) -> Vec<Option<Vec<ReverseItem<'_>>>> {
+        let now = Instant::now();
+        let ReverseOptions {
+            limit,
+            nearest_limit,
+            k,
+            countries,
+            exclude_countries,
+            blocked_ids,
+            adaptive_k,
+            min_population,
+            scoring,
+        } = options;
+
+        let filter = self.reverse_filter(countries, exclude_countries, blocked_ids, min_population);
+        let result = locations
+            .par_iter()
+            .map(|loc| {
+                if limit == 0 {
+                    return None;
+                }
+                match filter {
+                    Some(ref filter) => self.reverse_filtered(
+                        *loc,
+                        limit,
+                        nearest_limit,
+                        &ReverseScore {
+                            k,
+                            adaptive_k,
+                            scoring,
+                        },
+                        filter,
+                    ),
+                    None => Some(Vec::new()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(hook) = &self.query_hook {
+            hook.on_query(&QueryEvent {
+                kind: "reverse_batch",
+                pattern: None,
+                loc: None,
+                countries: countries.map(|c| c.iter().map(AsRef::as_ref).collect()),
+                bbox: None,
+                limit,
+                results: result.iter().flatten().map(Vec::len).sum(),
+                elapsed: now.elapsed(),
+            });
+        }
+        result
+    }
+
+    /// IANA timezone id of the nearest city (by great-circle distance, so across antimeridian
+    /// too), e.g. `Europe/Moscow`, when only timezone of location is needed.
+    /// `None` for empty index or city without timezone.
+    pub fn timezone_at(&self, lat: f32, lng: f32) -> Option<&str> {
+        let score = ReverseScore {
+            k: None,
+            adaptive_k: false,
+            scoring: ReverseScoring::Linear,
+        };
+        let filter = ReverseFilter {
+            countries: None,
+            excluded: None,
+            blocked_ids: Cow::Borrowed(&[]),
+            min_population: None,
+        };
+        self.reverse_filtered((lat, lng), 1, None, &score, &filter)?
+            .first()
+            .map(|item| item.city.timezone.as_str())
+            .filter(|timezone| !timezone.is_empty())
+    }
+
+    fn find_reverse<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        ReverseOptions {
+            limit,
+            nearest_limit,
+            k,
+            countries,
+            exclude_countries,
+            blocked_ids,
+            adaptive_k,
+            min_population,
+            scoring,
+        }: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        if limit == 0 {
+            return None;
+        }
+
+        let Some(filter) =
+            self.reverse_filter(countries, exclude_countries, blocked_ids, min_population)
+        else {
+            return Some(Vec::new());
+        };
+        let score = ReverseScore {
+            k,
+            adaptive_k,
+            scoring,
+        };
+        self.reverse_filtered(loc, limit, nearest_limit, &score, &filter)
+    }
+
+    /// Normalized filters of reverse, `None` when nothing can match
+    fn reverse_filter<'a, T: AsRef<str>>(
+        &self,
+        countries: Option<&[T]>,
+        exclude_countries: Option<&[T]>,
+        blocked_ids: &'a [u32],
+        min_population: Option<u32>,
+    ) -> Option<ReverseFilter<'a>> {
+        let countries = self.resolve_countries_filter(countries)?.map(|countries| {
+            countries
+                .iter()
+                .map(|code| country_code(code.as_ref()))
+                .collect::<Vec<_>>()
+        });
+        let excluded = self.resolve_excluded_countries(exclude_countries)?;
+        Some(ReverseFilter {
+            countries,
+            excluded,
+            blocked_ids: sorted_ids(blocked_ids),
+            min_population,
+        })
+    }
+
+    fn reverse_filtered<'a>(
+        &'a self,
+        loc: (f32, f32),
+        limit: usize,
+        nearest_limit: Option<usize>,
+        score: &ReverseScore,
+        filter: &ReverseFilter<'_>,
+    ) -> Option<Vec<ReverseItem<'a>>> {
+        let ReverseScore {
+            k,
+            adaptive_k,
+            scoring,
+        } = *score;
+        let rescored = k.is_some() || scoring != ReverseScoring::Linear;
+        let ReverseFilter {
+            countries,
+            excluded,
+            blocked_ids,
+            min_population,
+        } = filter;
+        let allowed = |city: &CitiesRecord| {
+            blocked_ids.binary_search(&city.id).is_err()
+                && min_population.is_none_or(|min_population| city.population >= min_population)
+                && excluded.as_ref().is_none_or(|excluded| {
+                    city.country
+                        .as_ref()
+                        .is_none_or(|c| !excluded.contains(&c.code))
+                })
+        };
+        let matches = |city: &CitiesRecord| {
+            allowed(city)
+                && countries.as_ref().is_none_or(|countries| {
+                    city.country
+                        .as_ref()
+                        .is_some_and(|c| countries.contains(&c.code))
+                })
+        };
+        let city_at = |index: usize| {
+            self.tree_index_to_geonameid
+                .get(&index)
+                .and_then(|geonameid| self.geonames.get(geonameid))
+        };
+
+        // candidates rescored by `k` or alternative scoring
+        let pool = if rescored {
+            nearest_limit.unwrap_or(limit).max(limit)
+        } else {
+            limit
+        };
+
+        // whole tree or trees of requested countries with their items to whole tree indexes
+        let trees = match countries {
+            Some(codes) => {
+                let trees = self.country_trees();
+                codes
+                    .iter()
+                    .filter_map(|code| trees.get(code))
+                    .map(|country| (&country.tree, Some(country.indexes.as_slice())))
+                    .collect::<Vec<_>>()
+            }
+            None => vec![(&self.tree, None)],
+        };
+        let index = |indexes: Option<&[u32]>, item: u32| {
+            indexes.map_or(item, |indexes| indexes[item as usize]) as usize
+        };
+
+        // nearest candidates by degrees, excluded cities are skipped, so look for more
+        // candidates until enough found
+        let mut candidates = Vec::new();
+        for (tree, indexes) in &trees {
+            let total = indexes.map_or(self.tree_index_to_geonameid.len(), <[u32]>::len);
+            if total == 0 {
+                continue;
+            }
+            let mut n = pool.min(total);
+            let found = loop {
+                let found =
+                    tree.nearest_n::<SquaredEuclidean>(&[loc.0, loc.1], std::num::NonZero::new(n)?);
+                if (excluded.is_none() && blocked_ids.is_empty() && min_population.is_none())
+                    || n >= total
+                    || found
+                        .iter()
+                        .filter(|nearest| {
+                            city_at(index(*indexes, nearest.item)).is_some_and(allowed)
+                        })
+                        .count()
+                        >= pool
+                {
+                    break found;
+                }
+                n = (n * 2).min(total);
+            };
+            candidates.extend(
+                found
+                    .iter()
+                    .filter_map(|nearest| city_at(index(*indexes, nearest.item)))
+                    .filter(|city| matches(city))
+                    .map(|city| (distance_m(loc, city), city)),
+            );
+        }
+        let by_distance = |a: &(f32, &CitiesRecord), b: &(f32, &CitiesRecord)| {
+            a.0.total_cmp(&b.0).then(a.1.id.cmp(&b.1.id))
+        };
+        candidates.sort_unstable_by(by_distance);
+        candidates.truncate(pool);
+
+        // kd-tree is in degrees, so the nearest cities by degrees may be not the nearest ones
+        // (across antimeridian, towards poles): cities within great-circle distance of the
+        // farthest candidate are looked up again
+        let Some(&(radius_m, _)) = candidates.last() else {
+            return Some(Vec::new());
+        };
+        let mut seen = HashSet::new();
+        let mut nearest = trees
+            .iter()
+            .flat_map(|(tree, indexes)| {
+                tree_within(tree, loc, radius_m)
+                    .into_iter()
+                    .map(|item| index(*indexes, item))
+            })
+            .filter(|index| seen.insert(*index))
+            .filter_map(city_at)
+            .filter(|city| matches(city))
+            .map(|city| (distance_m(loc, city), city))
+            .filter(|(distance_m, _)| *distance_m <= radius_m)
+            .collect::<Vec<_>>();
+        nearest.sort_unstable_by(by_distance);
+        nearest.truncate(pool);
+
+        if rescored {
+            // the same scale for all candidates, otherwise neighbours of two cells are reordered
+            // by cell borders; query in cell without cities takes cell of the nearest city
+            let scale = if adaptive_k {
+                self.density_scales
+                    .get(&density_cell(loc.0, loc.1))
+                    .or_else(|| {
+                        nearest.first().and_then(|(_, city)| {
+                            self.density_scales
+                                .get(&density_cell(city.latitude, city.longitude))
+                        })
+                    })
+                    .copied()
+                    .unwrap_or(1.0)
+            } else {
+                1.0
+            };
+            let mut points = nearest
+                .into_iter()
+                .map(|(distance_m, city)| {
+                    let distance = degrees_squared(loc, city);
+                    let population = city.population as f32;
+                    let score = match scoring {
+                        ReverseScoring::Linear => {
+                            distance - k.unwrap_or_default() * population * scale
+                        }
+                        ReverseScoring::LogPopulation => {
+                            distance_m / (std::f32::consts::E + population).ln()
+                        }
+                        ReverseScoring::Gravity => distance_m.powi(2) / (1.0 + population),
+                    };
+                    (distance, score, city, distance_m)
+                })
+                .collect::<Vec<_>>();
+
+            points.sort_unstable_by(|a, b| {
+                a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal)
+            });
+            points.truncate(limit);
+
+            Some(
+                points
+                    .into_iter()
+                    .map(|p| ReverseItem {
+                        distance: p.0,
+                        distance_m: p.3,
+                        score: p.1,
+                        city: p.2,
+                    })
+                    .collect(),
+            )
+        } else {
+            Some(
+                nearest
+                    .into_iter()
+                    .take(limit)
+                    .map(|(distance_m, city)| {
+                        let distance = degrees_squared(loc, city);
+                        ReverseItem {
+                            distance,
+                            distance_m,
+                            score: distance,
+                            city,
+                        }
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// Reverse geocode the center of points, e.g. to pick a "home city" of scattered GPS
+    /// samples, see [`CentroidMode`].
+    ///
+    /// Returns `None` when center is undefined (no points or antipodal ones).
+    pub fn reverse_centroid<T: AsRef<str>>(
+        &self,
+        points: &[(f32, f32)],
+        mode: CentroidMode,
+        options: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        let total_distance =
+            |loc: (f32, f32)| -> f32 { points.iter().map(|p| distance_km(loc, *p)).sum() };
+
+        match mode {
+            CentroidMode::Centroid => self.reverse_with(centroid(points)?, options),
+            CentroidMode::Medoid => {
+                let medoid = points
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| total_distance(*a).total_cmp(&total_distance(*b)))?;
+                self.reverse_with(medoid, options)
+            }
+            CentroidMode::MinTotalDistance => {
+                let limit = options.limit;
+                let center = centroid(points)?;
+
+                // nearest cities of centroid and of each point
+                let mut candidates: HashMap<u32, ReverseItem> = HashMap::new();
+                let nearest = |loc, limit| {
+                    self.find_reverse(
+                        loc,
+                        ReverseOptions {
+                            limit,
+                            nearest_limit: None,
+                            k: None,
+                            scoring: ReverseScoring::Linear,
+                            ..options.clone()
+                        },
+                    )
+                    .unwrap_or_default()
+                };
+                for item in std::iter::once(nearest(center, CENTROID_CANDIDATES.max(limit)))
+                    .chain(points.iter().map(|p| nearest(*p, 1)))
+                    .flatten()
+                {
+                    candidates.entry(item.city.id).or_insert(item);
+                }
+
+                let mut items = candidates
+                    .into_values()
+                    .map(|item| {
+                        let loc = (item.city.latitude, item.city.longitude);
+                        ReverseItem {
+                            city: item.city,
+                            // as reverse of centroid
+                            distance: degrees_squared(center, item.city),
+                            distance_m: distance_m(center, item.city),
+                            score: total_distance(loc),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                items.sort_unstable_by(|a, b| {
+                    a.score.total_cmp(&b.score).then(a.city.id.cmp(&b.city.id))
+                });
+                items.truncate(limit);
+                Some(items)
+            }
+        }
+    }
+
+    /// All cities within geodesic `radius_m` (meters) of `(lat, lng)` location, nearest first,
+    /// e.g. "cities within 50 km". Score of items is the distance in meters.
+    ///
+    /// Optional: `limit` of the nearest cities
+    pub fn within_radius<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        radius_m: f32,
+        limit: Option<usize>,
+        filters: RadiusFilters<'_, T>,
+    ) -> Vec<ReverseItem<'_>> {
+        let RadiusFilters {
+            countries,
+            exclude_countries,
+            min_population,
+            blocked_ids,
+        } = filters;
+
+        if limit == Some(0)
+            || radius_m.is_nan()
+            || radius_m < 0.0
+            || self.tree_index_to_geonameid.is_empty()
+        {
+            return Vec::new();
+        }
+
+        let Some(countries) = self.resolve_countries_filter(countries) else {
+            return Vec::new();
+        };
+        let countries = countries.map(|countries| {
+            countries
+                .iter()
+                .map(|code| country_code(code.as_ref()))
+                .collect::<Vec<_>>()
+        });
+        let Some(excluded) = self.resolve_excluded_countries(exclude_countries) else {
+            return Vec::new();
+        };
+        let blocked_ids = sorted_ids(blocked_ids);
+        let allowed = |city: &CitiesRecord| {
+            let code = city.country.as_ref().map(|c| &c.code);
+            blocked_ids.binary_search(&city.id).is_err()
+                && min_population.is_none_or(|min| city.population >= min)
+                && countries
+                    .as_ref()
+                    .is_none_or(|countries| code.is_some_and(|code| countries.contains(code)))
+                && excluded
+                    .as_ref()
+                    .is_none_or(|excluded| code.is_none_or(|code| !excluded.contains(code)))
+        };
+
+        let mut items = tree_within(&self.tree, loc, radius_m)
+            .into_iter()
+            .filter_map(|item| {
+                let geonameid = self.tree_index_to_geonameid.get(&(item as usize))?;
+                let city = self.geonames.get(geonameid)?;
+                if !allowed(city) {
+                    return None;
+                }
+                let distance_m = distance_m(loc, city);
+                (distance_m <= radius_m).then_some(ReverseItem {
+                    city,
+                    distance: degrees_squared(loc, city),
+                    distance_m,
+                    score: distance_m,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        items.sort_unstable_by(|a, b| {
+            a.distance_m
+                .total_cmp(&b.distance_m)
+                .then(a.city.id.cmp(&b.city.id))
+        });
+        if let Some(limit) = limit {
+            items.truncate(limit);
+        }
+        items
+    }
+
+    /// Cities inside of geohash cell (population desc).
+    ///
+    /// Returns empty result on invalid geohash.
+    pub fn cities_in_geohash(&self, geohash: &str) -> Vec<&CitiesRecord> {
+        let Some(bbox) = geohash::decode_bbox(geohash) else {
+            return Vec::new();
+        };
+        let geohash = geohash.to_lowercase();
+        self.cities_in_bbox_with(&bbox, |city| {
+            geohash::encode(city.latitude, city.longitude, geohash.len()) == geohash
+        })
+    }
+
+    /// Cities inside of H3 cell (population desc).
+    ///
+    /// Returns empty result on invalid cell index.
+    #[cfg(feature = "h3")]
+    pub fn cities_in_h3(&self, cell: u64) -> Vec<&CitiesRecord> {
+        let Ok(cell) = h3o::CellIndex::try_from(cell) else {
+            return Vec::new();
+        };
+        let in_cell = |city: &CitiesRecord| {
+            h3o::LatLng::new(city.latitude as f64, city.longitude as f64)
+                .is_ok_and(|loc| loc.to_cell(cell.resolution()) == cell)
+        };
+        let bboxes = h3_bboxes(cell);
+        let mut cities = bboxes
+            .iter()
+            .flat_map(|bbox| self.cities_in_bbox_with(bbox, in_cell))
+            .collect::<Vec<_>>();
+        if bboxes.len() > 1 {
+            cities.sort_unstable_by(|a, b| b.population.cmp(&a.population).then(a.id.cmp(&b.id)));
+            cities.dedup_by_key(|city| city.id);
+        }
+        cities
+    }
+
+    /// Cities within `max_offset_km` corridor of the route (polyline of `(lat, lng)` points)
+    /// ordered by route progression.
+    ///
+    /// Distances are approximated by equirectangular projection of each route segment,
+    /// routes crossing the antimeridian are not supported.
+    pub fn nearest_along(&self, route: &[(f32, f32)], max_offset_km: f32) -> Vec<RouteItem<'_>> {
+        let segments = match route {
+            [] => return Vec::new(),
+            [point] => vec![(*point, *point)],
+            route => route.windows(2).map(|w| (w[0], w[1])).collect(),
+        };
+
+        let mut found: HashMap<u32, RouteItem> = HashMap::new();
+        let mut progress = 0.0;
+
+        for (a, b) in segments {
+            let scale = ((a.0 + b.0) / 2.0).to_radians().cos();
+            let project = |p: (f32, f32)| (p.1 * scale * KM_PER_DEGREE, p.0 * KM_PER_DEGREE);
+            let (ax, ay) = project(a);
+            let (dx, dy) = {
+                let (bx, by) = project(b);
+                (bx - ax, by - ay)
+            };
+            let length_squared = dx * dx + dy * dy;
+
+            // segment bounding box extended by offset
+            let lat_pad = max_offset_km / KM_PER_DEGREE;
+            let max_lat = a.0.abs().max(b.0.abs()) + lat_pad;
+            let lng_pad = if max_lat < 89.0 {
+                lat_pad / max_lat.to_radians().cos()
+            } else {
+                180.0
+            };
+            let mut bbox = BBox::from_point(a.0, a.1);
+            bbox.extend(b.0, b.1);
+            bbox.extend(bbox.min_lat - lat_pad, bbox.min_lng - lng_pad);
+            bbox.extend(bbox.max_lat + lat_pad, bbox.max_lng + lng_pad);
+
+            for city in self.cities_in_bbox_with(&bbox, |_| true) {
+                let (px, py) = project((city.latitude, city.longitude));
+                let t = if length_squared > 0.0 {
+                    (((px - ax) * dx + (py - ay) * dy) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let offset_km = (px - ax - t * dx).hypot(py - ay - t * dy);
+                if offset_km > max_offset_km {
+                    continue;
+                }
+                if found
+                    .get(&city.id)
+                    .is_none_or(|item| item.offset_km > offset_km)
+                {
+                    found.insert(
+                        city.id,
+                        RouteItem {
+                            city,
+                            offset_km,
+                            progress_km: progress + t * length_squared.sqrt(),
+                        },
+                    );
+                }
+            }
+
+            progress += length_squared.sqrt();
+        }
+
+        let mut items = found.into_values().collect::<Vec<_>>();
+        items.sort_unstable_by(|a, b| {
+            a.progress_km
+                .total_cmp(&b.progress_km)
+                .then(a.offset_km.total_cmp(&b.offset_km))
+                .then(a.city.id.cmp(&b.city.id))
+        });
+        items
+    }
+
+    /// Build parts of engine which are built lazily otherwise (per-country kd-trees of country
+    /// filtered reverse), so the first requests don't pay for them.
+    ///
+    /// Call it again after [`Engine::apply_overrides`] or [`Engine::into_slim`].
+    pub fn warm_up(&self) {
+        #[cfg(feature = "tracing")]
+        let now = Instant::now();
+
+        self.country_trees();
+
+        #[cfg(feature = "tracing")]
+        tracing::info!("Warm up took {}ms", now.elapsed().as_millis());
+    }
+
+    fn country_trees(&self) -> &HashMap<String, CountryTree> {
+        self.country_trees
+            .get_or_init(|| build_country_trees(&self.tree_index_to_geonameid, &self.geonames))
+    }
+
+    /// Single country index fast path: countries filter is either no-op (`Some(None)`)
+    /// or excludes every city (`None`).
+    fn resolve_countries_filter<'a, T: AsRef<str>>(
+        &self,
+        countries: Option<&'a [T]>,
+    ) -> Option<Option<&'a [T]>> {
+        match (&self.single_country, countries) {
+            (Some(code), Some(countries)) => countries
+                .iter()
+                .any(|c| country_code(c.as_ref()) == *code)
+                .then_some(None),
+            (_, countries) => Some(countries),
+        }
+    }
+
+    /// Normalized excluded countries codes, `None` if excluded countries cover single country index.
+    fn resolve_excluded_countries<T: AsRef<str>>(
+        &self,
+        countries: Option<&[T]>,
+    ) -> Option<Option<Vec<String>>> {
+        let countries = countries
+            .map(|codes| {
+                codes
+                    .iter()
+                    .map(|code| country_code(code.as_ref()))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|codes| !codes.is_empty());
+        match (&self.single_country, &countries) {
+            (Some(code), Some(countries)) if countries.contains(code) => None,
+            _ => Some(countries),
+        }
+    }
+
+    fn cities_in_bbox_with(
+        &self,
+        bbox: &BBox,
+        filter: impl Fn(&CitiesRecord) -> bool,
+    ) -> Vec<&CitiesRecord> {
+        let center = (
+            (bbox.min_lat + bbox.max_lat) / 2.0,
+            (bbox.min_lng + bbox.max_lng) / 2.0,
+        );
+        let radius = bbox.max_distance_squared(center) * BBOX_RADIUS_MARGIN + f32::EPSILON;
+
+        let mut cities = self
+            .tree
+            .within::<SquaredEuclidean>(&[center.0, center.1], radius)
+            .iter()
+            .filter_map(|nearest| {
+                let geonameid = self.tree_index_to_geonameid.get(&(nearest.item as usize))?;
+                self.geonames.get(geonameid)
+            })
+            .filter(|city| filter(city))
+            .collect::<Vec<_>>();
+        cities.sort_unstable_by(|a, b| b.population.cmp(&a.population).then(a.id.cmp(&b.id)));
+        cities
+    }
+
+    /// Pick random city.
+    ///
+    /// Optional: weight by population, so big cities are picked more often
+    ///
+    /// Optional: filter by countries
+    ///
+    /// Result is reproducible for seeded `rng` and the same index.
+    pub fn random_city<R: Rng + ?Sized, T: AsRef<str>>(
+        &self,
+        rng: &mut R,
+        weight_by_population: bool,
+        countries: Option<&[T]>,
+    ) -> Option<&CitiesRecord> {
+        let total = self.tree_index_to_geonameid.len();
+
+        if countries.is_none() && !weight_by_population {
+            if total == 0 {
+                return None;
+            }
+            return self.get(self.tree_index_to_geonameid.get(&rng.gen_range(0..total))?);
+        }
+
+        let countries = countries.map(|c| {
+            c.iter()
+                .map(|code| country_code(code.as_ref()))
+                .collect::<HashSet<_>>()
+        });
+
+        // iterate by tree index to keep candidates order stable
+        let candidates = (0..total)
+            .filter_map(|index| self.get(self.tree_index_to_geonameid.get(&index)?))
+            .filter(|city| match countries.as_ref() {
+                Some(countries) => city
+                    .country
+                    .as_ref()
+                    .is_some_and(|c| countries.contains(&c.code)),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        if weight_by_population {
+            let weights =
+                WeightedIndex::new(candidates.iter().map(|c| c.population.max(1))).ok()?;
+            Some(candidates[weights.sample(rng)])
+        } else {
+            Some(candidates[rng.gen_range(0..candidates.len())])
+        }
+    }
+
+    /// Get country info by iso 2-letter country code.
+    pub fn country_info(&self, code: &str) -> Option<&CountryRecord> {
+        self.country_info_by_code.get(&country_code(code))
+    }
+
+    /// Get country info with capital city record by iso 2-letter country code.
+    pub fn country_info_with_capital(&self, country_code: &str) -> Option<CountryInfo<'_>> {
+        let country = self.country_info(country_code)?;
+        Some(CountryInfo {
+            capital: self.capital(&country.info.iso),
+            country,
+        })
+    }
+
+    /// Get admin division (first level) by code, e.g. `US.CA`.
+    pub fn admin1(&self, code: &str) -> Option<AdminDivisionInfo<'_>> {
+        self.admin_division_info(self.admin1_divisions.get(&code.to_uppercase())?)
+    }
+
+    /// Best fuzzy match of admin division (first level) by name, translation, code or
+    /// abbreviation (see [`ADMIN1_ABBREVIATIONS`]) with similarity score, e.g. `CA`, `Calif.`
+    /// or `Californa` for `US.CA`.
+    ///
+    /// Matches region token of structured queries like `Springfield, Ill.`, ambiguous tokens
+    /// (e.g. `WA`) are narrowed by `countries` codes, ties are resolved by order of `countries`
+    /// and then division code is preferred over abbreviation of other division.
+    pub fn find_admin1(
+        &self,
+        token: &str,
+        countries: Option<&[&str]>,
+        min_score: f32,
+    ) -> Option<(AdminDivisionInfo<'_>, f32)> {
+        let token = token.trim();
+        let compact = compact_token(token, self.case_folding);
+        if compact.is_empty() {
+            return None;
+        }
+        let normalized_token = normalize::normalize(token, self.case_folding);
+        let countries = countries.map(|c| c.iter().map(|c| country_code(c)).collect::<Vec<_>>());
+        let country_rank = |division: &AdminDivision| {
+            let code = division.code.split('.').next().unwrap_or_default();
+            match &countries {
+                Some(countries) => countries.iter().position(|c| c == code),
+                None => Some(0),
+            }
+        };
+
+        // score and whether token is the division code (preferred over listed abbreviations,
+        // e.g. `WA` is `US.WA` rather than Western Australia)
+        let score = |division: &AdminDivision| -> (f32, bool) {
+            let abbreviations = self.admin1_abbreviations.get(&division.id);
+            match abbreviations.and_then(|a| a.iter().position(|a| *a == compact)) {
+                Some(position) => (1.0, position == 0),
+                None => (
+                    self.place_score(
+                        division.id,
+                        &normalized_token,
+                        SimilarityMetric::JaroWinkler,
+                    ),
+                    false,
+                ),
+            }
+        };
+
+        self.admin1_divisions
+            .values()
+            .filter_map(|division| Some((division, country_rank(division)?)))
+            .map(|(division, rank)| (division, rank, score(division)))
+            .filter(|(_, _, (score, _))| *score >= min_score)
+            // equally scored divisions: of the first requested country, by code, stable choice
+            .max_by(|(lhs, lhs_rank, lhs_score), (rhs, rhs_rank, rhs_score)| {
+                lhs_score
+                    .0
+                    .partial_cmp(&rhs_score.0)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| rhs_rank.cmp(lhs_rank))
+                    .then_with(|| lhs_score.1.cmp(&rhs_score.1))
+                    .then_with(|| rhs.code.cmp(&lhs.code))
+            })
+            .and_then(|(division, _, (score, _))| {
+                Some((self.admin_division_info(division)?, score))
+            })
+    }
+
+    /// Get admin division (second level) by code, e.g. `US.CA.037`.
+    pub fn admin2(&self, code: &str) -> Option<AdminDivisionInfo<'_>> {
+        self.admin_division_info(self.admin2_divisions.get(&code.to_uppercase())?)
+    }
+
+    fn admin_division_info<'a>(
+        &'a self,
+        division: &'a AdminDivision,
+    ) -> Option<AdminDivisionInfo<'a>> {
+        Some(AdminDivisionInfo {
+            names: self.admin_names.get(&division.id),
+            country: division
+                .code
+                .split('.')
+                .next()
+                .and_then(|country_code| self.country_info(country_code)),
+            division,
+        })
+    }
+
+    /// Matched part of value by index normalization rules, see [`match_span`].
+    pub fn match_span(&self, value: &str, pattern: &str) -> Option<MatchSpan> {
+        match_span_with(value, pattern, self.case_folding)
+    }
+
+    /// Suggest pattern as matched against index entries (case folding of index, diacritics
+    /// stripping and [`DEFAULT_ABBREVIATIONS`] expansion).
+    pub fn normalize_query(&self, pattern: &str) -> NormalizedQuery {
+        self.normalize_query_with(pattern, DEFAULT_ABBREVIATIONS)
+    }
+
+    /// Suggest pattern as matched against index entries with custom abbreviations,
+    /// see [`SuggestOptions::abbreviations`].
+    pub fn normalize_query_with(
+        &self,
+        pattern: &str,
+        abbreviations: &[(&str, &str)],
+    ) -> NormalizedQuery {
+        let normalized = normalize::normalize(pattern, self.case_folding);
+        let expanded =
+            normalize::expand_abbreviations(&normalized, abbreviations, self.case_folding);
+        NormalizedQuery {
+            pattern: normalized,
+            expanded,
+        }
+    }
+
+    /// Aggregates of country indexed cities, `None` if country doesn't have any.
+    pub fn country_stats(&self, code: &str, largest: usize) -> Option<CountryStats<'_>> {
+        let country_code = country_code(code);
+        let mut cities = self
+            .geonames
+            .values()
+            .filter(|city| {
+                city.country
+                    .as_ref()
+                    .is_some_and(|c| c.code == country_code)
+            })
+            .collect::<Vec<_>>();
+        if cities.is_empty() {
+            return None;
+        }
+
+        let mut translations: HashMap<String, usize> = HashMap::new();
+        for city in &cities {
+            for lang in self.city_names(city).iter().flat_map(|names| names.keys()) {
+                *translations.entry(lang.to_owned()).or_default() += 1;
+            }
+        }
+
+        cities.sort_unstable_by(|a, b| b.population.cmp(&a.population).then(a.id.cmp(&b.id)));
+
+        Some(CountryStats {
+            population: cities.iter().map(|city| city.population as u64).sum(),
+            cities: cities.len(),
+            largest_cities: cities.into_iter().take(largest).collect(),
+            translations,
+        })
+    }
+
+    /// Suggest ranking profile of index metadata by name, e.g. `experiment`, see
+    /// [`SuggestRanking::Profile`]
+    pub fn ranking_profile(&self, name: &str) -> Option<RankingProfile> {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.ranking_profiles.get(name))
+            .copied()
+    }
+
+    /// Count of cities with translated name by isolanguage code, e.g. to populate languages selector.
+    ///
+    /// Computed on build and load.
+    pub fn language_coverage(&self) -> &HashMap<String, usize> {
+        &self.language_coverage
+    }
+
+    fn compute_language_coverage(&self) -> HashMap<String, usize> {
+        let mut coverage: HashMap<String, usize> = HashMap::new();
+        for city in self.geonames.values() {
+            for lang in self.city_names(city).iter().flat_map(|names| names.keys()) {
+                *coverage.entry(lang.to_owned()).or_default() += 1;
+            }
+        }
+        coverage
+    }
+
+    /// Approximate heap footprint of the engine parts, e.g. for capacity planning.
+    ///
+    /// Computed on build and load, only per-country kd-trees are counted on call.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.memory_usage;
+        usage.tree += self.country_trees.get().map_or(0, |trees| {
+            memory::map_bytes(trees)
+                + trees
+                    .iter()
+                    .map(|(code, country)| {
+                        code.capacity()
+                            + memory::vec_bytes(&country.indexes)
+                            + country.indexes.len()
+                                * (std::mem::size_of::<[f32; 2]>() + std::mem::size_of::<u32>())
+                    })
+                    .sum::<usize>()
+        });
+        usage
+    }
+
+    /// Recompute stats of indexed data, must be called after its change.
+    fn refresh_stats(&mut self) {
+        self.memory_usage = self.compute_memory_usage();
+        self.language_coverage = self.compute_language_coverage();
+    }
+
+    fn compute_memory_usage(&self) -> MemoryUsage {
+        let division = |division: Option<&AdminDivision>| {
+            division.map_or(0, |d| d.code.capacity() + d.name.capacity())
+        };
+        let cities = memory::map_bytes(&self.geonames)
+            + self
+                .geonames
+                .values()
+                .map(|city| {
+                    city.name.capacity()
+                        + city.timezone.capacity()
+                        + city.feature_code.capacity()
+                        + city
+                            .country
+                            .as_ref()
+                            .map_or(0, |c| c.code.capacity() + c.name.capacity())
+                        + division(city.admin_division.as_ref())
+                        + division(city.admin2_division.as_ref())
+                        + city.names.as_ref().map_or(0, memory::names_bytes)
+                        + city.former_names.as_ref().map_or(0, |names| {
+                            memory::vec_bytes(names) + memory::strings_bytes(names)
+                        })
+                })
+                .sum::<usize>()
+            + memory::map_bytes(&self.compressed_names)
+            + self
+                .compressed_names
+                .values()
+                .map(Vec::capacity)
+                .sum::<usize>();
+
+        let entries = [&self.entries, &self.former_entries, &self.translit_entries]
+            .into_iter()
+            .map(|entries| {
+                memory::vec_bytes(entries)
+                    + entries.iter().map(|e| e.value.capacity()).sum::<usize>()
+            })
+            .sum::<usize>()
+            + memory::map_bytes(&self.display_names)
+            + self
+                .display_names
+                .values()
+                .map(|names| memory::vec_bytes(names) + memory::strings_bytes(names))
+                .sum::<usize>();
+
+        let tree = memory::map_bytes(&self.tree_index_to_geonameid)
+            + self.tree_index_to_geonameid.len()
+                * (std::mem::size_of::<[f32; 2]>() + std::mem::size_of::<u32>());
+
+        let auxiliary = memory::map_bytes(&self.capitals)
+            + memory::strings_bytes(self.capitals.keys())
+            + memory::map_bytes(&self.country_info_by_code)
+            + self
+                .country_info_by_code
+                .iter()
+                .map(|(code, country)| {
+                    code.capacity()
+                        + country.names.as_ref().map_or(0, memory::names_bytes)
+                        + country
+                            .capital_names
+                            .as_ref()
+                            .map_or(0, memory::names_bytes)
+                })
+                .sum::<usize>()
+            + memory::map_bytes(&self.admin_names)
+            + self
+                .admin_names
+                .values()
+                .map(memory::names_bytes)
+                .sum::<usize>()
+            + [&self.admin1_divisions, &self.admin2_divisions]
+                .into_iter()
+                .map(|divisions| {
+                    memory::map_bytes(divisions)
+                        + divisions
+                            .iter()
+                            .map(|(code, d)| code.capacity() + division(Some(d)))
+                            .sum::<usize>()
+                })
+                .sum::<usize>()
+            + memory::map_bytes(&self.population_ranks)
+            + memory::map_bytes(&self.density_scales)
+            + [&self.place_names, &self.admin1_abbreviations]
+                .into_iter()
+                .map(|names| {
+                    memory::map_bytes(names)
+                        + names
+                            .values()
+                            .map(|n| memory::vec_bytes(n) + memory::strings_bytes(n))
+                            .sum::<usize>()
+                })
+                .sum::<usize>();
+
+        MemoryUsage {
+            cities,
+            entries,
+            tree,
+            auxiliary,
+        }
+    }
+
+    /// City population percentiles within its country and globally (precomputed on build).
+    pub fn population_rank(&self, city: &CitiesRecord) -> Option<PopulationRank> {
+        self.population_ranks.get(&city.id).copied()
+    }
+
+    /// Translations of city country name.
+    pub fn country_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        self.country_info(&city.country.as_ref()?.code)?
+            .names
+            .as_ref()
+    }
+
+    /// Translations of city admin division (first level) name.
+    pub fn admin1_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        self.admin_names.get(&city.admin_division.as_ref()?.id)
+    }
+
+    /// Translations of city admin division (second level) name.
+    pub fn admin2_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        self.admin_names.get(&city.admin2_division.as_ref()?.id)
+    }
+
+    /// Translations of city name.
+    ///
+    /// Compressed translations (see [`Engine::compress_names`]) are decompressed on each call.
+    pub fn city_names<'a>(
+        &'a self,
+        city: &'a CitiesRecord,
+    ) -> Option<Cow<'a, HashMap<String, String>>> {
+        if let Some(ref names) = city.names {
+            return Some(Cow::Borrowed(names));
+        }
+
+        let compressed = self.compressed_names.get(&city.id)?;
+        match decompress_names(compressed) {
+            Ok(names) => Some(Cow::Owned(names)),
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::error!("On decompress names of {}: {_e}", city.id);
+                None
+            }
+        }
+    }
+
+    /// City name translated to language.
+    pub fn city_name<'a>(&'a self, city: &'a CitiesRecord, lang: &str) -> Option<Cow<'a, str>> {
+        match self.city_names(city)? {
+            Cow::Borrowed(names) => names.get(lang).map(|name| Cow::Borrowed(name.as_str())),
+            Cow::Owned(mut names) => names.remove(lang).map(Cow::Owned),
+        }
+    }
+
+    /// Move cities translations into compressed side table.
+    ///
+    /// Trades a little CPU on access (see [`Engine::city_names`]) for a smaller resident index.
+    pub fn compress_names(&mut self) -> Result<(), Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        let now = Instant::now();
+
+        for city in self.geonames.values_mut() {
+            let Some(names) = city.names.take() else {
+                continue;
+            };
+            // in key order to get the same bytes for the same names
+            let data = bincode::serialize(&names.iter().collect::<BTreeMap<_, _>>())?;
+            self.compressed_names
+                .insert(city.id, lz4_flex::compress_prepend_size(&data));
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            "Compressed names of {} cities. took {}ms",
+            self.compressed_names.len(),
+            now.elapsed().as_millis(),
+        );
+
+        self.refresh_stats();
+        Ok(())
+    }
+
+    /// Apply runtime corrections (renames, population, suppressed cities) on top of loaded index,
+    /// overrides of unknown cities are skipped.
+    ///
+    /// Returns count of applied overrides.
+    pub fn apply_overrides(&mut self, overrides: &Overrides) -> usize {
+        let mut applied = 0;
+        let mut suppressed = HashSet::new();
+        let mut repopulated = HashSet::new();
+        // slim index doesn't have population ranks
+        let ranked = !self.population_ranks.is_empty();
+
+        for (id, item) in &overrides.cities {
+            if !self.geonames.contains_key(id) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Skip override of unknown city {id}");
+                continue;
+            }
+            applied += 1;
+
+            if item.suppress {
+                self.geonames.remove(id);
+                self.compressed_names.remove(id);
+                self.display_names.remove(id);
+                suppressed.insert(*id);
+                continue;
+            }
+
+            let names = (!item.names.is_empty()).then(|| {
+                let mut names = self
+                    .city_names(&self.geonames[id])
+                    .map(Cow::into_owned)
+                    .unwrap_or_default();
+                names.extend(item.names.clone());
+                names
+            });
+
+            let city = self.geonames.get_mut(id).expect("city exists");
+            if let Some(population) = item.population {
+                city.population = population;
+                repopulated.insert(*id);
+            }
+
+            let country_id = city.country.as_ref().map(|c| c.id);
+            let admin1_id = city.admin_division.as_ref().map(|a| a.id);
+            let displays = self.display_names.entry(*id).or_default();
+            let languages = &mut self.languages;
+            let values = item.name.iter().map(|name| (name, 0)).chain(
+                item.names
+                    .iter()
+                    .map(|(lang, name)| (name, intern_language(languages, lang))),
+            );
+            let first_entry = self.entries.len();
+            self.entries.extend(values.map(|(name, langs)| {
+                let value = normalize::normalize(name, self.case_folding);
+                Entry {
+                    id: *id,
+                    prominence: entry_prominence(city.population, &city.feature_code, &value),
+                    display: (value != *name).then(|| display_index(displays, name)),
+                    value,
+                    country_id,
+                    admin1_id,
+                    langs,
+                }
+            }));
+            merge_entries(&mut self.entries, first_entry);
+
+            #[cfg(feature = "translit")]
+            push_translit_entries(
+                &self.entries[first_entry..],
+                self.display_names.entry(*id).or_default(),
+                &mut self.translit_entries,
+            );
+
+            if let Some(name) = &item.name {
+                city.name = name.clone();
+            }
+            if let Some(names) = names {
+                city.names = Some(names);
+                self.compressed_names.remove(id);
+            }
+        }
+
+        for entry in &mut self.entries {
+            if !repopulated.contains(&entry.id) {
+                continue;
+            }
+            if let Some(city) = self.geonames.get(&entry.id) {
+                entry.prominence =
+                    entry_prominence(city.population, &city.feature_code, &entry.value);
+            }
+        }
+
+        if !suppressed.is_empty() {
+            self.entries.retain(|entry| !suppressed.contains(&entry.id));
+            self.former_entries
+                .retain(|entry| !suppressed.contains(&entry.id));
+            self.translit_entries
+                .retain(|entry| !suppressed.contains(&entry.id));
+            self.capitals.retain(|_, id| !suppressed.contains(id));
+
+            let items = self
+                .geonames
+                .values()
+                .map(|record| (record.id, [record.latitude, record.longitude]))
+                .collect::<Vec<_>>();
+            (self.tree_index_to_geonameid, self.tree) = build_tree(items);
+            self.country_trees = OnceLock::new();
+            self.single_country = single_country(self.geonames.values());
+            self.density_scales = density_scales(self.geonames.values());
+        }
+
+        if ranked && (!repopulated.is_empty() || !suppressed.is_empty()) {
+            self.population_ranks = population_ranks(self.geonames.values());
+        }
+
+        self.refresh_stats();
+        applied
+    }
+
+    /// Value of entry as in source (original casing)
+    fn display_name<'a>(&'a self, entry: &'a Entry) -> &'a str {
+        entry
+            .display
+            .and_then(|index| self.display_names.get(&entry.id)?.get(index as usize))
+            .map_or(&entry.value, String::as_str)
+    }
+
+    /// Check index consistency: entries, capitals and compressed names refer to existing cities,
+    /// coordinates are finite and compressed names are readable.
+    ///
+    /// See [`storage::Validation::Full`] to check indexes from untrusted sources on load.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .chain(&self.former_entries)
+            .chain(&self.translit_entries)
+            .find(|entry| !self.geonames.contains_key(&entry.id))
+        {
+            return Err(format!("Entry refers to unknown city {}", entry.id).into());
+        }
+
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .chain(&self.former_entries)
+            .chain(&self.translit_entries)
+            .find(|entry| entry.display.is_some() && self.display_name(entry) == entry.value)
+        {
+            return Err(
+                format!("Entry of city {} refers to unknown display name", entry.id).into(),
+            );
+        }
+
+        for (code, id) in &self.capitals {
+            if !self.geonames.contains_key(id) {
+                return Err(format!("Capital of {code} refers to unknown city {id}").into());
+            }
+        }
+
+        for (id, city) in &self.geonames {
+            if *id != city.id {
+                return Err(format!("City {} is stored by id {id}", city.id).into());
+            }
+            if !city.latitude.is_finite() || !city.longitude.is_finite() {
+                return Err(format!("City {id} has invalid coordinates").into());
+            }
+        }
+
+        for (id, compressed) in &self.compressed_names {
+            if !self.geonames.contains_key(id) {
+                return Err(format!("Compressed names refer to unknown city {id}").into());
+            }
+            decompress_names(compressed)
+                .map_err(|e| format!("Compressed names of city {id} are malformed: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that engine answers identically to `source` engine, e.g. that loaded index
+    /// dump answers as the freshly built engine.
+    ///
+    /// Sizes of indexes are compared, then suggest by name and reverse by coordinates of
+    /// (at most 100) sampled cities, error describes the first divergence.
+    pub fn verify_against(&self, source: &Engine) -> Result<(), Box<dyn Error>> {
+        for (what, count, expected) in [
+            ("cities", self.geonames.len(), source.geonames.len()),
+            ("entries", self.entries.len(), source.entries.len()),
+            (
+                "former entries",
+                self.former_entries.len(),
+                source.former_entries.len(),
+            ),
+            ("capitals", self.capitals.len(), source.capitals.len()),
+        ] {
+            if count != expected {
+                return Err(format!("Count of {what} is {count}, expected {expected}").into());
+            }
+        }
+
+        let ids = source
+            .geonames
+            .keys()
+            .copied()
+            .sorted_unstable()
+            .collect::<Vec<_>>();
+        let step = (ids.len() / VERIFY_PROBES).max(1);
+
+        for city in ids
+            .iter()
+            .step_by(step)
+            .filter_map(|id| source.geonames.get(id))
+        {
+            let Some(found) = self.geonames.get(&city.id) else {
+                return Err(format!("City {} is missing", city.id).into());
+            };
+            if found.name != city.name {
+                return Err(format!(
+                    "City {} is named {:?}, expected {:?}",
+                    city.id, found.name, city.name
+                )
+                .into());
+            }
+
+            let suggested = |engine: &Engine| {
+                engine
+                    .find_suggest(&city.name, SuggestOptions::new(VERIFY_LIMIT))
+                    .iter()
+                    .map(|item| (item.city.id, item.score.to_bits()))
+                    .collect::<Vec<_>>()
+            };
+            if suggested(self) != suggested(source) {
+                return Err(format!("Suggest of {:?} differs", city.name).into());
+            }
+
+            let loc = (city.latitude, city.longitude);
+            let reversed = |engine: &Engine| {
+                engine
+                    .find_reverse(loc, ReverseOptions::new(VERIFY_LIMIT))
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|item| (item.city.id, item.distance.to_bits()))
+                    .collect::<Vec<_>>()
+            };
+            if reversed(self) != reversed(source) {
+                return Err(format!("Reverse of {loc:?} differs").into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Normalize build specific metadata, so builds from the same sources are dumped to
+    /// byte-identical files (e.g. to verify index artifacts by hash).
+    ///
+    /// Creation time is set to `created_at`, e.g. `SOURCE_DATE_EPOCH` of reproducible builds.
+    pub fn make_reproducible(&mut self, created_at: std::time::SystemTime) {
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.created_at = created_at;
+        }
+    }
+
+    /// Strip everything except suggest/reverse essentials: translations, country extras,
+    /// admin divisions lookups and population ranks.
+    ///
+    /// Searchable names (including translated ones) are kept, so suggest results are the same.
+    pub fn into_slim(mut self) -> Engine {
+        for city in self.geonames.values_mut() {
+            city.names = None;
+            city.former_names = None;
+        }
+        self.compressed_names = HashMap::new();
+        self.admin_names = HashMap::new();
+        self.admin1_divisions = HashMap::new();
+        self.admin2_divisions = HashMap::new();
+        self.population_ranks = HashMap::new();
+
+        for country in self.country_info_by_code.values_mut() {
+            country.names = None;
+            country.capital_names = None;
+            let info = &mut country.info;
+            for extra in [
+                &mut info.iso_numeric,
+                &mut info.fips,
+                &mut info.capital,
+                &mut info.area,
+                &mut info.continent,
+                &mut info.tld,
+                &mut info.currency_code,
+                &mut info.currency_name,
+                &mut info.phone,
+                &mut info.postal_code_format,
+                &mut info.postal_code_regex,
+                &mut info.languages,
+                &mut info.neighbours,
+                &mut info.equivalent_fips_code,
+            ] {
+                std::mem::take(extra);
+            }
+        }
+
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata
+                .extra
+                .insert("profile".to_owned(), "slim".to_owned());
+        }
+
+        self.index_place_names();
+        self.refresh_stats();
+        self
+    }
+
+    /// Copy of the engine with translations only for given languages.
+    ///
+    /// Same as built from the same sources with `filter_languages` subset, but without parsing them again.
+    pub fn with_languages(&self, languages: &[&str]) -> Result<Engine, Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        let now = Instant::now();
+
+        let mut language_warnings = Vec::new();
+        let languages = canonical_languages(languages, &mut language_warnings);
+
+        let retain = |names: &HashMap<String, String>| -> Option<HashMap<String, String>> {
+            let names = names
+                .iter()
+                .filter(|(lang, _)| languages.contains(lang))
+                .map(|(lang, name)| (lang.to_owned(), name.to_owned()))
+                .collect::<HashMap<_, _>>();
+            (!names.is_empty()).then_some(names)
+        };
+
+        let geonames = self
+            .geonames
+            .iter()
+            .map(|(id, city)| {
+                let mut city = city.clone();
+                city.names = self
+                    .city_names(&city)
+                    .and_then(|names| retain(names.as_ref()));
+                (*id, city)
+            })
+            .collect();
+
+        let country_info_by_code = self
+            .country_info_by_code
+            .iter()
+            .map(|(code, country)| {
+                let mut country = country.clone();
+                country.names = country.names.as_ref().and_then(retain);
+                country.capital_names = country.capital_names.as_ref().and_then(retain);
+                (code.to_owned(), country)
+            })
+            .collect();
+
+        let admin_names = self
+            .admin_names
+            .iter()
+            .filter_map(|(id, names)| Some((*id, retain(names)?)))
+            .collect();
+
+        let metadata = self.metadata.clone().map(|mut metadata| {
+            metadata.source.filter_languages = languages.clone();
+            metadata.language_warnings = language_warnings;
+            metadata
+        });
+
+        let mut engine = Engine::from(EngineDump {
+            entries: self.entries.clone(),
+            former_entries: self.former_entries.clone(),
+            translit_entries: self.translit_entries.clone(),
+            display_names: self.display_names.clone(),
+            languages: self.languages.clone(),
+            geonames,
+            capitals: self.capitals.clone(),
+            country_info_by_code,
+            admin_names,
+            admin1_divisions: self.admin1_divisions.clone(),
+            admin2_divisions: self.admin2_divisions.clone(),
+            compressed_names: HashMap::new(),
+            population_ranks: self.population_ranks.clone(),
+            density_scales: self.density_scales.clone(),
+            case_folding: self.case_folding,
+            metadata,
+        });
+
+        if !self.compressed_names.is_empty() {
+            engine.compress_names()?;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            "Engine with languages {languages:?} ready. took {}ms",
+            now.elapsed().as_millis(),
+        );
+
+        Ok(engine)
+    }
+
+    pub fn new_from_files<P: AsRef<std::path::Path>>(
+        SourceFileOptions {
+            cities,
+            names,
+            countries,
+            filter_languages,
+            admin1_codes,
+            admin2_codes,
+            case_folding,
+            max_malformed_rows,
+            entry_filter,
+            duplicates,
+        }: SourceFileOptions<P>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = |p: &P| p.as_ref().display().to_string();
+        let source = EngineSourceMetadata {
+            cities: path(&cities),
+            names: names.as_ref().map(path),
+            countries: countries.as_ref().map(path),
+            admin1_codes: admin1_codes.as_ref().map(path),
+            admin2_codes: admin2_codes.as_ref().map(path),
+            filter_languages: filter_languages.iter().map(|l| l.to_string()).collect(),
+            etag: HashMap::default(),
+        };
+
+        let mut engine = Engine::new_from_files_content(SourceFileContentOptions {
             cities: std::fs::read_to_string(cities)?,
             names: if let Some(p) = names {
                 Some(std::fs::read_to_string(p)?)
@@ -529,7 +4236,17 @@ impl Engine {
                 None
             },
             filter_languages,
-        })
+            case_folding,
+            max_malformed_rows,
+            entry_filter,
+            duplicates,
+        })?;
+
+        if let Some(metadata) = engine.metadata.as_mut() {
+            metadata.source = source;
+        }
+
+        Ok(engine)
     }
 
     pub fn new_from_files_content(
@@ -540,32 +4257,72 @@ impl Engine {
             filter_languages,
             admin1_codes,
             admin2_codes,
+            case_folding,
+            max_malformed_rows,
+            entry_filter,
+            duplicates,
         }: SourceFileContentOptions,
     ) -> Result<Self, Box<dyn Error>> {
         #[cfg(feature = "tracing")]
         let now = Instant::now();
 
-        let records = split_content_to_n_parts(&cities, rayon::current_num_threads())
-            .par_iter()
-            .map(|chunk| {
-                let mut rdr = csv::ReaderBuilder::new()
-                    .has_headers(false)
-                    .delimiter(b'\t')
-                    .from_reader(chunk.as_bytes());
+        let mut malformed_rows: HashMap<String, MalformedRows> = HashMap::new();
 
-                rdr.deserialize()
-                    .filter_map(|row| {
-                        let record: CitiesRecordRaw = row.ok()?;
-                        Some(record)
-                    })
-                    .collect::<Vec<CitiesRecordRaw>>()
-            })
-            .reduce(Vec::new, |mut m1, ref mut m2| {
-                m1.append(m2);
-                m1
-            });
+        let mut language_warnings = Vec::new();
+        let filter_languages = canonical_languages(&filter_languages, &mut language_warnings);
+        // unknown language of alternate names goes first
+        let languages = std::iter::once(String::new())
+            .chain(filter_languages.iter().cloned())
+            .collect::<Vec<_>>();
+        if languages.len() > MAX_LANGUAGES {
+            return Err(format!(
+                "Too many filter languages: {} (max {})",
+                filter_languages.len(),
+                MAX_LANGUAGES - 1
+            )
+            .into());
+        }
+
+        let (mut records, malformed) =
+            split_content_to_n_parts(&cities, rayon::current_num_threads())
+                .par_iter()
+                .map(|chunk| {
+                    let mut rdr = csv::ReaderBuilder::new()
+                        .has_headers(false)
+                        .delimiter(b'\t')
+                        .from_reader(chunk.as_bytes());
+
+                    let mut malformed = MalformedRows::default();
+                    let records = rdr
+                        .deserialize()
+                        .filter_map(|row| {
+                            let record: CitiesRecordRaw =
+                                row.map_err(|e| malformed.push(e)).ok()?;
+                            Some(record)
+                        })
+                        .collect::<Vec<CitiesRecordRaw>>();
+                    (records, malformed)
+                })
+                .reduce(
+                    || (Vec::new(), MalformedRows::default()),
+                    |(mut m1, e1), (ref mut m2, e2)| {
+                        m1.append(m2);
+                        (m1, e1.merge(e2))
+                    },
+                );
+        malformed_rows.insert("cities".to_owned(), malformed);
+
+        let duplicate_rows = dedup_cities(&mut records, duplicates);
+        #[cfg(feature = "tracing")]
+        if duplicate_rows > 0 {
+            tracing::warn!("Duplicate cities rows resolved by {duplicates:?}: {duplicate_rows}");
+        }
 
         let mut geonames: Vec<CitiesRecord> = Vec::with_capacity(records.len());
+        let mut former_entries: Vec<Entry> = Vec::new();
+        #[cfg_attr(not(feature = "translit"), allow(unused_mut))]
+        let mut translit_entries: Vec<Entry> = Vec::new();
+        let mut display_names: HashMap<u32, Vec<String>> = HashMap::new();
         let mut entries: Vec<Entry> = Vec::with_capacity(
             records.len()
                 * if !filter_languages.is_empty() {
@@ -595,6 +4352,7 @@ impl Engine {
                     .delimiter(b'\t')
                     .from_reader(contents.as_bytes());
 
+                let mut malformed = MalformedRows::default();
                 let countries = rdr
                     .deserialize()
                     .filter_map(|row| {
@@ -603,12 +4361,13 @@ impl Engine {
                                 #[cfg(feature = "tracing")]
                                 tracing::error!("On read country row: {e}");
 
-                                e
+                                malformed.push(e)
                             })
                             .ok()?;
                         Some((record.iso.clone(), record))
                     })
                     .collect::<HashMap<String, CountryRecordRaw>>();
+                malformed_rows.insert("countries".to_owned(), malformed);
 
                 #[cfg(feature = "tracing")]
                 tracing::info!(
@@ -633,10 +4392,12 @@ impl Engine {
                     .delimiter(b'\t')
                     .from_reader(contents.as_bytes());
 
+                let mut malformed = MalformedRows::default();
                 let admin_division = rdr
                     .deserialize()
                     .filter_map(|row| {
-                        let record: Admin1CodeRecordRaw = row.ok()?;
+                        let record: Admin1CodeRecordRaw =
+                            row.map_err(|e| malformed.push(e)).ok()?;
                         Some((
                             record.code.clone(),
                             AdminDivision {
@@ -647,6 +4408,7 @@ impl Engine {
                         ))
                     })
                     .collect::<HashMap<String, AdminDivision>>();
+                malformed_rows.insert("admin1_codes".to_owned(), malformed);
 
                 #[cfg(feature = "tracing")]
                 tracing::info!(
@@ -671,10 +4433,12 @@ impl Engine {
                     .delimiter(b'\t')
                     .from_reader(contents.as_bytes());
 
+                let mut malformed = MalformedRows::default();
                 let admin_division = rdr
                     .deserialize()
                     .filter_map(|row| {
-                        let record: Admin2CodeRecordRaw = row.ok()?;
+                        let record: Admin2CodeRecordRaw =
+                            row.map_err(|e| malformed.push(e)).ok()?;
                         Some((
                             record.code.clone(),
                             AdminDivision {
@@ -685,6 +4449,7 @@ impl Engine {
                         ))
                     })
                     .collect::<HashMap<String, AdminDivision>>();
+                malformed_rows.insert("admin2_codes".to_owned(), malformed);
 
                 #[cfg(feature = "tracing")]
                 tracing::info!(
@@ -698,7 +4463,8 @@ impl Engine {
             None => None,
         };
 
-        let mut names_by_id: Option<HashMap<u32, HashMap<String, String>>> = match names {
+        let keep_former_names = entry_filter.former_names;
+        let (mut names_by_id, mut former_names_by_id) = match names {
             Some(contents) => {
                 #[cfg(feature = "tracing")]
                 let now = Instant::now();
@@ -738,110 +4504,134 @@ impl Engine {
 
                 // TODO: split to N parts can split one geonameid and build not accurate index
                 // use rayon::current_num_threads() instead of 1
-                let names_by_id = split_content_to_n_parts(&contents, 1)
-                    .par_iter()
-                    .map(move |chunk| {
-                        let mut rdr = csv::ReaderBuilder::new()
-                            .has_headers(false)
-                            .delimiter(b'\t')
-                            .from_reader(chunk.as_bytes());
-
-                        let mut names_by_id: HashMap<u32, HashMap<String, AlternateNamesRaw>> =
-                            HashMap::new();
-
-                        for row in rdr.deserialize() {
-                            let record: AlternateNamesRaw = if let Ok(r) = row {
-                                r
-                            } else {
-                                continue;
-                            };
-
-                            let is_city_name = city_geoids.contains(&record.geonameid);
-                            let mut skip = !is_city_name;
-
-                            if skip {
-                                skip = !country_geoids.contains(&record.geonameid)
-                            }
+                let (names_by_id, former_names_by_id, malformed) =
+                    split_content_to_n_parts(&contents, 1)
+                        .par_iter()
+                        .map(move |chunk| {
+                            let mut rdr = csv::ReaderBuilder::new()
+                                .has_headers(false)
+                                .delimiter(b'\t')
+                                .from_reader(chunk.as_bytes());
+
+                            let mut names_by_id: HashMap<u32, HashMap<String, AlternateNamesRaw>> =
+                                HashMap::new();
+                            let mut former_names_by_id: HashMap<u32, Vec<String>> = HashMap::new();
+
+                            let mut malformed = MalformedRows::default();
+
+                            for row in rdr.deserialize() {
+                                let record: AlternateNamesRaw = match row {
+                                    Ok(r) => r,
+                                    Err(e) => {
+                                        malformed.push(e);
+                                        continue;
+                                    }
+                                };
+
+                                let is_city_name = city_geoids.contains(&record.geonameid);
+                                let mut skip = !is_city_name;
+
+                                if skip {
+                                    skip = !country_geoids.contains(&record.geonameid)
+                                }
 
-                            if skip {
-                                skip = !admin1_geoids.contains(&record.geonameid)
-                            }
+                                if skip {
+                                    skip = !admin1_geoids.contains(&record.geonameid)
+                                }
 
-                            if skip {
-                                skip = !admin2_geoids.contains(&record.geonameid)
-                            }
+                                if skip {
+                                    skip = !admin2_geoids.contains(&record.geonameid)
+                                }
 
-                            // entry not used
-                            if skip {
-                                continue;
-                            }
+                                // entry not used
+                                if skip {
+                                    continue;
+                                }
 
-                            // skip short not preferred names for cities
-                            if is_city_name
-                                && record.is_short_name == "1"
-                                && record.is_preferred_name != "1"
-                            {
-                                continue;
-                            }
+                                // skip short not preferred names for cities
+                                if is_city_name
+                                    && record.is_short_name == "1"
+                                    && record.is_preferred_name != "1"
+                                {
+                                    continue;
+                                }
 
-                            if record.is_colloquial == "1" {
-                                continue;
-                            }
-                            if record.is_historic == "1" {
-                                continue;
-                            }
+                                if record.is_colloquial == "1" {
+                                    continue;
+                                }
+                                if record.is_historic == "1" {
+                                    // skip pseudo languages (`link`, `post`, `iata`, ...)
+                                    if keep_former_names
+                                        && is_city_name
+                                        && record.isolanguage.len() <= 3
+                                    {
+                                        former_names_by_id
+                                            .entry(record.geonameid)
+                                            .or_default()
+                                            .push(record.alternate_name);
+                                    }
+                                    continue;
+                                }
 
-                            // filter by languages
-                            if !filter_languages.contains(&record.isolanguage.as_str()) {
-                                continue;
-                            }
+                                // filter by languages
+                                if !filter_languages.contains(&record.isolanguage) {
+                                    continue;
+                                }
 
-                            let lang = record.isolanguage.to_owned();
+                                let lang = record.isolanguage.to_owned();
 
-                            if let Some(item) = names_by_id.get_mut(&record.geonameid) {
-                                // don't overwrite preferred name
-                                let is_current_preferred_name = item
-                                    .get(&record.isolanguage)
-                                    .map(|i| i.is_preferred_name == "1")
-                                    .unwrap_or(false);
+                                if let Some(item) = names_by_id.get_mut(&record.geonameid) {
+                                    // don't overwrite preferred name
+                                    let is_current_preferred_name = item
+                                        .get(&record.isolanguage)
+                                        .map(|i| i.is_preferred_name == "1")
+                                        .unwrap_or(false);
 
-                                if !is_current_preferred_name {
-                                    item.insert(lang, record);
+                                    if !is_current_preferred_name {
+                                        item.insert(lang, record);
+                                    }
+                                } else {
+                                    let mut map: HashMap<String, AlternateNamesRaw> =
+                                        HashMap::new();
+                                    let geonameid = record.geonameid;
+                                    map.insert(lang.to_owned(), record);
+                                    names_by_id.insert(geonameid, map);
                                 }
-                            } else {
-                                let mut map: HashMap<String, AlternateNamesRaw> = HashMap::new();
-                                let geonameid = record.geonameid;
-                                map.insert(lang.to_owned(), record);
-                                names_by_id.insert(geonameid, map);
                             }
-                        }
 
-                        // convert names to simple struct
-                        let result: HashMap<u32, HashMap<String, String>> =
-                            names_by_id.iter().fold(HashMap::new(), |mut acc, c| {
-                                let (geonameid, names) = c;
-                                acc.insert(
-                                    *geonameid,
-                                    names.iter().fold(
-                                        HashMap::new(),
-                                        |mut accn: HashMap<String, String>, n| {
-                                            let (isolanguage, n) = n;
-                                            accn.insert(
-                                                isolanguage.to_owned(),
-                                                n.alternate_name.to_owned(),
-                                            );
-                                            accn
-                                        },
-                                    ),
-                                );
-                                acc
-                            });
-                        result
-                    })
-                    .reduce(HashMap::new, |mut m1, m2| {
-                        m1.extend(m2);
-                        m1
-                    });
+                            // convert names to simple struct
+                            let result: HashMap<u32, HashMap<String, String>> =
+                                names_by_id.iter().fold(HashMap::new(), |mut acc, c| {
+                                    let (geonameid, names) = c;
+                                    acc.insert(
+                                        *geonameid,
+                                        names.iter().fold(
+                                            HashMap::new(),
+                                            |mut accn: HashMap<String, String>, n| {
+                                                let (isolanguage, n) = n;
+                                                accn.insert(
+                                                    isolanguage.to_owned(),
+                                                    n.alternate_name.to_owned(),
+                                                );
+                                                accn
+                                            },
+                                        ),
+                                    );
+                                    acc
+                                });
+                            (result, former_names_by_id, malformed)
+                        })
+                        .reduce(
+                            || (HashMap::new(), HashMap::new(), MalformedRows::default()),
+                            |(mut m1, mut f1, e1), (m2, f2, e2)| {
+                                m1.extend(m2);
+                                for (id, names) in f2 {
+                                    f1.entry(id).or_default().extend(names);
+                                }
+                                (m1, f1, e1.merge(e2))
+                            },
+                        );
+                malformed_rows.insert("names".to_owned(), malformed);
 
                 #[cfg(feature = "tracing")]
                 tracing::info!(
@@ -850,11 +4640,29 @@ impl Engine {
                     now.elapsed().as_millis(),
                 );
 
-                Some(names_by_id)
+                (Some(names_by_id), former_names_by_id)
             }
-            None => None,
+            None => (None, HashMap::new()),
         };
 
+        malformed_rows.retain(|_, rows| rows.count > 0);
+
+        #[cfg(feature = "tracing")]
+        for (source, rows) in &malformed_rows {
+            tracing::warn!("Malformed {source} rows: {}", rows.count);
+        }
+
+        if let Some(max) = max_malformed_rows {
+            if let Some((source, rows)) = malformed_rows.iter().find(|(_, rows)| rows.count > max) {
+                return Err(format!(
+                    "Too many malformed {source} rows: {} (max {max}), first errors: {}",
+                    rows.count,
+                    rows.samples.join("; ")
+                )
+                .into());
+            }
+        }
+
         let mut capitals: HashMap<String, u32> =
             HashMap::with_capacity(if let Some(items) = &country_by_code {
                 items.len()
@@ -862,6 +4670,10 @@ impl Engine {
                 0
             });
 
+        let mut admin_names: HashMap<u32, HashMap<String, String>> = HashMap::new();
+        let mut admin1_divisions: HashMap<String, AdminDivision> = HashMap::new();
+        let mut admin2_divisions: HashMap<String, AdminDivision> = HashMap::new();
+
         for record in records {
             // INCLUDE:
             // PPL	populated place	a city, town, village, or other agglomeration of buildings where people live and work
@@ -890,64 +4702,143 @@ impl Engine {
                 _ => {}
             };
 
-            let is_capital = feature_code == "PPLC";
-
-            let country_id = country_by_code
-                .as_ref()
-                .and_then(|m| m.get(&record.country_code).map(|c| c.geonameid));
+            let is_capital = feature_code == "PPLC";
+
+            let country_id = country_by_code
+                .as_ref()
+                .and_then(|m| m.get(&record.country_code).map(|c| c.geonameid));
+
+            let admin_division = if let Some(ref a) = admin1_by_code {
+                a.get(&format!("{}.{}", record.country_code, record.admin1_code))
+                    .cloned()
+            } else {
+                None
+            };
+
+            let admin1_id = admin_division.as_ref().map(|a| a.id);
 
-            entries.push(Entry {
+            let first_entry = entries.len();
+            let mut displays = Vec::new();
+            let mut entry = |value: String, display: &str, langs: u64| Entry {
                 id: record.geonameid,
-                value: record.name.to_lowercase().to_owned(),
+                prominence: entry_prominence(record.population, feature_code, &value),
+                display: (value != display).then(|| display_index(&mut displays, display)),
+                value,
                 country_id,
-            });
+                admin1_id,
+                langs,
+            };
+
+            entries.push(entry(
+                normalize::normalize(&record.name, case_folding),
+                &record.name,
+                0,
+            ));
+
+            // cyrillic names are also searchable by romanization
+            #[cfg(feature = "romanize")]
+            if let Some(key) = normalize::romanize_key(&record.name) {
+                entries.push(entry(key, &record.name, 0));
+            }
 
             if record.name != record.asciiname {
-                entries.push(Entry {
-                    id: record.geonameid,
-                    value: record.asciiname.to_lowercase().to_owned(),
-                    country_id,
-                });
+                entries.push(entry(
+                    normalize::normalize(&record.asciiname, case_folding),
+                    &record.asciiname,
+                    0,
+                ));
             }
 
-            for altname in record.alternatenames.split(',') {
-                entries.push(Entry {
-                    id: record.geonameid,
-                    value: altname.to_lowercase(),
-                    country_id,
-                });
+            // language of alternate name is known only by translations of names file
+            let city_names = names_by_id
+                .as_ref()
+                .and_then(|names| names.get(&record.geonameid));
+
+            // historic names which are not current names (in any language) of city
+            let former_names = former_names_by_id
+                .remove(&record.geonameid)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| entry_filter.apply(name))
+                .filter(|name| {
+                    name != &record.name
+                        && name != &record.asciiname
+                        && !city_names
+                            .into_iter()
+                            .flatten()
+                            .any(|(_, current)| current == name)
+                })
+                .map(|name| name.into_owned())
+                .sorted_unstable()
+                .dedup()
+                .collect::<Vec<_>>();
+
+            for name in &former_names {
+                former_entries.push(entry(
+                    normalize::normalize(name, case_folding),
+                    name,
+                    language_mask(&languages, ""),
+                ));
             }
 
-            let country = if let Some(ref c) = country_by_code {
-                if is_capital {
-                    capitals.insert(record.country_code.to_string(), record.geonameid);
+            for altname in record.alternatenames.split(',') {
+                let Some(altname) = entry_filter.apply(altname) else {
+                    continue;
+                };
+                let altname = altname.as_ref();
+
+                if former_names.iter().any(|name| name == altname) {
+                    continue;
                 }
-                c.get(&record.country_code).cloned()
-            } else {
-                None
-            };
 
-            let country_names = if let Some(ref c) = country {
-                match names_by_id {
-                    Some(ref names) => names.get(&c.geonameid).cloned(),
-                    None => None,
+                let langs = city_names
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, name)| name.as_str() == altname)
+                    .fold(0, |mask, (lang, _)| mask | language_mask(&languages, lang));
+                let langs = if langs == 0 {
+                    language_mask(&languages, "")
+                } else {
+                    langs
+                };
+
+                // chinese names are also searchable by pinyin
+                #[cfg(feature = "cjk")]
+                if let Some(key) = normalize::pinyin_key(altname) {
+                    entries.push(entry(key, altname, langs));
                 }
-            } else {
-                None
-            };
 
-            let admin_division = if let Some(ref a) = admin1_by_code {
-                a.get(&format!("{}.{}", record.country_code, record.admin1_code))
-                    .cloned()
-            } else {
-                None
-            };
+                #[cfg(feature = "romanize")]
+                if let Some(key) = normalize::romanize_key(altname) {
+                    entries.push(entry(key, altname, langs));
+                }
 
-            let admin1_names = if let Some(ref a) = admin_division {
-                match names_by_id {
-                    Some(ref names) => names.get(&a.id).cloned(),
-                    None => None,
+                entries.push(entry(
+                    normalize::normalize(altname, case_folding),
+                    altname,
+                    langs,
+                ));
+            }
+
+            // e.g. alternate name of several languages or the same as city name
+            merge_entries(&mut entries, first_entry);
+
+            #[cfg(feature = "translit")]
+            push_translit_entries(
+                &entries[first_entry..],
+                &mut displays,
+                &mut translit_entries,
+            );
+
+            if !displays.is_empty() {
+                display_names.insert(record.geonameid, displays);
+            }
+
+            let country = if let Some(ref c) = country_by_code {
+                if is_capital {
+                    capitals.insert(record.country_code.to_string(), record.geonameid);
                 }
+                c.get(&record.country_code).cloned()
             } else {
                 None
             };
@@ -962,14 +4853,29 @@ impl Engine {
                 None
             };
 
-            let admin2_names = if let Some(ref a) = admin2_division {
-                match names_by_id {
-                    Some(ref names) => names.get(&a.id).cloned(),
-                    None => None,
+            if let Some(ref a) = admin_division {
+                admin1_divisions
+                    .entry(a.code.clone())
+                    .or_insert_with(|| a.clone());
+            }
+            if let Some(ref a) = admin2_division {
+                admin2_divisions
+                    .entry(a.code.clone())
+                    .or_insert_with(|| a.clone());
+            }
+
+            // admin divisions translations are stored once per division
+            if let Some(ref names) = names_by_id {
+                for division in [&admin_division, &admin2_division].into_iter().flatten() {
+                    if admin_names.contains_key(&division.id) {
+                        continue;
+                    }
+                    if let Some(division_names) = names.get(&division.id) {
+                        admin_names.insert(division.id, division_names.clone());
+                    }
                 }
-            } else {
-                None
-            };
+            }
+
             geonames.push(CitiesRecord {
                 id: record.geonameid,
                 name: record.name,
@@ -990,37 +4896,65 @@ impl Engine {
                     }
                     None => None,
                 },
-                country_names,
-                admin1_names,
-                admin2_names,
                 population: record.population,
+                feature_code: record.feature_code,
+                former_names: (!former_names.is_empty()).then_some(former_names),
             });
         }
 
+        // ids are unique, see `dedup_cities`
         geonames.sort_unstable_by_key(|item| item.id);
-        geonames.dedup_by_key(|item| item.id);
 
-        let tree_index_to_geonameid = HashMap::from_iter(
-            geonames
-                .iter()
-                .enumerate()
-                .map(|(index, item)| (index, item.id)),
-        );
+        let mut bbox_by_code: HashMap<&str, BBox> = HashMap::new();
+        for item in geonames.iter() {
+            let Some(country) = item.country.as_ref() else {
+                continue;
+            };
+            bbox_by_code
+                .entry(&country.code)
+                .and_modify(|bbox| bbox.extend(item.latitude, item.longitude))
+                .or_insert_with(|| BBox::from_point(item.latitude, item.longitude));
+        }
+        let bbox_by_code = bbox_by_code
+            .into_iter()
+            .map(|(code, bbox)| (code.to_owned(), bbox))
+            .collect::<HashMap<String, BBox>>();
 
-        let tree = ImmutableKdTree::new_from_slice(
-            geonames
-                .iter()
-                .map(|item| [item.latitude, item.longitude])
-                .collect::<Vec<_>>()
-                .as_slice(),
-        );
+        let items = geonames
+            .par_iter()
+            .map(|item| (item.id, [item.latitude, item.longitude]))
+            .collect::<Vec<_>>();
+
+        let ((tree_index_to_geonameid, tree), population_ranks) =
+            rayon::join(|| build_tree(items), || population_ranks(geonames.iter()));
+        let density_scales = density_scales(geonames.iter());
 
-        let engine = Engine {
+        let single_country = single_country(geonames.iter());
+
+        let mut engine = Engine {
             geonames: HashMap::from_iter(geonames.into_iter().map(|item| (item.id, item))),
             tree_index_to_geonameid,
             tree,
+            place_names: HashMap::new(),
+            admin1_abbreviations: HashMap::new(),
+            memory_usage: MemoryUsage::default(),
+            language_coverage: HashMap::new(),
+            country_trees: OnceLock::new(),
             entries,
-            metadata: None,
+            former_entries,
+            translit_entries,
+            display_names,
+            languages,
+            metadata: Some(EngineMetadata {
+                malformed_rows,
+                language_warnings,
+                duplicate_rows,
+                single_country: single_country.clone(),
+                ..Default::default()
+            }),
+            single_country,
+            query_hook: None,
+            ranker: None,
             country_info_by_code: if let Some(country_by_code) = country_by_code {
                 HashMap::from_iter(country_by_code.into_iter().map(|(code, country)| {
                     let country_record = CountryRecord {
@@ -1037,6 +4971,7 @@ impl Engine {
                             }
                             None => None,
                         },
+                        bbox: bbox_by_code.get(&code).copied(),
                         info: country,
                     };
 
@@ -1046,9 +4981,22 @@ impl Engine {
                 HashMap::new()
             },
             capitals,
+            admin_names,
+            admin1_divisions,
+            admin2_divisions,
+            compressed_names: HashMap::new(),
+            population_ranks,
+            density_scales,
+            case_folding,
             #[cfg(feature = "geoip2_support")]
             geoip2_reader: None,
+            #[cfg(feature = "geoip2_support")]
+            geoip2_cache: None,
+            #[cfg(feature = "geoip2_support")]
+            geoip2_cache_hits: AtomicU64::new(0),
         };
+        engine.index_place_names();
+        engine.refresh_stats();
 
         #[cfg(feature = "tracing")]
         tracing::info!(
@@ -1068,6 +5016,13 @@ impl Engine {
         &mut self,
         path: P,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_geoip2(GeoIP2Data::read(path)?)
+    }
+
+    /// Initialize geoip2 reader of database read by [`GeoIP2Data::read`], e.g. read in parallel
+    /// with index load, see [`Engine::load_geoip2`].
+    #[cfg(feature = "geoip2_support")]
+    pub fn set_geoip2(&mut self, data: GeoIP2Data) -> Result<(), Box<dyn std::error::Error>> {
         // consume and release memory of previously leaked buffer and reader
         if let Some((b, r)) = self.geoip2_reader.take() {
             // make Box<T> from raw pointer to drop it
@@ -1078,24 +5033,72 @@ impl Engine {
         }
 
         // leak geoip buffer and reader with reference to buffer
-        let buffer = std::fs::read(path)?;
-        let buffer: &'static Vec<u8> = Box::leak(Box::new(buffer));
+        let buffer: &'static Vec<u8> = Box::leak(Box::new(data.0));
         let reader = Reader::<City>::from_bytes(buffer).map_err(GeoIP2Error)?;
         let reader: &'static Reader<City> = Box::leak(Box::new(reader));
 
         self.geoip2_reader = Some((buffer, reader));
 
+        // cached lookups belong to previous database
+        if let Some(cache) = self.geoip2_cache.as_ref() {
+            cache.lock().map_err(|e| e.to_string())?.clear();
+        }
+
         Ok(())
     }
 
+    /// Cache up to `capacity` geoip2 lookups (ip -> geonameid), zero disables cache.
+    ///
+    /// Cache is invalidated on [`Engine::load_geoip2`].
+    #[cfg(feature = "geoip2_support")]
+    pub fn set_geoip2_cache(&mut self, capacity: usize) {
+        self.geoip2_cache = NonZeroUsize::new(capacity).map(|c| Mutex::new(LruCache::new(c)));
+    }
+
+    /// Count of geoip2 lookups served from cache since engine start, see
+    /// [`Engine::set_geoip2_cache`].
+    #[cfg(feature = "geoip2_support")]
+    pub fn geoip2_cache_hits(&self) -> u64 {
+        self.geoip2_cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// Report every suggest/reverse query to `hook`, `None` disables reporting.
+    pub fn set_query_hook(&mut self, hook: Option<Arc<dyn QueryHook>>) {
+        self.query_hook = hook;
+    }
+
+    /// Adjust rank of suggested cities by `ranker` before sorting, `None` restores default ranking.
+    pub fn set_ranker(&mut self, ranker: Option<Arc<dyn Ranker>>) {
+        self.ranker = ranker;
+    }
+
     #[cfg(feature = "geoip2_support")]
     pub fn geoip2_lookup(&self, addr: IpAddr) -> Option<&CitiesRecord> {
         match self.geoip2_reader.as_ref() {
             Some((_, reader)) => {
-                let result = reader.lookup(addr).ok()?;
-                let city = result.city?;
-                let id = city.geoname_id?;
-                self.geonames.get(&id)
+                let lookup = || -> Option<u32> { reader.lookup(addr).ok()?.city?.geoname_id };
+
+                let Some(cache) = self.geoip2_cache.as_ref() else {
+                    return self.geonames.get(&lookup()?);
+                };
+
+                // the lock is held only to touch the cache, not for the lookup itself
+                let cached = cache.lock().ok().and_then(|mut c| c.get(&addr).copied());
+                let id = match cached {
+                    Some(id) => {
+                        self.geoip2_cache_hits.fetch_add(1, Ordering::Relaxed);
+                        id
+                    }
+                    None => {
+                        let id = lookup();
+                        if let Ok(mut cache) = cache.lock() {
+                            cache.put(addr, id);
+                        }
+                        id
+                    }
+                };
+
+                self.geonames.get(&id?)
             }
             None => {
                 #[cfg(feature = "tracing")]
@@ -1115,6 +5118,310 @@ fn split_content_to_n_parts(content: &str, n: usize) -> Vec<String> {
     lines.chunks(n).map(|chunk| chunk.join("\n")).collect()
 }
 
+/// Lookup operations of the engine.
+///
+/// Implemented by [`Engine`], consumers generic over the trait could be tested
+/// against mocks or served by alternative backends.
+pub trait GeoEngine {
+    /// Get city by geonameid.
+    fn get(&self, id: &u32) -> Option<&CitiesRecord>;
+
+    /// Get capital city by iso 2-letter country code.
+    fn capital(&self, country_code: &str) -> Option<&CitiesRecord>;
+
+    /// Suggest cities with scores and matched names, see [`Engine::suggest_with_scores`].
+    fn suggest_with_scores<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>>;
+
+    /// Suggest cities by pattern, see [`Engine::suggest_with`].
+    fn suggest_with<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<&CitiesRecord> {
+        self.suggest_with_scores(pattern, options)
+            .into_iter()
+            .map(|item| item.city)
+            .collect()
+    }
+
+    /// Suggest cities by pattern, see [`Engine::suggest`].
+    fn suggest<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        limit: usize,
+        min_score: Option<f32>,
+        countries: Option<&[T]>,
+    ) -> Vec<&CitiesRecord> {
+        self.suggest_with(
+            pattern,
+            SuggestOptions {
+                min_score,
+                ..SuggestOptions::new(limit)
+            }
+            .with_countries(countries),
+        )
+    }
+
+    /// Top scored candidates ignoring min score, see [`Engine::suggest_candidates`].
+    fn suggest_candidates<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>>;
+
+    /// Cities, admin divisions and countries ranked together, see [`Engine::suggest_places`].
+    fn suggest_places<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+        weights: PlaceWeights,
+    ) -> Vec<PlaceItem<'_>>;
+
+    /// Find nearest cities by coordinates, see [`Engine::reverse_with`].
+    fn reverse_with<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        options: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>>;
+
+    /// Find nearest cities by coordinates, see [`Engine::reverse`].
+    fn reverse<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        limit: usize,
+        k: Option<f32>,
+        countries: Option<&[T]>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        self.reverse_with(
+            loc,
+            ReverseOptions {
+                k,
+                ..ReverseOptions::new(limit)
+            }
+            .with_countries(countries),
+        )
+    }
+
+    /// Get country info by iso 2-letter country code.
+    fn country_info(&self, country_code: &str) -> Option<&CountryRecord>;
+
+    /// Get country info with capital city record by iso 2-letter country code.
+    fn country_info_with_capital(&self, country_code: &str) -> Option<CountryInfo<'_>> {
+        let country = self.country_info(country_code)?;
+        Some(CountryInfo {
+            capital: self.capital(&country.info.iso),
+            country,
+        })
+    }
+
+    /// Pick random city, see [`Engine::random_city`].
+    fn random_city<R: Rng + ?Sized, T: AsRef<str>>(
+        &self,
+        rng: &mut R,
+        weight_by_population: bool,
+        countries: Option<&[T]>,
+    ) -> Option<&CitiesRecord>;
+
+    /// Get admin division (first level) by code, e.g. `US.CA`.
+    fn admin1(&self, code: &str) -> Option<AdminDivisionInfo<'_>>;
+
+    /// Get admin division (second level) by code, e.g. `US.CA.037`.
+    fn admin2(&self, code: &str) -> Option<AdminDivisionInfo<'_>>;
+
+    /// Aggregates of country indexed cities, see [`Engine::country_stats`].
+    fn country_stats(&self, country_code: &str, largest: usize) -> Option<CountryStats<'_>>;
+
+    /// Count of cities with translated name by language, see [`Engine::language_coverage`].
+    fn language_coverage(&self) -> Cow<'_, HashMap<String, usize>>;
+
+    /// City population percentiles, see [`Engine::population_rank`].
+    fn population_rank(&self, city: &CitiesRecord) -> Option<PopulationRank>;
+
+    /// City name translated to language, see [`Engine::city_name`].
+    fn city_name<'a>(&'a self, city: &'a CitiesRecord, lang: &str) -> Option<Cow<'a, str>>;
+
+    /// Translations of city country name.
+    fn country_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        self.country_info(&city.country.as_ref()?.code)?
+            .names
+            .as_ref()
+    }
+
+    /// Matched part of value, see [`match_span`].
+    fn match_span(&self, value: &str, pattern: &str) -> Option<MatchSpan> {
+        match_span(value, pattern)
+    }
+
+    /// Translations of city admin division (first level) name.
+    fn admin1_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>>;
+
+    /// Translations of city admin division (second level) name.
+    fn admin2_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>>;
+
+    /// Suggest ranking profile by name, see [`Engine::ranking_profile`].
+    fn ranking_profile(&self, name: &str) -> Option<RankingProfile>;
+
+    /// Timezone of the nearest city, see [`Engine::timezone_at`].
+    fn timezone_at(&self, lat: f32, lng: f32) -> Option<&str> {
+        self.reverse_with::<&str>((lat, lng), ReverseOptions::new(1))?
+            .first()
+            .map(|item| item.city.timezone.as_str())
+            .filter(|timezone| !timezone.is_empty())
+    }
+
+    /// Index metadata, see [`Engine::metadata`].
+    fn metadata(&self) -> Option<&EngineMetadata>;
+
+    /// Approximate heap footprint, see [`Engine::memory_usage`].
+    fn memory_usage(&self) -> MemoryUsage;
+
+    /// City by IP address, see [`Engine::geoip2_lookup`].
+    #[cfg(feature = "geoip2_support")]
+    fn geoip2_lookup(&self, addr: IpAddr) -> Option<&CitiesRecord>;
+}
+
+impl GeoEngine for Engine {
+    fn get(&self, id: &u32) -> Option<&CitiesRecord> {
+        Engine::get(self, id)
+    }
+
+    fn capital(&self, country_code: &str) -> Option<&CitiesRecord> {
+        Engine::capital(self, country_code)
+    }
+
+    fn suggest_with<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<&CitiesRecord> {
+        Engine::suggest_with(self, pattern, options)
+    }
+
+    fn suggest_with_scores<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        Engine::suggest_with_scores(self, pattern, options)
+    }
+
+    fn suggest_candidates<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        Engine::suggest_candidates(self, pattern, options)
+    }
+
+    fn suggest_places<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+        weights: PlaceWeights,
+    ) -> Vec<PlaceItem<'_>> {
+        Engine::suggest_places(self, pattern, options, weights)
+    }
+
+    fn reverse_with<T: AsRef<str>>(
+        &self,
+        loc: (f32, f32),
+        options: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        Engine::reverse_with(self, loc, options)
+    }
+
+    fn country_info(&self, country_code: &str) -> Option<&CountryRecord> {
+        Engine::country_info(self, country_code)
+    }
+
+    fn random_city<R: Rng + ?Sized, T: AsRef<str>>(
+        &self,
+        rng: &mut R,
+        weight_by_population: bool,
+        countries: Option<&[T]>,
+    ) -> Option<&CitiesRecord> {
+        Engine::random_city(self, rng, weight_by_population, countries)
+    }
+
+    fn admin1(&self, code: &str) -> Option<AdminDivisionInfo<'_>> {
+        Engine::admin1(self, code)
+    }
+
+    fn admin2(&self, code: &str) -> Option<AdminDivisionInfo<'_>> {
+        Engine::admin2(self, code)
+    }
+
+    fn city_name<'a>(&'a self, city: &'a CitiesRecord, lang: &str) -> Option<Cow<'a, str>> {
+        Engine::city_name(self, city, lang)
+    }
+
+    fn country_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        Engine::country_names(self, city)
+    }
+
+    fn match_span(&self, value: &str, pattern: &str) -> Option<MatchSpan> {
+        Engine::match_span(self, value, pattern)
+    }
+
+    fn language_coverage(&self) -> Cow<'_, HashMap<String, usize>> {
+        Cow::Borrowed(Engine::language_coverage(self))
+    }
+
+    fn population_rank(&self, city: &CitiesRecord) -> Option<PopulationRank> {
+        Engine::population_rank(self, city)
+    }
+
+    fn country_stats(&self, country_code: &str, largest: usize) -> Option<CountryStats<'_>> {
+        Engine::country_stats(self, country_code, largest)
+    }
+
+    fn admin1_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        Engine::admin1_names(self, city)
+    }
+
+    fn admin2_names(&self, city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        Engine::admin2_names(self, city)
+    }
+
+    fn ranking_profile(&self, name: &str) -> Option<RankingProfile> {
+        Engine::ranking_profile(self, name)
+    }
+
+    fn timezone_at(&self, lat: f32, lng: f32) -> Option<&str> {
+        Engine::timezone_at(self, lat, lng)
+    }
+
+    fn metadata(&self) -> Option<&EngineMetadata> {
+        self.metadata.as_ref()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        Engine::memory_usage(self)
+    }
+
+    #[cfg(feature = "geoip2_support")]
+    fn geoip2_lookup(&self, addr: IpAddr) -> Option<&CitiesRecord> {
+        Engine::geoip2_lookup(self, addr)
+    }
+}
+
+/// Content of geoip2 (mmdb) database checked to be readable, see [`Engine::set_geoip2`]
+#[cfg(feature = "geoip2_support")]
+pub struct GeoIP2Data(Vec<u8>);
+
+#[cfg(feature = "geoip2_support")]
+impl GeoIP2Data {
+    pub fn read<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let buffer = std::fs::read(path)?;
+        Reader::<City>::from_bytes(&buffer).map_err(GeoIP2Error)?;
+        Ok(GeoIP2Data(buffer))
+    }
+}
+
 #[cfg(feature = "geoip2_support")]
 struct GeoIP2Error(geoip2::Error);
 
@@ -1137,39 +5444,51 @@ impl std::fmt::Display for GeoIP2Error {
 
 impl From<EngineDump> for Engine {
     fn from(engine_dump: EngineDump) -> Engine {
-        let mut items = engine_dump
+        let items = engine_dump
             .geonames
-            .values()
-            .map(|record| (record.id, [record.latitude, record.longitude]))
+            .par_iter()
+            .map(|(_, record)| (record.id, [record.latitude, record.longitude]))
             .collect::<Vec<_>>();
 
-        items.sort_unstable_by_key(|item| item.0);
-        items.dedup_by_key(|item| item.0);
-
-        let tree_index_to_geonameid = HashMap::from_iter(
-            items
-                .iter()
-                .enumerate()
-                .map(|(index, item)| (index, item.0)),
-        );
-        let tree = ImmutableKdTree::new_from_slice(
-            items
-                .into_iter()
-                .map(|item| item.1)
-                .collect::<Vec<_>>()
-                .as_slice(),
-        );
+        let (tree_index_to_geonameid, tree) = build_tree(items);
+        let single_country = single_country(engine_dump.geonames.values());
 
-        Engine {
+        let mut engine = Engine {
             entries: engine_dump.entries,
+            former_entries: engine_dump.former_entries,
+            translit_entries: engine_dump.translit_entries,
+            display_names: engine_dump.display_names,
+            languages: engine_dump.languages,
             geonames: engine_dump.geonames,
             capitals: engine_dump.capitals,
             country_info_by_code: engine_dump.country_info_by_code,
+            admin_names: engine_dump.admin_names,
+            admin1_divisions: engine_dump.admin1_divisions,
+            admin2_divisions: engine_dump.admin2_divisions,
+            compressed_names: engine_dump.compressed_names,
+            population_ranks: engine_dump.population_ranks,
+            density_scales: engine_dump.density_scales,
+            case_folding: engine_dump.case_folding,
             tree_index_to_geonameid,
             tree,
+            place_names: HashMap::new(),
+            admin1_abbreviations: HashMap::new(),
+            memory_usage: MemoryUsage::default(),
+            language_coverage: HashMap::new(),
+            country_trees: OnceLock::new(),
+            single_country,
+            query_hook: None,
+            ranker: None,
             metadata: engine_dump.metadata,
             #[cfg(feature = "geoip2_support")]
             geoip2_reader: None,
-        }
+            #[cfg(feature = "geoip2_support")]
+            geoip2_cache: None,
+            #[cfg(feature = "geoip2_support")]
+            geoip2_cache_hits: AtomicU64::new(0),
+        };
+        engine.index_place_names();
+        engine.refresh_stats();
+        engine
     }
 }
@@ -0,0 +1,49 @@
+//! Approximate heap footprint of engine, see [`crate::Engine::memory_usage`].
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use serde::Serialize;
+
+#[cfg(feature = "oaph_support")]
+use oaph::schemars::{self, JsonSchema};
+
+/// Approximate heap bytes of engine parts (allocator overhead is not counted)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct MemoryUsage {
+    /// City records with translations (compressed ones too)
+    pub cities: usize,
+    /// Search entries, former names entries too
+    pub entries: usize,
+    /// Reverse kd-tree (estimated by its items), tree item to geonameid map and per-country
+    /// kd-trees once built
+    pub tree: usize,
+    /// Countries, admin divisions, translations of divisions, capitals, population ranks,
+    /// density scales and normalized names of places
+    pub auxiliary: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.cities + self.entries + self.tree + self.auxiliary
+    }
+}
+
+/// Hash table slots with control bytes
+pub(crate) fn map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
+
+pub(crate) fn vec_bytes<T>(items: &Vec<T>) -> usize {
+    items.capacity() * size_of::<T>()
+}
+
+pub(crate) fn strings_bytes<'a>(values: impl IntoIterator<Item = &'a String>) -> usize {
+    values.into_iter().map(String::capacity).sum()
+}
+
+/// Translations by isolanguage code
+pub(crate) fn names_bytes(names: &HashMap<String, String>) -> usize {
+    map_bytes(names) + strings_bytes(names.keys()) + strings_bytes(names.values())
+}
@@ -0,0 +1,442 @@
+//! Text normalization shared by index build and query time.
+
+#[cfg(feature = "translit")]
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::decompose_compatible;
+
+#[cfg(feature = "oaph_support")]
+use oaph::schemars::{self, JsonSchema};
+
+/// Case folding rules, configured per index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseFolding {
+    /// Locale independent, `İ` is folded to `i`
+    #[default]
+    Unicode,
+    /// Turkish and Azerbaijani dotted/dotless i: `I` -> `ı`, `İ` -> `i`
+    Turkic,
+}
+
+impl std::str::FromStr for CaseFolding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "unicode" => Ok(CaseFolding::Unicode),
+            "turkic" | "tr" | "az" => Ok(CaseFolding::Turkic),
+            _ => Err(format!("Unknown case folding: {value}")),
+        }
+    }
+}
+
+/// Version of [`normalize`] rules, index entries normalized by other rules don't match queries
+pub const NORMALIZATION_VERSION: u32 = 1;
+
+/// Enabled build features changing [`normalize`] rules or search keys of index entries,
+/// index built with other features doesn't match queries (e.g. kana folding of `cjk`)
+pub const NORMALIZATION_FEATURES: &[&str] = &[
+    #[cfg(feature = "cjk")]
+    "cjk",
+    #[cfg(feature = "romanize")]
+    "romanize",
+    #[cfg(feature = "translit")]
+    "translit",
+];
+
+/// Normalize searchable value (index entry or query pattern).
+///
+/// Values are NFKD decomposed with diacritics stripped, so `Córdoba` and `Zürich` are matched
+/// by `cordoba` and `zurich`. Cyrillic letters are kept as is (`й` and `ё` are separate letters).
+///
+/// With `cjk` feature katakana is folded into hiragana, so both scripts match each other.
+pub(crate) fn normalize(value: &str, case_folding: CaseFolding) -> String {
+    let mut normalized = String::with_capacity(value.len());
+    for c in value.chars() {
+        normalize_char(c, case_folding, &mut normalized);
+    }
+    normalized
+}
+
+#[cfg(feature = "cjk")]
+fn fold_kana_char(c: char) -> char {
+    match c {
+        // katakana ァ..ヶ are laid out exactly as hiragana ぁ..ゖ, shifted by 0x60
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn normalize_char(c: char, case_folding: CaseFolding, out: &mut String) {
+    match (case_folding, c) {
+        // plain lowercase gives `i` with combining dot above
+        (_, 'İ') => out.push('i'),
+        (CaseFolding::Turkic, 'I') => out.push('ı'),
+        _ => {
+            for c in c.to_lowercase() {
+                if c.is_ascii() || is_cyrillic(c) || is_hangul(c) {
+                    push_folded(c, out);
+                    continue;
+                }
+                decompose_compatible(c, |c| {
+                    if !is_diacritic(c) {
+                        c.to_lowercase().for_each(|c| push_folded(c, out));
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn push_folded(c: char, out: &mut String) {
+    // final sigma
+    let c = if c == 'ς' { 'σ' } else { c };
+
+    #[cfg(feature = "cjk")]
+    let c = fold_kana_char(c);
+
+    out.push(c);
+}
+
+/// Combining diacritical marks, e.g. acute accent of decomposed `ó`
+fn is_diacritic(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}')
+}
+
+/// Hangul syllables are decomposed into jamo by NFKD
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{AC00}'..='\u{D7A3}')
+}
+
+/// Abbreviations of admin divisions (first level) and their codes matched by
+/// [`crate::Engine::find_admin1`], e.g. `Calif.` for `US.CA`.
+pub const ADMIN1_ABBREVIATIONS: &[(&str, &str)] = &[
+    // US states (AP style, postal codes are codes of divisions)
+    ("Ala.", "US.AL"),
+    ("Ariz.", "US.AZ"),
+    ("Ark.", "US.AR"),
+    ("Calif.", "US.CA"),
+    ("Cal.", "US.CA"),
+    ("Colo.", "US.CO"),
+    ("Conn.", "US.CT"),
+    ("Del.", "US.DE"),
+    ("D.C.", "US.DC"),
+    ("Fla.", "US.FL"),
+    ("Ga.", "US.GA"),
+    ("Ill.", "US.IL"),
+    ("Ind.", "US.IN"),
+    ("Kan.", "US.KS"),
+    ("Ky.", "US.KY"),
+    ("La.", "US.LA"),
+    ("Md.", "US.MD"),
+    ("Mass.", "US.MA"),
+    ("Mich.", "US.MI"),
+    ("Minn.", "US.MN"),
+    ("Miss.", "US.MS"),
+    ("Mo.", "US.MO"),
+    ("Mont.", "US.MT"),
+    ("Neb.", "US.NE"),
+    ("Nev.", "US.NV"),
+    ("N.H.", "US.NH"),
+    ("N.J.", "US.NJ"),
+    ("N.M.", "US.NM"),
+    ("N.Y.", "US.NY"),
+    ("N.C.", "US.NC"),
+    ("N.D.", "US.ND"),
+    ("Okla.", "US.OK"),
+    ("Ore.", "US.OR"),
+    ("Pa.", "US.PA"),
+    ("Penn.", "US.PA"),
+    ("R.I.", "US.RI"),
+    ("S.C.", "US.SC"),
+    ("S.D.", "US.SD"),
+    ("Tenn.", "US.TN"),
+    ("Tex.", "US.TX"),
+    ("Vt.", "US.VT"),
+    ("Va.", "US.VA"),
+    ("Wash.", "US.WA"),
+    ("W.Va.", "US.WV"),
+    ("Wis.", "US.WI"),
+    ("Wyo.", "US.WY"),
+    // Canadian provinces and territories
+    ("AB", "CA.01"),
+    ("Alta.", "CA.01"),
+    ("BC", "CA.02"),
+    ("B.C.", "CA.02"),
+    ("MB", "CA.03"),
+    ("Man.", "CA.03"),
+    ("NB", "CA.04"),
+    ("N.B.", "CA.04"),
+    ("NL", "CA.05"),
+    ("Nfld.", "CA.05"),
+    ("NS", "CA.07"),
+    ("N.S.", "CA.07"),
+    ("ON", "CA.08"),
+    ("Ont.", "CA.08"),
+    ("PE", "CA.09"),
+    ("P.E.I.", "CA.09"),
+    ("QC", "CA.10"),
+    ("Que.", "CA.10"),
+    ("SK", "CA.11"),
+    ("Sask.", "CA.11"),
+    ("YT", "CA.12"),
+    ("NT", "CA.13"),
+    ("NU", "CA.14"),
+    // Australian states and territories
+    ("ACT", "AU.01"),
+    ("NSW", "AU.02"),
+    ("NT", "AU.03"),
+    ("QLD", "AU.04"),
+    ("Qld.", "AU.04"),
+    ("SA", "AU.05"),
+    ("TAS", "AU.06"),
+    ("Tas.", "AU.06"),
+    ("VIC", "AU.07"),
+    ("Vic.", "AU.07"),
+    ("WA", "AU.08"),
+];
+
+/// Common English abbreviations of place names expanded in suggest pattern,
+/// see [`crate::SuggestOptions::abbreviations`].
+pub const DEFAULT_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("st", "saint"),
+    ("ste", "sainte"),
+    ("mt", "mount"),
+    ("mtn", "mountain"),
+    ("ft", "fort"),
+    ("pt", "point"),
+    ("hts", "heights"),
+    ("spgs", "springs"),
+    ("jct", "junction"),
+    ("lk", "lake"),
+];
+
+/// Suggest pattern as matched against index entries, see [`crate::Engine::normalize_query`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NormalizedQuery {
+    /// Normalized pattern, e.g. `st. petersburg` for `St. Petersburg`
+    pub pattern: String,
+    /// Pattern with expanded abbreviations, e.g. `saint petersburg`, it's matched too and the
+    /// best score wins
+    pub expanded: Option<String>,
+}
+
+/// Expand abbreviated words of normalized pattern, e.g. `st. petersburg` -> `saint petersburg`.
+///
+/// Abbreviation is a whole word optionally followed by dot. Returns `None` if pattern doesn't
+/// have any abbreviations.
+pub(crate) fn expand_abbreviations(
+    pattern: &str,
+    abbreviations: &[(&str, &str)],
+    case_folding: CaseFolding,
+) -> Option<String> {
+    let find = |word: &str| {
+        abbreviations
+            .iter()
+            .find(|(short, _)| normalize(short, case_folding) == word)
+            .map(|(_, full)| normalize(full, case_folding))
+    };
+
+    let mut expanded = false;
+    let words = pattern
+        .split_whitespace()
+        .map(|word| {
+            // `st.petersburg` is `st. petersburg`
+            let (head, tail) = match word.split_once('.') {
+                Some((head, tail)) => (head, Some(tail)),
+                None => (word, None),
+            };
+            match find(head) {
+                Some(full) => {
+                    expanded = true;
+                    match tail.filter(|tail| !tail.is_empty()) {
+                        Some(tail) => format!("{full} {tail}"),
+                        None => full,
+                    }
+                }
+                None => word.to_owned(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    expanded.then(|| words.join(" "))
+}
+
+/// Range of matched pattern within value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "oaph_support", derive(JsonSchema))]
+pub struct MatchSpan {
+    /// start char index (inclusive)
+    pub start: usize,
+    /// end char index (exclusive)
+    pub end: usize,
+    /// start byte offset (inclusive)
+    pub byte_start: usize,
+    /// end byte offset (exclusive)
+    pub byte_end: usize,
+}
+
+/// Find first occurrence of pattern in value by the same normalization rules as suggest.
+///
+/// Useful to highlight matched part of suggested name, e.g. `vor` in `Voronezh`.
+pub fn match_span(value: &str, pattern: &str) -> Option<MatchSpan> {
+    match_span_with(value, pattern, CaseFolding::default())
+}
+
+/// [`match_span`] with explicit case folding rules.
+pub fn match_span_with(value: &str, pattern: &str, case_folding: CaseFolding) -> Option<MatchSpan> {
+    let needle = normalize(pattern.trim(), case_folding);
+    if needle.is_empty() {
+        return None;
+    }
+
+    // normalized offset, char index and byte offset of each value char
+    let mut haystack = String::with_capacity(value.len());
+    let mut positions = Vec::with_capacity(value.len());
+    for (index, (offset, c)) in value.char_indices().enumerate() {
+        positions.push((haystack.len(), index, offset));
+        normalize_char(c, case_folding, &mut haystack);
+    }
+
+    let found = haystack.find(&needle)?;
+    let found_end = found + needle.len();
+
+    let (_, start, byte_start) = positions
+        .iter()
+        .rev()
+        .find(|(normalized, _, _)| *normalized <= found)
+        .copied()?;
+    let (end, byte_end) = positions
+        .iter()
+        .find(|(normalized, _, _)| *normalized >= found_end)
+        .map(|(_, index, offset)| (*index, *offset))
+        .unwrap_or((positions.len(), value.len()));
+
+    Some(MatchSpan {
+        start,
+        end,
+        byte_start,
+        byte_end,
+    })
+}
+
+/// Tone-less pinyin key for values containing han ideographs, e.g. `北京` -> `beijing`.
+#[cfg(feature = "cjk")]
+pub(crate) fn pinyin_key(value: &str) -> Option<String> {
+    if !value.chars().any(is_han) {
+        return None;
+    }
+
+    let key = deunicode::deunicode(value)
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<String>();
+
+    if key.is_empty() {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+#[cfg(feature = "cjk")]
+fn is_han(c: char) -> bool {
+    matches!(
+        c,
+        '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}'
+    )
+}
+
+/// Latin transliteration of normalized value of any script, e.g. `москва` -> `moskva` and
+/// `αθηνα` -> `athena`, ASCII values are kept as is.
+#[cfg(feature = "translit")]
+pub(crate) fn transliterate(value: &str) -> Cow<'_, str> {
+    if value.is_ascii() {
+        Cow::Borrowed(value)
+    } else {
+        Cow::Owned(deunicode::deunicode(value).to_lowercase())
+    }
+}
+
+/// BGN/PCGN romanization key (ASCII, without apostrophes) for values containing cyrillic,
+/// e.g. `Воронеж` -> `voronezh`.
+#[cfg(feature = "romanize")]
+pub(crate) fn romanize_key(value: &str) -> Option<String> {
+    if !value.chars().any(is_cyrillic) {
+        return None;
+    }
+
+    let mut key = String::with_capacity(value.len() * 2);
+    let mut prev: Option<char> = None;
+    for c in value.to_lowercase().chars() {
+        // `ye` at the word start and after vowels, й, ъ and ь
+        let is_ye =
+            c == 'е' && prev.is_none_or(|p| !p.is_alphabetic() || "аеёиоуыэюяйъьієї".contains(p));
+
+        match romanize_char(c) {
+            _ if is_ye => key.push_str("ye"),
+            Some(value) => key.push_str(value),
+            None => key.push(c),
+        }
+        prev = Some(c);
+    }
+
+    Some(key)
+}
+
+#[cfg(feature = "romanize")]
+fn romanize_char(c: char) -> Option<&'static str> {
+    let value = match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        // ukrainian
+        'є' => "ye",
+        'ї' => "yi",
+        'ґ' => "g",
+        // serbian and macedonian
+        'ђ' => "dj",
+        'ј' => "j",
+        'љ' => "lj",
+        'њ' => "nj",
+        'ћ' => "c",
+        'џ' => "dz",
+        'ѓ' => "gj",
+        'ќ' => "kj",
+        'ѕ' => "dz",
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}')
+}
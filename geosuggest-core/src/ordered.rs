@@ -0,0 +1,55 @@
+//! Serialization of hash maps in key order, so dumps of the same index are byte-identical.
+
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+
+struct Sorted<'a, K, V>(&'a HashMap<K, V>);
+
+impl<K: Ord + Serialize, V: Serialize> Serialize for Sorted<'_, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        map(self.0, serializer)
+    }
+}
+
+fn sorted<K: Ord, V>(map: &HashMap<K, V>) -> Vec<(&K, &V)> {
+    let mut items = map.iter().collect::<Vec<_>>();
+    items.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    items
+}
+
+pub(crate) fn map<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    serializer.collect_map(sorted(map))
+}
+
+pub(crate) fn option_map<K, V, S>(
+    map: &Option<HashMap<K, V>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    match map {
+        Some(map) => serializer.serialize_some(&Sorted(map)),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn nested_map<K, K2, V, S>(
+    map: &HashMap<K, HashMap<K2, V>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    K2: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    serializer.collect_map(sorted(map).into_iter().map(|(k, v)| (k, Sorted(v))))
+}
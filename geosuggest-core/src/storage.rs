@@ -1,10 +1,52 @@
-use crate::{Engine, EngineMetadata};
+use crate::{
+    Engine, EngineMetadata, INDEX_FORMAT_VERSION, NORMALIZATION_FEATURES, NORMALIZATION_VERSION,
+};
 use std::fs::OpenOptions;
 use std::path::Path;
-
-#[cfg(feature = "tracing")]
 use std::time::Instant;
 
+/// Checks of loaded index
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Validation {
+    /// Trust index content (fast path)
+    #[default]
+    Trusted,
+    /// Check index consistency, see [`Engine::validate`]
+    Full,
+}
+
+/// Refuse index with search entries normalized by other rules or features, queries wouldn't
+/// match them
+fn check_normalization(engine: &Engine) -> Result<(), Box<dyn std::error::Error>> {
+    match engine.metadata.as_ref() {
+        Some(metadata) if metadata.normalization_version != NORMALIZATION_VERSION => Err(format!(
+            "Index entries are normalized by rules version {}, supported {} (rebuild index)",
+            metadata.normalization_version, NORMALIZATION_VERSION
+        )
+        .into()),
+        Some(metadata) if metadata.normalization_features != NORMALIZATION_FEATURES => {
+            Err(format!(
+                "Index entries are built with features {:?}, enabled {:?} (rebuild index)",
+                metadata.normalization_features, NORMALIZATION_FEATURES
+            )
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Refuse index with other layout of stored structs, they would fail to load or load garbage
+fn check_format_version(version: u32) -> Result<(), Box<dyn std::error::Error>> {
+    if version != INDEX_FORMAT_VERSION {
+        return Err(format!(
+            "Index format version {}, supported {} (rebuild index)",
+            version, INDEX_FORMAT_VERSION
+        )
+        .into());
+    }
+    Ok(())
+}
+
 pub trait IndexStorage {
     /// Serialize engine
     fn dump<W>(&self, engine: &Engine, buff: &mut W) -> Result<(), Box<dyn std::error::Error>>
@@ -43,14 +85,13 @@ pub trait IndexStorage {
 
         Ok(())
     }
-    /// Load whole engine from file
+    /// Load whole engine from file, load duration is recorded to [`EngineMetadata::load_duration`]
     fn load_from<P: AsRef<std::path::Path>>(
         &self,
         path: P,
     ) -> Result<Engine, Box<dyn std::error::Error>> {
         #[cfg(feature = "tracing")]
         tracing::info!("Loading index...");
-        #[cfg(feature = "tracing")]
         let now = Instant::now();
 
         let mut file = OpenOptions::new()
@@ -59,7 +100,10 @@ pub trait IndexStorage {
             .truncate(false)
             .open(&path)?;
 
-        let index = self.load(&mut file)?;
+        let mut index = self.load(&mut file)?;
+        if let Some(metadata) = index.metadata.as_mut() {
+            metadata.load_duration = Some(now.elapsed());
+        }
 
         #[cfg(feature = "tracing")]
         tracing::info!(
@@ -72,18 +116,28 @@ pub trait IndexStorage {
 }
 
 pub mod json {
-    use super::IndexStorage;
+    use super::{check_normalization, IndexStorage, Validation};
     use crate::{Engine, EngineDump, EngineMetadata};
     use std::fs::OpenOptions;
     use std::io::BufRead;
     use std::path::Path;
 
     /// JSON storage in 2-lines format `<metadata>\n<payload>`
-    pub struct Storage;
+    pub struct Storage {
+        validation: Validation,
+    }
 
     impl Storage {
         pub fn new() -> Self {
-            Self {}
+            Self {
+                validation: Validation::default(),
+            }
+        }
+
+        /// Checks of loaded index, trusted by default
+        pub fn with_validation(mut self, validation: Validation) -> Self {
+            self.validation = validation;
+            self
         }
     }
 
@@ -113,7 +167,12 @@ pub mod json {
                 return Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into());
             };
 
-            Ok(serde_json::from_str::<EngineDump>(&raw_payload?)?.into())
+            let engine: Engine = serde_json::from_str::<EngineDump>(&raw_payload?)?.into();
+            check_normalization(&engine)?;
+            if self.validation == Validation::Full {
+                engine.validate()?;
+            }
+            Ok(engine)
         }
         /// Read engine metadata and don't load whole engine
         fn read_metadata<P: AsRef<Path>>(
@@ -136,18 +195,50 @@ pub mod json {
 }
 
 pub mod bincode {
-    use super::IndexStorage;
-    use crate::{Engine, EngineDump, EngineMetadata};
+    use super::{check_format_version, check_normalization, IndexStorage, Validation};
+    use crate::{Engine, EngineDump, EngineMetadata, INDEX_FORMAT_VERSION};
     use std::fs::OpenOptions;
     use std::io::Read;
     use std::path::Path;
 
-    /// Bincode storage in len-prefix format `<4-bytes metadata length><metadata><payload>`
-    pub struct Storage;
+    const MAGIC: &[u8; 4] = b"GSIX";
+
+    /// Check header and return metadata length.
+    /// Index without header (geosuggest 0.6.5 and earlier) starts with metadata length
+    /// and is reported as format version 0.
+    fn read_header<R: Read>(buff: &mut R) -> Result<u32, Box<dyn std::error::Error>> {
+        let mut chunk = [0; 4];
+        buff.read_exact(&mut chunk)?;
+        let version = if &chunk == MAGIC {
+            buff.read_exact(&mut chunk)?;
+            u32::from_be_bytes(chunk)
+        } else {
+            0
+        };
+        check_format_version(version)?;
+
+        buff.read_exact(&mut chunk)?;
+        Ok(u32::from_be_bytes(chunk))
+    }
+
+    /// Bincode storage in len-prefix format
+    /// `<4-bytes magic><4-bytes format version><4-bytes metadata length><metadata><payload>`,
+    /// fields are read by position so index of other [`INDEX_FORMAT_VERSION`] is refused
+    pub struct Storage {
+        validation: Validation,
+    }
 
     impl Storage {
         pub fn new() -> Self {
-            Self {}
+            Self {
+                validation: Validation::default(),
+            }
+        }
+
+        /// Checks of loaded index, trusted by default
+        pub fn with_validation(mut self, validation: Validation) -> Self {
+            self.validation = validation;
+            self
         }
     }
 
@@ -164,6 +255,8 @@ pub mod bincode {
             W: std::io::Write,
         {
             let metadata = bincode::serialize(&engine.metadata)?;
+            buff.write_all(MAGIC)?;
+            buff.write_all(&INDEX_FORMAT_VERSION.to_be_bytes())?;
             buff.write_all(&(metadata.len() as u32).to_be_bytes())?;
             buff.write_all(&metadata)?;
             bincode::serialize_into(buff, &engine)?;
@@ -176,16 +269,19 @@ pub mod bincode {
             R: std::io::Read,
         {
             // skip metadata
-            let mut metadata_len = [0; 4];
-            buff.read_exact(&mut metadata_len)?;
-            let metadata_len = u32::from_be_bytes(metadata_len);
+            let metadata_len = read_header(buff)?;
             // TODO use Seek?
             // std::io::copy(buff.take(metadata_len.into()), &mut std::io::sink());
             let mut skip = vec![0; metadata_len as usize];
             buff.read_exact(&mut skip)?;
 
             // load payload
-            Ok(bincode::deserialize_from::<_, EngineDump>(buff)?.into())
+            let engine: Engine = bincode::deserialize_from::<_, EngineDump>(buff)?.into();
+            check_normalization(&engine)?;
+            if self.validation == Validation::Full {
+                engine.validate()?;
+            }
+            Ok(engine)
         }
 
         /// Read engine metadata and don't load whole engine
@@ -199,10 +295,7 @@ pub mod bincode {
                 .truncate(false)
                 .open(&path)?;
 
-            let mut metadata_len = [0; 4];
-            file.read_exact(&mut metadata_len)?;
-
-            let metadata_len = u32::from_be_bytes(metadata_len);
+            let metadata_len = read_header(&mut file)?;
             let mut raw_metadata = vec![0; metadata_len as usize];
             file.read_exact(&mut raw_metadata)?;
 
@@ -1,9 +1,22 @@
 use geosuggest_core::{
+    canonical_language, match_span, rollup_admin1,
     storage::{self, IndexStorage},
-    Engine, EngineMetadata, SourceFileOptions,
+    vote_admin2, BBox, CaseFolding, CentroidMode, CitiesRecord, DuplicatePolicy, Engine,
+    EngineMetadata, EntryFilter, MatchSpan, Overrides, Place, PlaceKind, PlaceWeights, QueryEvent,
+    RadiusFilters, RankingProfile, ReverseOptions, ReverseScoring, SimilarityMetric,
+    SourceFileContentOptions, SourceFileOptions, SuggestOptions, SuggestRanking,
+    DEFAULT_ABBREVIATIONS, INDEX_FORMAT_VERSION, NORMALIZATION_FEATURES, NORMALIZATION_VERSION,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    borrow::Borrow,
+    env::temp_dir,
+    error::Error,
+    sync::{Arc, Mutex},
 };
-use std::{env::temp_dir, error::Error};
 
+#[cfg(feature = "geoip2_support")]
+use geosuggest_core::GeoIP2Data;
 #[cfg(feature = "geoip2_support")]
 use std::{net::IpAddr, str::FromStr};
 
@@ -20,67 +33,1872 @@ fn get_engine(
         filter_languages,
         admin1_codes: Some("tests/misc/admin1-codes.txt"),
         admin2_codes: Some("tests/misc/admin2-codes.txt"),
+        ..Default::default()
+    })?;
+    engine.metadata = Some(EngineMetadata::default());
+    Ok(engine)
+}
+
+fn engine_from_cities<S: Borrow<str>>(cities: &[S]) -> Result<Engine, Box<dyn Error>> {
+    Engine::new_from_files_content(SourceFileContentOptions {
+        cities: cities.join("\n"),
+        ..Default::default()
+    })
+}
+
+#[test_log::test]
+fn suggest() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest::<&str>("voronezh", 1, None, None);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "Voronezh");
+    assert_eq!(items[0].country.as_ref().unwrap().name, "Russia");
+    assert_eq!(items[0].admin_division.as_ref().unwrap().name, "Voronezj");
+
+    let items = engine.suggest::<&str>("Beverley", 1, None, None);
+    tracing::info!("Items {items:#?}");
+    assert_eq!(items[0].name, "Beverley");
+    assert_eq!(
+        items[0].admin2_division.as_ref().unwrap().name,
+        "East Riding of Yorkshire"
+    );
+
+    let items = engine.suggest("Beverley", 1, None, Some(&["ru"]));
+    assert_eq!(items.len(), 0);
+
+    let items = engine.suggest("Beverley", 1, None, Some(&["gb"]));
+    assert_eq!(items.len(), 1);
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "cjk")]
+fn suggest_cjk() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    // hiragana pattern matches katakana alternate name モスクワ
+    let items = engine.suggest::<&str>("もすくわ", 1, None, None);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "Moscow");
+
+    // pinyin pattern matches chinese alternate name 莫斯科
+    let items = engine.suggest::<&str>("mosike", 1, None, None);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "Moscow");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_match_span() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        match_span("Voronezh", "VOR"),
+        Some(MatchSpan {
+            start: 0,
+            end: 3,
+            byte_start: 0,
+            byte_end: 3
+        })
+    );
+    assert_eq!(
+        match_span("Нижний Новгород", "новг"),
+        Some(MatchSpan {
+            start: 7,
+            end: 11,
+            byte_start: 13,
+            byte_end: 21
+        })
+    );
+    assert!(match_span("Voronezh", "moscow").is_none());
+    assert!(match_span("Voronezh", " ").is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_case_folding() -> Result<(), Box<dyn Error>> {
+    let build = |case_folding| {
+        Engine::new_from_files_content(SourceFileContentOptions {
+            cities: "298117\tIğdır\tIgdir\tIğdır\t39.92371\t44.045\tP\tPPLA\tTR\t\t76\t\t\t\t77000\t\t858\tEurope/Istanbul\t2019-09-04".to_owned(),
+            case_folding,
+            ..Default::default()
+        })
+    };
+
+    let engine = build(CaseFolding::Turkic)?;
+    assert_eq!(
+        engine.suggest::<&str>("ığd", 1, Some(0.99), None)[0].id,
+        298117
+    );
+    assert_eq!(
+        engine.suggest::<&str>("IĞD", 1, Some(0.99), None)[0].id,
+        298117
+    );
+    assert_eq!(
+        engine.match_span("Iğdır", "ığ").map(|s| (s.start, s.end)),
+        Some((0, 2))
+    );
+
+    let engine = build(CaseFolding::Unicode)?;
+    assert!(engine
+        .suggest::<&str>("ığd", 1, Some(0.99), None)
+        .is_empty());
+    assert_eq!(
+        engine.suggest::<&str>("iğd", 1, Some(0.99), None)[0].id,
+        298117
+    );
+
+    // dotted capital I is folded to plain i
+    assert_eq!(
+        match_span("İstanbul", "ist").map(|s| (s.start, s.end, s.byte_end)),
+        Some((0, 3, 4))
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_diacritics() -> Result<(), Box<dyn Error>> {
+    // no ascii alternate names
+    let engine = engine_from_cities(&[
+            "3860259\tCórdoba\tCórdoba\t\t-31.4135\t-64.18105\tP\tPPLA\tAR\t\t05\t\t\t\t1428214\t\t398\tAmerica/Argentina/Cordoba\t2019-09-04",
+            "2657896\tZürich\tZürich\t\t47.36667\t8.55\tP\tPPLA\tCH\t\tZH\t\t\t\t341730\t\t429\tEurope/Zurich\t2019-09-04",
+        ])?;
+
+    assert_eq!(
+        engine.suggest::<&str>("cordoba", 1, Some(0.99), None)[0].id,
+        3860259
+    );
+    assert_eq!(
+        engine.suggest::<&str>("ZURICH", 1, Some(0.99), None)[0].id,
+        2657896
+    );
+    // precomposed and decomposed forms are the same
+    assert_eq!(
+        engine.suggest::<&str>("Zu\u{308}r", 1, Some(0.99), None)[0].id,
+        2657896
+    );
+    assert_eq!(
+        engine
+            .match_span("Córdoba", "cord")
+            .map(|s| (s.start, s.end, s.byte_end)),
+        Some((0, 4, 5))
+    );
+
+    // cyrillic letters are kept
+    assert!(match_span("Йошкар-Ола", "иошкар").is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_prominence() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&[
+            "1\tSpringfield\tSpringfield\t\t37.21533\t-93.29824\tP\tPPL\tUS\t\tMO\t\t\t\t150000\t\t397\tAmerica/Chicago\t2019-09-04",
+            "2\tSpringfield\tSpringfield\t\t39.80172\t-89.64371\tP\tPPLA\tUS\t\tIL\t\t\t\t150000\t\t180\tAmerica/Chicago\t2019-09-04",
+            "3\tSpringfield\tSpringfield\t\t42.10148\t-72.58981\tP\tPPL\tUS\t\tMA\t\t\t\t1000\t\t21\tAmerica/New_York\t2019-09-04",
+        ])?;
+
+    // same score and population, admin seat first, then the most populated
+    let ids = engine
+        .suggest::<&str>("springfield", 3, None, None)
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![2, 1, 3]);
+
+    // custom ranking, e.g. warehouse cities first
+    let mut engine = engine;
+    engine.set_ranker(Some(Arc::new(
+        |city: &CitiesRecord, rank: f32| {
+            if city.id == 3 {
+                rank + 1.0
+            } else {
+                rank
+            }
+        },
+    )));
+    let items = engine.suggest_with_scores("springfield", SuggestOptions::new(3));
+    assert_eq!(
+        items.iter().map(|i| i.city.id).collect::<Vec<_>>(),
+        vec![3, 2, 1]
+    );
+    // similarity scores are untouched
+    assert!(items.iter().all(|i| i.score == 1.0));
+
+    engine.set_ranker(None);
+    let ids = engine
+        .suggest::<&str>("springfield", 3, None, None)
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![2, 1, 3]);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn country_code_case() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    for codes in [["GB"], ["gb"], [" Gb "]] {
+        let items = engine.suggest("Beverley", 1, None, Some(&codes));
+        assert_eq!(items.len(), 1, "{codes:?}");
+
+        let items = engine
+            .reverse((53.845, -0.4273), 1, None, Some(&codes))
+            .unwrap();
+        assert_eq!(items[0].city.name, "Beverley", "{codes:?}");
+
+        assert!(engine.country_info(codes[0]).is_some(), "{codes:?}");
+        assert_eq!(
+            engine.capital(codes[0]).map(|c| c.name.as_str()),
+            Some("London"),
+            "{codes:?}"
+        );
+    }
+
+    for codes in [["RU"], ["ru"]] {
+        assert!(engine.suggest("Beverley", 1, None, Some(&codes)).is_empty());
+        assert!(engine
+            .reverse((53.845, -0.4273), 1, None, Some(&codes))
+            .unwrap()
+            .iter()
+            .all(|item| item.city.country.as_ref().unwrap().code == "RU"));
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_adaptive_k() -> Result<(), Box<dyn Error>> {
+    let city = |id: u32, lat: f32, lng: f32, population: u32| {
+        format!("{id}\tCity{id}\tCity{id}\t\t{lat}\t{lng}\tP\tPPL\tXX\t\t01\t\t\t\t{population}\t\t0\tUTC\t2019-09-04")
+    };
+    // dense region of 8 cities and sparse one of village and town
+    let mut cities = (0..8)
+        .map(|i| city(i + 1, 50.1 + i as f32 * 0.1, 10.5, 1000))
+        .collect::<Vec<_>>();
+    cities.push(city(100, 10.6, 10.5, 100));
+    cities.push(city(200, 10.8, 10.5, 100000));
+
+    let engine = engine_from_cities(&cities)?;
+
+    let top = |loc, options: ReverseOptions| engine.reverse_with(loc, options).unwrap()[0].city.id;
+    let options = ReverseOptions::new(2).k(0.0000005);
+
+    // nearest village by global k, population matters more in sparse region
+    assert_eq!(top((10.5, 10.5), options.clone()), 100);
+    assert_eq!(top((10.5, 10.5), options.clone().adaptive_k(true)), 200);
+
+    // nearest one of equal cities
+    assert_eq!(top((50.12, 10.5), options.adaptive_k(true)), 1);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_adaptive_k_query_cell() -> Result<(), Box<dyn Error>> {
+    let city = |id: u32, lat: f32, population: u32| {
+        format!("{id}\tCity{id}\tCity{id}\t\t{lat}\t10.5\tP\tPPL\tXX\t\t01\t\t\t\t{population}\t\t0\tUTC\t2019-09-04")
+    };
+    // towns of sparse and middle cells, dense cell of villages next to the middle one
+    let mut cities = vec![city(1, 49.95, 30000), city(2, 50.5, 30000)];
+    cities.extend((0..10).map(|i| city(100 + i, 51.1 + i as f32 * 0.05, 1)));
+
+    let engine = engine_from_cities(&cities)?;
+    let options = ReverseOptions::new(1)
+        .nearest_limit(5)
+        .k(0.000001)
+        .adaptive_k(true);
+
+    // both towns are scaled by the queried cell, the nearest one wins
+    let items = engine.reverse_with((50.3, 10.5), options).unwrap();
+    assert_eq!(items[0].city.id, 2);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn exclude_countries() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_with("Beverley", SuggestOptions::new(1));
+    assert_eq!(items.len(), 1);
+    let items = engine.suggest_with(
+        "Beverley",
+        SuggestOptions::new(1).exclude_countries(&["gb"]),
+    );
+    assert!(items.is_empty());
+    let items = engine.suggest_with(
+        "Beverley",
+        SuggestOptions::new(1).exclude_countries(&["RU"]),
+    );
+    assert_eq!(items.len(), 1);
+
+    // nearest to Voronezh are russian cities, enough candidates are looked for
+    let items = engine
+        .reverse_with(
+            (51.6372, 39.1937),
+            ReverseOptions::new(3).exclude_countries(&["ru"]),
+        )
+        .unwrap();
+    assert_eq!(items.len(), 3);
+    assert!(items
+        .iter()
+        .all(|item| item.city.country.as_ref().unwrap().code != "RU"));
+
+    let items = engine
+        .reverse_with(
+            (51.6372, 39.1937),
+            ReverseOptions::new(3)
+                .countries(&["RU", "GB"])
+                .exclude_countries(&["RU"]),
+        )
+        .unwrap();
+    assert_eq!(
+        items
+            .iter()
+            .map(|item| item.city.country.as_ref().unwrap().code.as_str())
+            .collect::<Vec<_>>(),
+        vec!["GB", "GB"]
+    );
+
+    // owned codes
+    let (countries, excluded) = (
+        vec!["RU".to_owned(), "GB".to_owned()],
+        vec!["ru".to_owned()],
+    );
+    let items = engine
+        .reverse_with(
+            (51.6372, 39.1937),
+            ReverseOptions::new(2)
+                .countries(&countries)
+                .exclude_countries(&excluded),
+        )
+        .unwrap();
+    assert!(items
+        .iter()
+        .all(|item| item.city.country.as_ref().unwrap().code == "GB"));
+    let items = engine.suggest_with(
+        "Beverley",
+        SuggestOptions::new(1)
+            .countries(&countries)
+            .exclude_countries(&excluded),
+    );
+    assert_eq!(items.len(), 1);
+    let items = engine.within_radius(
+        (51.6372, 39.1937),
+        3_000_000.0,
+        None,
+        RadiusFilters::new()
+            .countries(&countries)
+            .exclude_countries(&excluded),
+    );
+    assert!(!items.is_empty());
+    assert!(items
+        .iter()
+        .all(|item| item.city.country.as_ref().unwrap().code == "GB"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn blocked_ids() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_with("Beverley", SuggestOptions::new(1).blocked_ids(&[2655785]));
+    assert!(items.is_empty());
+    let items = engine.suggest_with("Beverley", SuggestOptions::new(1).blocked_ids(&[472045]));
+    assert_eq!(items.len(), 1);
+    // not sorted ids
+    let items = engine.suggest_with(
+        "Beverley",
+        SuggestOptions::new(1).blocked_ids(&[2655785, 472045, 1]),
+    );
+    assert!(items.is_empty());
+
+    let items = engine
+        .reverse_with(
+            (51.6372, 39.1937),
+            ReverseOptions::new(3).blocked_ids(&[472045]),
+        )
+        .unwrap();
+    assert_eq!(items.len(), 3);
+    assert!(items.iter().all(|item| item.city.id != 472045));
+
+    let items = engine
+        .reverse_with(
+            (51.6372, 39.1937),
+            ReverseOptions::new(3).blocked_ids(&[524901, 472045, 1]),
+        )
+        .unwrap();
+    assert_eq!(items.len(), 3);
+    assert!(items
+        .iter()
+        .all(|item| item.city.id != 472045 && item.city.id != 524901));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_population_blend() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&[
+            "1\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t\t\t\t2138551\t\t42\tEurope/Paris\t2019-09-04",
+            "2\tParris\tParris\t\t45.1\t-70.2\tP\tPPL\tUS\t\tME\t\t\t\t500\t\t100\tAmerica/New_York\t2019-09-04",
+        ])?;
+
+    let ids = |options: SuggestOptions| {
+        engine
+            .suggest_with_scores("Parris", options)
+            .iter()
+            .map(|item| item.city.id)
+            .collect::<Vec<_>>()
+    };
+
+    // exact match first
+    assert_eq!(ids(SuggestOptions::new(2)), vec![2, 1]);
+
+    // big city outranks village
+    assert_eq!(
+        ids(SuggestOptions::new(2).ranking(SuggestRanking::POPULATION_BLEND)),
+        vec![1, 2]
+    );
+
+    // similarity only
+    assert_eq!(
+        ids(
+            SuggestOptions::new(2).ranking(SuggestRanking::PopulationBlend {
+                alpha: 1.0,
+                beta: 0.0
+            })
+        ),
+        vec![2, 1]
+    );
+
+    // scores are still similarity
+    let items = engine.suggest_with_scores(
+        "Parris",
+        SuggestOptions::new(2).ranking(SuggestRanking::POPULATION_BLEND),
+    );
+    assert_eq!(items[1].score, 1.0);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_ranking_profile() -> Result<(), Box<dyn Error>> {
+    let mut engine = engine_from_cities(&[
+            "1\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t\t\t\t2138551\t\t42\tEurope/Paris\t2019-09-04",
+            "2\tParris\tParris\t\t45.1\t-70.2\tP\tPPL\tUS\t\tME\t\t\t\t500\t\t100\tAmerica/New_York\t2019-09-04",
+        ])?;
+
+    let capitals = RankingProfile {
+        capital_boost: 1.0,
+        ..RankingProfile::default()
+    };
+    let nearby = RankingProfile {
+        bias_distance: 100.0,
+        ..RankingProfile::default()
+    };
+    let ids = |engine: &Engine, profile, bias| {
+        engine
+            .suggest_with_scores(
+                "Parris",
+                SuggestOptions::new(2).ranking(SuggestRanking::Profile { profile, bias }),
+            )
+            .iter()
+            .map(|item| item.city.id)
+            .collect::<Vec<_>>()
+    };
+
+    // similarity only by default
+    assert_eq!(ids(&engine, RankingProfile::default(), None), vec![2, 1]);
+    assert_eq!(ids(&engine, capitals, None), vec![1, 2]);
+    assert_eq!(ids(&engine, nearby, Some((48.85, 2.35))), vec![1, 2]);
+    assert_eq!(ids(&engine, nearby, Some((45.1, -70.2))), vec![2, 1]);
+    // no bias point, no proximity bonus
+    assert_eq!(ids(&engine, nearby, None), vec![2, 1]);
+
+    // embedded into index
+    assert!(engine.ranking_profile("capitals").is_none());
+    engine
+        .metadata
+        .get_or_insert_with(EngineMetadata::default)
+        .ranking_profiles
+        .insert("capitals".to_owned(), capitals);
+
+    let storage = storage::bincode::Storage::new();
+    let filepath = temp_dir().join("test-ranking-profile.bincode");
+    storage.dump_to(&filepath, &engine)?;
+    let engine = storage.load_from(&filepath)?;
+    let profile = engine.ranking_profile("capitals").unwrap();
+    assert_eq!(profile, capitals);
+    assert_eq!(ids(&engine, profile, None), vec![1, 2]);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_max_edits() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&[
+            "1\tBari\tBari\t\t41.12066\t16.86982\tP\tPPLA\tIT\t\t13\t\t\t\t316532\t\t5\tEurope/Rome\t2019-09-04",
+            "2\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t\t\t\t2138551\t\t42\tEurope/Paris\t2019-09-04",
+            "3\tPerm\tPerm\t\t58.01046\t56.25017\tP\tPPLA\tRU\t\t90\t\t\t\t982419\t\t152\tAsia/Yekaterinburg\t2019-09-04",
+        ])?;
+
+    let ids = |options: SuggestOptions| {
+        let mut ids = engine
+            .suggest_with("pari", options)
+            .iter()
+            .map(|city| city.id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    };
+
+    assert_eq!(ids(SuggestOptions::new(5).max_edits(0)), vec![2]);
+    assert_eq!(ids(SuggestOptions::new(5).max_edits(1)), vec![1, 2]);
+    assert_eq!(ids(SuggestOptions::new(5).max_edits(2)), vec![1, 2, 3]);
+
+    // ratio threshold is ignored
+    assert_eq!(
+        ids(SuggestOptions::new(5).min_score(0.99).max_edits(1)),
+        vec![1, 2]
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_abbreviations() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&[
+            "1\tSaint Petersburg\tSaint Petersburg\t\t27.77086\t-82.67927\tP\tPPL\tUS\t\tFL\t\t\t\t258308\t\t14\tAmerica/New_York\t2019-09-04",
+            "2\tFort Worth\tFort Worth\t\t32.72541\t-97.32085\tP\tPPL\tUS\t\tTX\t\t\t\t918915\t\t199\tAmerica/Chicago\t2019-09-04",
+            "3\tMount Vernon\tMount Vernon\t\t40.9126\t-73.83708\tP\tPPL\tUS\t\tNY\t\t\t\t67292\t\t35\tAmerica/New_York\t2019-09-04",
+        ])?;
+
+    let top = |pattern: &str, options: SuggestOptions| {
+        engine
+            .suggest_with(pattern, options.min_score(0.95))
+            .first()
+            .map(|c| c.id)
+    };
+
+    assert_eq!(top("St. Petersburg", SuggestOptions::new(1)), Some(1));
+    assert_eq!(top("st.petersburg", SuggestOptions::new(1)), Some(1));
+    assert_eq!(top("Ft Worth", SuggestOptions::new(1)), Some(2));
+    assert_eq!(top("Mt Vernon", SuggestOptions::new(1)), Some(3));
+
+    // extended table
+    let mut abbreviations = DEFAULT_ABBREVIATIONS.to_vec();
+    abbreviations.push(("spb", "saint petersburg"));
+    assert_eq!(top("spb", SuggestOptions::new(1)), None);
+    assert_eq!(
+        top("spb", SuggestOptions::new(1).abbreviations(&abbreviations)),
+        Some(1)
+    );
+
+    // disabled
+    assert_eq!(
+        top("Ft Worth", SuggestOptions::new(1).abbreviations(&[])),
+        None
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn normalize_query() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let query = engine.normalize_query("  St. Zürich ");
+    assert_eq!(query.pattern, "  st. zurich ");
+    assert_eq!(query.expanded.as_deref(), Some("saint zurich"));
+
+    let query = engine.normalize_query("Воронеж");
+    assert_eq!(query.pattern, "воронеж");
+    assert!(query.expanded.is_none());
+
+    let query = engine.normalize_query_with("spb", &[("spb", "Saint Petersburg")]);
+    assert_eq!(query.expanded.as_deref(), Some("saint petersburg"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn entry_filter() -> Result<(), Box<dyn Error>> {
+    let filter = EntryFilter {
+        max_length: Some(20),
+        drop_urls: true,
+        strip_parentheses: true,
+        former_names: false,
+    };
+    assert_eq!(
+        filter.apply("Frankfurt (Oder)").as_deref(),
+        Some("Frankfurt")
+    );
+    assert_eq!(filter.apply(" Voronezh ").as_deref(), Some("Voronezh"));
+    assert!(filter
+        .apply("https://en.wikipedia.org/wiki/Voronezh")
+        .is_none());
+    assert!(filter.apply("www.voronezh.ru").is_none());
+    assert!(filter.apply("Voronezh Voronezh Voronezh").is_none());
+    assert!(filter.apply("(Oder)").is_none());
+    assert!(EntryFilter::default().apply("").is_none());
+
+    let build = |entry_filter| {
+        Engine::new_from_files_content(SourceFileContentOptions {
+            cities: "2925533\tFrankfurt (Oder)\tFrankfurt (Oder)\thttp://frankfurt-oder.de,Slubfurt (Oder)\t52.34714\t14.55062\tP\tPPLA2\tDE\t\t11\t\t\t\t57015\t\t48\tEurope/Berlin\t2019-09-04".to_owned(),
+            entry_filter,
+            ..Default::default()
+        })
+    };
+
+    let engine = build(EntryFilter::default())?;
+    assert_eq!(
+        engine.suggest::<&str>("http://frankfurt-oder.de", 1, Some(0.99), None)[0].id,
+        2925533
+    );
+    assert_eq!(
+        engine.suggest::<&str>("slubfurt", 1, Some(0.99), None)[0].id,
+        2925533
+    );
+
+    // `Slubfurt (Oder)` is too long
+    let engine = build(EntryFilter {
+        max_length: Some(10),
+        ..Default::default()
+    })?;
+    assert!(engine
+        .suggest::<&str>("slubfurt", 1, Some(0.99), None)
+        .is_empty());
+
+    // but not after stripping of qualifier
+    let engine = build(EntryFilter {
+        max_length: Some(10),
+        ..filter
+    })?;
+    assert!(engine
+        .suggest::<&str>("http://frankfurt-oder.de", 1, Some(0.99), None)
+        .is_empty());
+    assert_eq!(
+        engine.suggest::<&str>("slubfurt", 1, Some(0.99), None)[0].id,
+        2925533
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn former_names() -> Result<(), Box<dyn Error>> {
+    let build = |former_names| {
+        Engine::new_from_files_content(SourceFileContentOptions {
+            cities: "498817\tSaint Petersburg\tSaint Petersburg\tLeningrad,Petrograd,Sankt-Peterburg\t59.93863\t30.31413\tP\tPPLA\tRU\t\t66\t\t\t\t5351935\t\t11\tEurope/Moscow\t2019-09-04".to_owned(),
+            names: Some(
+                [
+                    "1\t498817\ten\tLeningrad\t\t\t\t1\t1924\t1991",
+                    "2\t498817\t\tPetrograd\t\t\t\t1\t1914\t1924",
+                    "3\t498817\tlink\thttps://en.wikipedia.org/wiki/Leningrad\t\t\t\t1\t\t",
+                    "4\t498817\tde\tSankt-Peterburg\t\t\t\t\t\t",
+                ]
+                .join("\n"),
+            ),
+            filter_languages: vec!["de"],
+            entry_filter: EntryFilter {
+                former_names,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    };
+
+    // historic names are searchable alternate names by default
+    let engine = build(false)?;
+    assert!(engine.get(&498817).unwrap().former_names.is_none());
+    assert_eq!(
+        engine.suggest::<&str>("leningrad", 1, Some(0.99), None)[0].id,
+        498817
+    );
+
+    let engine = build(true)?;
+    assert_eq!(
+        engine.get(&498817).unwrap().former_names.as_deref(),
+        Some(["Leningrad".to_owned(), "Petrograd".to_owned()].as_slice())
+    );
+    assert!(engine
+        .suggest::<&str>("leningrad", 1, Some(0.99), None)
+        .is_empty());
+    assert_eq!(
+        engine.suggest::<&str>("sankt-peterburg", 1, Some(0.99), None)[0].id,
+        498817
+    );
+
+    // redirect to current city
+    let items = engine.suggest_with("leningrad", SuggestOptions::new(1).former_names(true));
+    assert_eq!(items[0].id, 498817);
+    assert_eq!(items[0].name, "Saint Petersburg");
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "romanize")]
+fn suggest_romanize() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&["1486209\tЕкатеринбург\tЕкатеринбург\tВоронеж-Сити\t56.8519\t60.6122\tP\tPPLA\tRU\t\t71\t\t\t\t1495066\t\t237\tAsia/Yekaterinburg\t2019-09-04"])?;
+
+    for pattern in ["yekaterinburg", "voronezh-siti"] {
+        let items = engine.suggest::<&str>(pattern, 1, Some(0.99), None);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, 1486209);
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "translit")]
+fn suggest_transliterate() -> Result<(), Box<dyn Error>> {
+    let engine = engine_from_cities(&[
+            "2655785\tBeverley\tBeverley\t\t53.84587\t-0.42332\tP\tPPL\tGB\t\tENG\t\t\t\t29110\t\t17\tEurope/London\t2019-09-04",
+            "264371\tΑθήνα\tΑθήνα\t\t37.98376\t23.72784\tP\tPPLC\tGR\t\tESYE31\t\t\t\t664046\t\t70\tEurope/Athens\t2019-09-04",
+        ])?;
+
+    for (pattern, id) in [("Беверли", 2655785), ("athena", 264371)] {
+        let options = SuggestOptions::new(1).min_score(0.9);
+        assert!(engine.suggest_with(pattern, options.clone()).is_empty());
+        let items = engine.suggest_with(pattern, options.transliterate(true));
+        assert_eq!(items[0].id, id, "{pattern}");
+    }
+
+    // matched name is the original spelling of transliterated entry
+    let items = engine.suggest_with_scores(
+        "athena",
+        SuggestOptions::new(1).min_score(0.9).transliterate(true),
+    );
+    assert!(
+        !items[0].matched_name.is_ascii(),
+        "{}",
+        items[0].matched_name
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let result = engine.reverse::<&str>((51.6372, 39.1937), 1, None, None);
+    assert!(result.is_some());
+    let items = result.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.name, "Voronezh");
+    assert_eq!(items[0].city.country.as_ref().unwrap().name, "Russia");
+    assert_eq!(
+        items[0].city.admin_division.as_ref().unwrap().name,
+        "Voronezj"
+    );
+    // ~3.9 km to city center
+    assert!((3800.0..4000.0).contains(&items[0].distance_m));
+
+    let result = engine.reverse::<&str>((53.84587, -0.42332), 1, None, None);
+    assert!(result.is_some());
+    let items = result.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.name, "Beverley");
+    assert_eq!(
+        items[0].city.admin2_division.as_ref().unwrap().name,
+        "East Riding of Yorkshire"
+    );
+
+    let result = engine.reverse((53.84587, -0.42332), 1, None, Some(&["ar"]));
+    assert_eq!(result.unwrap().len(), 0);
+
+    let result = engine.reverse((53.84587, -0.42332), 1, None, Some(&["gb"]));
+    assert_eq!(result.unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_antimeridian_and_poles() -> Result<(), Box<dyn Error>> {
+    let city = |id: u32, lat: f32, lng: f32| {
+        format!("{id}\tCity{id}\tCity{id}\t\t{lat}\t{lng}\tP\tPPL\tXX\t\t01\t\t\t\t1000\t\t0\tUTC\t2019-09-04")
+    };
+    let cities = [
+        // Chukotka and Alaska sides of antimeridian
+        city(1, 65.0, 179.5),
+        city(2, 65.0, -179.0),
+        city(3, 65.0, 175.0),
+        // arctic ones: across the pole and along the parallel
+        city(4, 89.0, 0.0),
+        city(5, 89.0, 180.0),
+        city(6, 88.0, 40.0),
+    ];
+
+    let engine = engine_from_cities(&cities)?;
+    let ids = |loc, limit| {
+        engine
+            .reverse::<&str>(loc, limit, None, None)
+            .unwrap()
+            .iter()
+            .map(|item| item.city.id)
+            .collect::<Vec<_>>()
+    };
+
+    // across antimeridian
+    assert_eq!(ids((65.0, -179.9), 2), vec![1, 2]);
+    assert_eq!(ids((65.0, 179.9), 3), vec![1, 2, 3]);
+
+    // across the pole: 2 degrees over it vs 40 degrees along parallel of 89
+    assert_eq!(ids((89.0, 0.0), 1), vec![4]);
+    assert_eq!(ids((89.5, 170.0), 1), vec![5]);
+    assert_eq!(ids((88.9, 179.0), 2), vec![5, 4]);
+
+    let items = engine
+        .reverse::<&str>((65.0, -179.9), 1, None, None)
+        .unwrap();
+    assert!((items[0].distance - 0.36).abs() < 0.001);
+    assert!(items[0].distance_m < 30_000.0);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_nearest_limit() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let loc = (51.6372, 39.1937);
+    let options = ReverseOptions::new(1).k(0.00001);
+
+    // only the nearest city is rescored by default
+    let items = engine.reverse_with(loc, options.clone()).unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.name, "Voronezh");
+
+    // populated Moscow outranks Voronezh within wider pool
+    let items = engine
+        .reverse_with(loc, options.clone().nearest_limit(5))
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.name, "Moscow");
+
+    // pool is never narrower than limit
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(3).k(0.00001).nearest_limit(1))
+        .unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].city.name, "Moscow");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_scoring() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let options = ReverseOptions::new(1).nearest_limit(5);
+    let name = |loc, options: ReverseOptions| {
+        engine.reverse_with(loc, options).unwrap()[0]
+            .city
+            .name
+            .clone()
+    };
+
+    // megacity outranks everything by linear score, but not by log of population
+    let voronezh = (51.6372, 39.1937);
+    assert_eq!(name(voronezh, options.clone().k(0.00001)), "Moscow");
+    assert_eq!(
+        name(
+            voronezh,
+            options.clone().scoring(ReverseScoring::LogPopulation)
+        ),
+        "Voronezh"
+    );
+
+    // between Beverley and London, nearer to Beverley
+    let loc = (53.1446, -0.334);
+    assert_eq!(name(loc, options.clone()), "Beverley");
+    assert_eq!(
+        name(loc, options.clone().scoring(ReverseScoring::LogPopulation)),
+        "Beverley"
+    );
+    assert_eq!(
+        name(loc, options.clone().scoring(ReverseScoring::Gravity)),
+        "London"
+    );
+
+    let loc = (52.7941, -0.2894);
+    assert_eq!(name(loc, options.clone()), "Beverley");
+    assert_eq!(
+        name(loc, options.clone().scoring(ReverseScoring::LogPopulation)),
+        "London"
+    );
+
+    // sorted by score
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(5).scoring(ReverseScoring::Gravity))
+        .unwrap();
+    assert_eq!(items.len(), 5);
+    assert!(items.windows(2).all(|w| w[0].score <= w[1].score));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_min_population() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let loc = (51.6372, 39.1937);
+
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(1).min_population(1_000_000))
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.name, "Moscow");
+
+    // the nearest significant cities are found beyond the nearest ones
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(2).min_population(1_000_000))
+        .unwrap();
+    assert!(items.iter().all(|item| item.city.population >= 1_000_000));
+    assert_eq!(items.len(), 2);
+
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(1).min_population(u32::MAX))
+        .unwrap();
+    assert!(items.is_empty());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_country_trees() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let tree = engine.memory_usage().tree;
+    let loc = (51.6372, 39.1937);
+
+    // filtered by countries answers as nearest cities of whole tree filtered by countries
+    let countries = ["GB", "rs"];
+    let all = engine.reverse_with(loc, ReverseOptions::new(10)).unwrap();
+    let expected = all
+        .iter()
+        .filter(|item| {
+            item.city
+                .country
+                .as_ref()
+                .is_some_and(|c| c.code == "GB" || c.code == "RS")
+        })
+        .take(2)
+        .map(|item| item.city.id)
+        .collect::<Vec<_>>();
+    assert_eq!(expected.len(), 2);
+
+    let items = engine
+        .reverse_with(loc, ReverseOptions::new(2).countries(&countries))
+        .unwrap();
+    assert_eq!(
+        items.iter().map(|item| item.city.id).collect::<Vec<_>>(),
+        expected
+    );
+
+    // blocked cities are skipped
+    let blocked = [expected[0]];
+    let items = engine
+        .reverse_with(
+            loc,
+            ReverseOptions::new(2)
+                .countries(&countries)
+                .blocked_ids(&blocked),
+        )
+        .unwrap();
+    assert_eq!(
+        items.iter().map(|item| item.city.id).collect::<Vec<_>>(),
+        all.iter()
+            .filter(|item| {
+                item.city.id != expected[0]
+                    && item
+                        .city
+                        .country
+                        .as_ref()
+                        .is_some_and(|c| c.code == "GB" || c.code == "RS")
+            })
+            .take(2)
+            .map(|item| item.city.id)
+            .collect::<Vec<_>>()
+    );
+
+    // country trees are built on demand
+    assert!(engine.memory_usage().tree > tree);
+
+    // or ahead of the first request
+    let engine = get_engine(None, None, None, vec![])?;
+    engine.warm_up();
+    assert!(engine.memory_usage().tree > tree);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn options() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let countries = vec!["GB".to_owned()];
+    let items = engine.suggest_with(
+        "Beverley",
+        SuggestOptions::new(1).min_score(0.9).countries(&countries),
+    );
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0].id,
+        engine.suggest("Beverley", 1, Some(0.9), Some(&countries))[0].id
+    );
+    assert!(engine
+        .suggest_with("Beverley", SuggestOptions::new(1).countries(&["RU"]))
+        .is_empty());
+
+    // viewport around Voronezh
+    let bbox = BBox {
+        min_lat: 50.0,
+        min_lng: 38.0,
+        max_lat: 53.0,
+        max_lng: 40.0,
+    };
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(1).bbox(bbox));
+    assert_eq!(items[0].id, 472045);
+    let items = engine.suggest_with("Moscow", SuggestOptions::new(1).min_score(0.9).bbox(bbox));
+    assert!(items.is_empty());
+
+    let items = engine
+        .reverse_with((53.84587, -0.42332), ReverseOptions::new(2).k(0.1))
+        .unwrap();
+    let expected = engine
+        .reverse::<&str>((53.84587, -0.42332), 2, Some(0.1), None)
+        .unwrap();
+    assert_eq!(
+        items.iter().map(|i| i.city.id).collect::<Vec<_>>(),
+        expected.iter().map(|i| i.city.id).collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_candidates() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let options = SuggestOptions::new(3).min_score(0.99);
+    assert!(engine.suggest_with("Voronezx", options.clone()).is_empty());
+
+    let items = engine.suggest_candidates("Voronezx", options);
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].city.id, 472045);
+    assert!(items[0].score > 0.9 && items[0].score < 0.99);
+    assert!(items.windows(2).all(|w| w[0].score >= w[1].score));
+
+    // filters are still applied
+    let items = engine.suggest_candidates("Voronezx", SuggestOptions::new(3).countries(&["GB"]));
+    assert!(items
+        .iter()
+        .all(|i| i.city.country.as_ref().unwrap().code == "GB"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_session() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let options = SuggestOptions::new(3).min_score(0.9);
+    let ids = |items: Vec<geosuggest_core::SuggestItem>| {
+        items.iter().map(|item| item.city.id).collect::<Vec<_>>()
+    };
+
+    let mut session = engine.suggest_session(options.clone());
+    for pattern in ["Vo", "Vor", "Voro", "Voronezh", "Vor", "Bev", "Beverley"] {
+        assert_eq!(
+            ids(session.suggest(pattern)),
+            ids(engine.suggest_with_scores(pattern, options.clone())),
+            "{pattern}"
+        );
+    }
+
+    session.reset();
+    assert_eq!(ids(session.suggest("Beverley")), vec![2655785]);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_with_scores() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_with_scores("Воронеж", SuggestOptions::new(1));
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].city.id, 472045);
+    assert_eq!(items[0].matched, "воронеж");
+    assert_eq!(items[0].matched_name, "Воронеж");
+    assert_eq!(items[0].score, 1.0);
+
+    let items = engine.suggest_with_scores("Voronezx", SuggestOptions::new(1));
+    assert_eq!(items[0].city.id, 472045);
+    assert!(items[0].matched.starts_with("voronez"));
+    assert!(items[0].matched_name.starts_with("Voronez"));
+    assert!(items[0].score < 1.0);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_admin1() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(5).admin1("RU.86"));
+    assert!(!items.is_empty());
+    assert!(items
+        .iter()
+        .all(|c| c.admin_division.as_ref().unwrap().id == 472039));
+
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(5).admin1("RU.48"));
+    assert!(items.is_empty());
+
+    // unknown division
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(5).admin1("XX.00"));
+    assert!(items.is_empty());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn find_admin1() -> Result<(), Box<dyn Error>> {
+    let engine = Engine::new_from_files_content(SourceFileContentOptions {
+        cities: [
+            "5368361\tLos Angeles\tLos Angeles\t\t34.05223\t-118.24368\tP\tPPLA2\tUS\t\tCA\t037\t\t\t3971883\t\t96\tAmerica/Los_Angeles\t2019-09-04",
+            "5128581\tNew York City\tNew York City\t\t40.71427\t-74.00597\tP\tPPL\tUS\t\tNY\t\t\t\t8804190\t\t10\tAmerica/New_York\t2019-09-04",
+            "2063523\tPerth\tPerth\t\t-31.95224\t115.8614\tP\tPPLA\tAU\t\t08\t\t\t\t1896548\t\t8\tAustralia/Perth\t2019-09-04",
+            "5809844\tSeattle\tSeattle\t\t47.60621\t-122.33207\tP\tPPLA2\tUS\t\tWA\t033\t\t\t737015\t\t56\tAmerica/Los_Angeles\t2019-09-04",
+        ]
+        .join("\n"),
+        admin1_codes: Some(
+            [
+                "US.CA\tCalifornia\tCalifornia\t5332921",
+                "US.NY\tNew York\tNew York\t5128638",
+                "AU.08\tWestern Australia\tWestern Australia\t2058645",
+                "US.WA\tWashington\tWashington\t5815135",
+            ]
+            .join("\n"),
+        ),
+        ..Default::default()
+    })?;
+
+    let code = |token: &str, countries: Option<&[&str]>| {
+        engine
+            .find_admin1(token, countries, 0.9)
+            .map(|(info, _)| info.division.code.clone())
+    };
+
+    for token in ["CA", "Calif.", "california", "Californa", " calif "] {
+        assert_eq!(code(token, None).as_deref(), Some("US.CA"), "{token}");
+    }
+    assert_eq!(code("N.Y.", None).as_deref(), Some("US.NY"));
+    assert_eq!(code("new york", None).as_deref(), Some("US.NY"));
+    assert_eq!(code("WA", Some(&["au"])).as_deref(), Some("AU.08"));
+    // ambiguous token is division of the first requested country, code over abbreviation
+    assert_eq!(code("WA", Some(&["AU", "US"])).as_deref(), Some("AU.08"));
+    assert_eq!(code("WA", Some(&["US", "AU"])).as_deref(), Some("US.WA"));
+    assert_eq!(code("WA", None).as_deref(), Some("US.WA"));
+    assert_eq!(code("Texas", None), None);
+    assert_eq!(code("Calif.", Some(&["AU"])), None);
+    assert_eq!(code("  ", None), None);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_min_population() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_with("Vo", SuggestOptions::new(10).min_score(0.5));
+    assert!(items.iter().any(|c| c.population < 100_000));
+
+    let items = engine.suggest_with(
+        "Vo",
+        SuggestOptions::new(10)
+            .min_score(0.5)
+            .min_population(100_000),
+    );
+    assert!(items.iter().any(|c| c.id == 472045));
+    assert!(items.iter().all(|c| c.population >= 100_000));
+
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(10).min_population(900_000));
+    assert!(items.is_empty());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_feature_codes() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let city = engine.get(&472045).unwrap();
+    assert_eq!(city.feature_code, "PPLA");
+
+    let items = engine.suggest_with(
+        "Voronezh",
+        SuggestOptions::new(5).feature_codes(&["pplc", "ppla"]),
+    );
+    assert_eq!(items[0].id, 472045);
+    assert!(items
+        .iter()
+        .all(|c| ["PPLC", "PPLA"].contains(&c.feature_code.as_str())));
+
+    let items = engine.suggest_with("Voronezh", SuggestOptions::new(5).feature_codes(&["PPLC"]));
+    assert!(items.is_empty());
+
+    let items = engine.suggest_with("Moscow", SuggestOptions::new(5).feature_codes(&["PPLC"]));
+    assert_eq!(items[0].id, 524901);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_places() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine.suggest_places("Voronez", SuggestOptions::new(10), PlaceWeights::default());
+    assert!(matches!(items[0].place, Place::City(city) if city.id == 472045));
+    assert!(items
+        .iter()
+        .any(|i| matches!(&i.place, Place::Admin1(info) if info.division.id == 472039)));
+    assert!(items.windows(2).all(|w| w[0].score >= w[1].score));
+
+    let weights = PlaceWeights {
+        city: 0.0,
+        admin1: 0.0,
+        admin2: 0.0,
+        country: 1.0,
+    };
+    let items = engine.suggest_places("Russia", SuggestOptions::new(10), weights);
+    assert!(!items.is_empty());
+    assert!(items.iter().all(|i| matches!(i.place, Place::Country(_))));
+
+    // countries filter is applied to all kinds
+    let items = engine.suggest_places(
+        "Voronez",
+        SuggestOptions::new(10).countries(&["GB"]),
+        PlaceWeights::default(),
+    );
+    assert!(items.iter().all(|i| match &i.place {
+        Place::City(city) => city.country.as_ref().unwrap().code == "GB",
+        Place::Admin1(info) | Place::Admin2(info) => info.division.code.starts_with("GB."),
+        Place::Country(country) => country.info.iso == "GB",
+        _ => false,
+    }));
+
+    // admin2 divisions are excluded by default
+    let items = engine.suggest_places(
+        "East Riding",
+        SuggestOptions::new(10),
+        PlaceWeights::default(),
+    );
+    assert!(items.iter().all(|i| !matches!(i.place, Place::Admin2(_))));
+    let items = engine.suggest_places(
+        "East Riding",
+        SuggestOptions::new(10),
+        PlaceWeights::only(&[PlaceKind::Admin2]),
+    );
+    assert_eq!(items.len(), 1);
+    assert!(matches!(&items[0].place, Place::Admin2(info) if info.division.code == "GB.ENG.E1"));
+
+    // countries and admin divisions only, e.g. address form
+    let items = engine.suggest_places(
+        "Vor",
+        SuggestOptions::new(10),
+        PlaceWeights::only(&[PlaceKind::Country, PlaceKind::Admin1]),
+    );
+    assert!(!items.is_empty());
+    assert!(items.iter().all(|i| !matches!(i.place, Place::City(_))));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_lang() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec!["ru", "de", "ja"])?;
+
+    let find = |pattern: &str, lang: Option<&str>| {
+        let options = SuggestOptions::new(1);
+        let options = match lang {
+            Some(lang) => options.lang(lang),
+            None => options,
+        };
+        engine.suggest_with(pattern, options).first().map(|c| c.id)
+    };
+
+    assert_eq!(find("ヴォロネジ", None), Some(472045));
+    assert_eq!(find("ヴォロネジ", Some("ja")), Some(472045));
+    assert_eq!(find("ヴォロネジ", Some("de")), None);
+    assert_eq!(find("Воронеж", Some("de")), None);
+    assert_eq!(find("Woronesch", Some("de-AT")), Some(472045));
+
+    // default name is always matched
+    assert_eq!(find("Voronezh", Some("ko")), Some(472045));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn suggest_lang_shared_spelling() -> Result<(), Box<dyn Error>> {
+    // `Voronež` is the same in fi and sl, one entry of both languages
+    let engine = get_engine(None, None, None, vec!["fi", "sl", "de"])?;
+
+    let matched = |lang: &str| {
+        engine
+            .suggest_with_scores("Voronež", SuggestOptions::new(1).lang(lang))
+            .first()
+            .map(|item| item.matched_name.to_owned())
+    };
+
+    assert_eq!(matched("fi").as_deref(), Some("Voronež"));
+    assert_eq!(matched("sl").as_deref(), Some("Voronež"));
+    assert_eq!(matched("de").as_deref(), Some("Voronezh"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn similarity_metric() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    for metric in [
+        SimilarityMetric::JaroWinkler,
+        SimilarityMetric::Levenshtein,
+        SimilarityMetric::NormalizedDamerau,
+        SimilarityMetric::NGram,
+    ] {
+        let items = engine.suggest_with("Voronezx", SuggestOptions::new(1).metric(metric));
+        assert_eq!(items[0].id, 472045, "{metric:?}");
+    }
+
+    // transposition is a single edit only for Damerau–Levenshtein
+    let options = SuggestOptions::new(1).min_score(0.85);
+    assert!(engine
+        .suggest_with(
+            "Vornoezh",
+            options.clone().metric(SimilarityMetric::Levenshtein)
+        )
+        .is_empty());
+    let items = engine.suggest_with(
+        "Vornoezh",
+        options.metric(SimilarityMetric::NormalizedDamerau),
+    );
+    assert_eq!(items[0].id, 472045);
+
+    assert_eq!(
+        "ngram".parse::<SimilarityMetric>()?,
+        SimilarityMetric::NGram
+    );
+    assert!("soundex".parse::<SimilarityMetric>().is_err());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn cities_in_geohash() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let ids =
+        |items: Vec<&geosuggest_core::CitiesRecord>| items.iter().map(|c| c.id).collect::<Vec<_>>();
+
+    assert_eq!(ids(engine.cities_in_geohash("uc5vgw")), vec![472045]);
+    assert_eq!(ids(engine.cities_in_geohash("UC5")), vec![472045]);
+    // London and Beverley, population desc
+    assert_eq!(ids(engine.cities_in_geohash("g")), vec![2643743, 2655785]);
+    assert!(engine.cities_in_geohash("uc5vgx").is_empty());
+    assert!(engine.cities_in_geohash("ua!").is_empty());
+
+    Ok(())
+}
+
+#[cfg(feature = "h3")]
+#[test_log::test]
+fn cities_in_h3() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let cell = h3o::LatLng::new(51.67204, 39.1843)?.to_cell(h3o::Resolution::Five);
+    let items = engine.cities_in_h3(cell.into());
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].id, 472045);
+
+    let cell = h3o::LatLng::new(51.67204, 39.1843)?.to_cell(h3o::Resolution::Zero);
+    assert!(engine
+        .cities_in_h3(cell.into())
+        .iter()
+        .any(|c| c.id == 472045));
+
+    assert!(engine.cities_in_h3(0).is_empty());
+
+    // cells crossing the antimeridian and cells of poles
+    let city = |id: u32, lat: f32, lng: f32| {
+        format!("{id}\tCity{id}\tCity{id}\t\t{lat}\t{lng}\tP\tPPL\tXX\t\t01\t\t\t\t1000\t\t0\tUTC\t2019-09-04")
+    };
+    let cities = [
+        (1, -16.8, 179.95),
+        (2, -16.8, -179.95),
+        (3, 89.99, 45.0),
+        (4, -89.99, -135.0),
+    ];
+    let engine = engine_from_cities(
+        &cities
+            .iter()
+            .map(|(id, lat, lng)| city(*id, *lat, *lng))
+            .collect::<Vec<_>>(),
+    )?;
+    for resolution in [
+        h3o::Resolution::Zero,
+        h3o::Resolution::Two,
+        h3o::Resolution::Five,
+    ] {
+        for (id, lat, lng) in cities {
+            let cell = h3o::LatLng::new(lat as f64, lng as f64)?.to_cell(resolution);
+            let items = engine.cities_in_h3(cell.into());
+            assert!(items.iter().any(|c| c.id == id), "{id} {resolution}");
+            // no duplicates of split cells
+            let mut ids = items.iter().map(|c| c.id).collect::<Vec<_>>();
+            ids.dedup();
+            assert_eq!(ids.len(), items.len());
+        }
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn nearest_along() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    // Moscow -> Voronezh
+    let route = [(55.75, 37.6), (53.5, 38.5), (51.7, 39.2)];
+    let items = engine.nearest_along(&route, 20.0);
+    assert_eq!(
+        items.iter().map(|i| i.city.id).collect::<Vec<_>>(),
+        vec![524901, 472045]
+    );
+    assert!(items[0].progress_km < 1.0);
+    assert!(items[1].progress_km > 400.0 && items[1].progress_km < 500.0);
+    assert!(items.iter().all(|i| i.offset_km <= 20.0));
+
+    let route = route.into_iter().rev().collect::<Vec<_>>();
+    let items = engine.nearest_along(&route, 20.0);
+    assert_eq!(
+        items.iter().map(|i| i.city.id).collect::<Vec<_>>(),
+        vec![472045, 524901]
+    );
+
+    // Beverley is about 3.5km aside from the straight line, London is near the start
+    let route = [(51.5, -0.12), (54.0, -0.5)];
+    assert_eq!(engine.nearest_along(&route, 1.0).len(), 1);
+    assert_eq!(engine.nearest_along(&route, 20.0).len(), 2);
+
+    assert!(engine.nearest_along(&[], 20.0).is_empty());
+    assert_eq!(engine.nearest_along(&[(51.67, 39.18)], 5.0).len(), 1);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_centroid() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let top = |points: &[(f32, f32)], mode| {
+        engine
+            .reverse_centroid(points, mode, ReverseOptions::new(1))
+            .map(|items| items[0].city.id)
+    };
+
+    // gps samples around Voronezh
+    let samples = [(51.65, 39.15), (51.69, 39.21), (51.62, 39.23), (51.7, 39.1)];
+    assert_eq!(top(&samples, CentroidMode::Centroid), Some(472045));
+    assert_eq!(top(&samples, CentroidMode::Medoid), Some(472045));
+    assert_eq!(top(&samples, CentroidMode::MinTotalDistance), Some(472045));
+
+    // and an outlier in Moscow
+    let samples = [samples.as_slice(), &[(55.75, 37.6)]].concat();
+    assert_eq!(top(&samples, CentroidMode::Medoid), Some(472045));
+    assert_eq!(top(&samples, CentroidMode::MinTotalDistance), Some(472045));
+
+    let items = engine
+        .reverse_centroid(
+            &samples,
+            CentroidMode::MinTotalDistance,
+            ReverseOptions::new(2),
+        )
+        .unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items[0].score <= items[1].score);
+
+    assert!(top(&[], CentroidMode::Centroid).is_none());
+    assert!(top(&[(0.0, 0.0), (0.0, 180.0)], CentroidMode::Centroid).is_none());
+
+    // distance is wrapped at antimeridian
+    let engine = engine_from_cities(&[
+        "1\tEast\tEast\t\t-16.8\t179.95\tP\tPPL\tFJ\t\t01\t\t\t\t1000\t\t0\tPacific/Fiji\t2019-09-04",
+        "2\tWest\tWest\t\t-16.8\t-179.95\tP\tPPL\tFJ\t\t01\t\t\t\t1000\t\t0\tPacific/Fiji\t2019-09-04",
+    ])?;
+    let items = engine
+        .reverse_centroid(
+            &[(-16.8, 179.9), (-16.8, -179.9)],
+            CentroidMode::MinTotalDistance,
+            ReverseOptions::new(2),
+        )
+        .unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items.iter().all(|item| item.distance < 0.01), "{items:?}");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_rollup_admin1() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let items = engine
+        .reverse::<&str>((51.6372, 39.1937), 10, None, None)
+        .unwrap();
+    let regions = rollup_admin1(&items);
+
+    assert_eq!(regions[0].division.unwrap().id, 472039);
+    assert_eq!(regions[0].city.id, 472045);
+    assert_eq!(regions.iter().map(|r| r.cities).sum::<usize>(), items.len());
+    assert_eq!(
+        regions.iter().map(|r| r.population).sum::<u64>(),
+        items.iter().map(|i| i.city.population as u64).sum::<u64>()
+    );
+    for (i, region) in regions.iter().enumerate() {
+        assert!(regions[i + 1..]
+            .iter()
+            .all(|r| r.division.map(|d| d.id) != region.division.map(|d| d.id)));
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_vote_admin2() -> Result<(), Box<dyn Error>> {
+    let city = |id: u32, lat: f32, admin2: &str| {
+        format!("{id}\tCity{id}\tCity{id}\t\t{lat}\t10\tP\tPPL\tXX\t\t01\t{admin2}\t\t\t1000\t\t0\tUTC\t2019-09-04")
+    };
+    // the nearest city is of county B, the most of others are of county A
+    let cities = [
+        city(1, 50.01, "B"),
+        city(2, 50.02, "A"),
+        city(3, 50.025, "A"),
+        city(4, 50.03, "A"),
+    ];
+
+    let engine = Engine::new_from_files_content(SourceFileContentOptions {
+        cities: cities.join("\n"),
+        admin2_codes: Some(
+            "XX.01.A\tCounty A\tCounty A\t9001\nXX.01.B\tCounty B\tCounty B\t9002".to_owned(),
+        ),
+        ..Default::default()
+    })?;
+
+    let items = engine.reverse::<&str>((50.0, 10.0), 4, None, None).unwrap();
+    assert_eq!(items[0].city.admin2_division.as_ref().unwrap().id, 9002);
+
+    let city = vote_admin2(&items).unwrap();
+    assert_eq!(city.id, 2);
+    assert_eq!(city.admin2_division.as_ref().unwrap().id, 9001);
+
+    // single city decides alone
+    assert_eq!(vote_admin2(&items[..1]).unwrap().id, 1);
+    assert!(vote_admin2(&[]).is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn timezone_at() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    assert_eq!(engine.timezone_at(51.6372, 39.1937), Some("Europe/Moscow"));
+    assert_eq!(engine.timezone_at(53.8, -0.4), Some("Europe/London"));
+    assert_eq!(engine.timezone_at(44.8, 20.4), Some("Europe/Belgrade"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn timezone_at_antimeridian() -> Result<(), Box<dyn Error>> {
+    let cities = [
+        "2204582\tLabasa\tLabasa\t\t-16.41667\t179.38333\tP\tPPLA\tFJ\t\t03\t\t\t\t27949\t\t0\tPacific/Fiji\t2019-09-04",
+        "4035413\tApia\tApia\t\t-13.83333\t-171.76666\tP\tPPLC\tWS\t\t11\t\t\t\t40407\t\t0\tPacific/Apia\t2019-09-04",
+    ];
+    let engine = engine_from_cities(&cities)?;
+
+    // east of antimeridian (Taveuni): Apia is nearer by degrees, Labasa by great-circle distance
+    assert_eq!(engine.timezone_at(-16.8, -179.9), Some("Pacific/Fiji"));
+    assert_eq!(engine.timezone_at(-16.4, 179.0), Some("Pacific/Fiji"));
+    assert_eq!(engine.timezone_at(-14.0, -172.0), Some("Pacific/Apia"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn population_rank() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    // Moscow is the largest one
+    let rank = engine
+        .population_rank(engine.get(&524901).unwrap())
+        .unwrap();
+    assert_eq!(rank.country, 100.0);
+    assert_eq!(rank.global, 100.0);
+
+    // Voronezh is the second of two russian cities
+    let rank = engine
+        .population_rank(engine.get(&472045).unwrap())
+        .unwrap();
+    assert_eq!(rank.country, 50.0);
+
+    // Beverley is the smallest one
+    let rank = engine
+        .population_rank(engine.get(&2655785).unwrap())
+        .unwrap();
+    assert_eq!(rank.country, 50.0);
+    assert_eq!(rank.global, 20.0);
+
+    // survives dump/load
+    let storage = storage::bincode::Storage::new();
+    let filepath = temp_dir().join("test-population-rank.bincode");
+    storage.dump_to(&filepath, &engine)?;
+    let engine = storage.load_from(&filepath)?;
+    let rank = engine
+        .population_rank(engine.get(&2655785).unwrap())
+        .unwrap();
+    assert_eq!(rank.global, 20.0);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn country_stats() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec!["ru"])?;
+
+    let stats = engine.country_stats("gb", 10).unwrap();
+    assert_eq!(stats.cities, 2);
+    assert_eq!(stats.population, 7556900 + 30587);
+    assert_eq!(
+        stats
+            .largest_cities
+            .iter()
+            .map(|c| c.id)
+            .collect::<Vec<_>>(),
+        vec![2643743, 2655785]
+    );
+
+    let stats = engine.country_stats("RU", 1).unwrap();
+    assert_eq!(stats.largest_cities[0].id, 524901);
+    // only Voronezh is translated
+    assert_eq!(stats.translations.get("ru"), Some(&1));
+
+    assert!(engine.country_stats("US", 10).is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn language_coverage() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec!["ru"])?;
+
+    let coverage = engine.language_coverage().clone();
+    assert_eq!(coverage.len(), 1);
+    assert_eq!(coverage.get("ru"), Some(&3));
+
+    // translations are counted from compressed names too
+    engine.compress_names()?;
+    assert_eq!(engine.language_coverage(), &coverage);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn overrides() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec!["ru"])?;
+    engine.compress_names()?;
+
+    let filepath = temp_dir().join("test-overrides.json");
+    std::fs::write(
+        &filepath,
+        r#"{"cities": {
+            "472045": {"name": "Voronizh", "names": {"en": "Voronizh"}, "population": 20000000},
+            "524901": {"suppress": true},
+            "1": {"suppress": true}
+        }}"#,
+    )?;
+    let overrides = Overrides::from_file(&filepath)?;
+    assert_eq!(engine.apply_overrides(&overrides), 2);
+
+    let city = engine.get(&472045).unwrap();
+    assert_eq!(city.name, "Voronizh");
+    assert_eq!(engine.city_name(city, "en").as_deref(), Some("Voronizh"));
+    // previous translations are kept
+    assert_eq!(engine.city_name(city, "ru").as_deref(), Some("Воронеж"));
+    assert_eq!(engine.population_rank(city).unwrap().global, 100.0);
+
+    assert_eq!(
+        engine.suggest::<&str>("voronizh", 1, None, None)[0].id,
+        472045
+    );
+    assert_eq!(
+        engine.suggest::<&str>("voronezh", 1, None, None)[0].id,
+        472045
+    );
+
+    // suppressed city
+    assert!(engine.get(&524901).is_none());
+    assert!(engine.capital("RU").is_none());
+    assert!(engine
+        .suggest::<&str>("Moscow", 10, None, None)
+        .iter()
+        .all(|c| c.id != 524901));
+    let items = engine
+        .reverse::<&str>((55.75222, 37.61556), 1, None, None)
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_ne!(items[0].city.id, 524901);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn tree_ids() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let ids = engine.tree_ids();
+    assert_eq!(ids.len(), engine.cities().len());
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+    // mapping is preserved by dump/load
+    let storage = storage::bincode::Storage::new();
+    let filepath = temp_dir().join("test-tree-ids.bincode");
+    storage.dump_to(&filepath, &engine)?;
+    assert_eq!(storage.load_from(&filepath)?.tree_ids(), ids);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn language_tags() -> Result<(), Box<dyn Error>> {
+    assert_eq!(canonical_language("ru")?, "ru");
+    assert_eq!(canonical_language("en-US")?, "en");
+    assert_eq!(canonical_language("zh_Hant")?, "zh");
+    assert_eq!(canonical_language("ENG")?, "en");
+    assert_eq!(canonical_language("IATA")?, "iata");
+    assert_eq!(canonical_language("fr_1793")?, "fr_1793");
+    assert!(canonical_language("english").is_err());
+    assert!(canonical_language("e1").is_err());
+
+    let engine = Engine::new_from_files(SourceFileOptions {
+        cities: "tests/misc/cities.txt",
+        names: Some("tests/misc/names.txt"),
+        filter_languages: vec!["RU-ru", "rus", "english"],
+        ..Default::default()
     })?;
-    engine.metadata = Some(EngineMetadata::default());
-    Ok(engine)
+    assert_eq!(
+        engine
+            .city_name(engine.get(&472045).unwrap(), "ru")
+            .as_deref(),
+        Some("Воронеж")
+    );
+    assert_eq!(engine.metadata.as_ref().unwrap().language_warnings.len(), 3);
+
+    Ok(())
 }
 
 #[test_log::test]
-fn suggest() -> Result<(), Box<dyn Error>> {
-    let engine = get_engine(None, None, None, vec![])?;
+fn query_hook() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec![])?;
 
-    let items = engine.suggest::<&str>("voronezh", 1, None, None);
-    assert_eq!(items.len(), 1);
-    assert_eq!(items[0].name, "Voronezh");
-    assert_eq!(items[0].country.as_ref().unwrap().name, "Russia");
-    assert_eq!(items[0].admin_division.as_ref().unwrap().name, "Voronezj");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    engine.set_query_hook(Some(Arc::new(move |event: &QueryEvent| {
+        events_clone.lock().unwrap().push((
+            event.kind,
+            event.pattern.map(String::from),
+            event.countries.as_ref().map(|c| c.join(",")),
+            event.results,
+        ));
+    })));
+
+    engine.suggest("voronezh", 5, None, Some(&["RU"]));
+    engine.suggest::<&str>("unknowncity", 5, None, None);
+    engine.reverse::<&str>((51.6372, 39.1937), 2, None, None);
+    engine.suggest_places("voronezh", SuggestOptions::new(5), PlaceWeights::default());
 
-    let items = engine.suggest::<&str>("Beverley", 1, None, None);
-    tracing::info!("Items {items:#?}");
-    assert_eq!(items[0].name, "Beverley");
     assert_eq!(
-        items[0].admin2_division.as_ref().unwrap().name,
-        "East Riding of Yorkshire"
+        *events.lock().unwrap(),
+        vec![
+            (
+                "suggest",
+                Some("voronezh".to_string()),
+                Some("RU".to_string()),
+                1
+            ),
+            ("suggest", Some("unknowncity".to_string()), None, 0),
+            ("reverse", None, None, 2),
+            ("places", Some("voronezh".to_string()), None, 2),
+        ]
     );
 
-    let items = engine.suggest("Beverley", 1, None, Some(&["ru"]));
-    assert_eq!(items.len(), 0);
-
-    let items = engine.suggest("Beverley", 1, None, Some(&["gb"]));
-    assert_eq!(items.len(), 1);
+    engine.set_query_hook(None);
+    engine.suggest::<&str>("voronezh", 5, None, None);
+    assert_eq!(events.lock().unwrap().len(), 4);
 
     Ok(())
 }
 
 #[test_log::test]
-fn reverse() -> Result<(), Box<dyn Error>> {
-    let engine = get_engine(None, None, None, vec![])?;
-    let result = engine.reverse::<&str>((51.6372, 39.1937), 1, None, None);
-    assert!(result.is_some());
-    let items = result.unwrap();
-    assert_eq!(items.len(), 1);
-    assert_eq!(items[0].city.name, "Voronezh");
-    assert_eq!(items[0].city.country.as_ref().unwrap().name, "Russia");
+fn single_country() -> Result<(), Box<dyn Error>> {
+    let cities = std::fs::read_to_string("tests/misc/cities.txt")?
+        .lines()
+        .filter(|line| line.contains("\tRU\t"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let engine = Engine::new_from_files_content(SourceFileContentOptions {
+        cities,
+        countries: Some(std::fs::read_to_string("tests/misc/country-info.txt")?),
+        ..Default::default()
+    })?;
     assert_eq!(
-        items[0].city.admin_division.as_ref().unwrap().name,
-        "Voronezj"
+        engine.metadata.as_ref().unwrap().single_country.as_deref(),
+        Some("RU")
     );
 
-    let result = engine.reverse::<&str>((53.84587, -0.42332), 1, None, None);
-    assert!(result.is_some());
-    let items = result.unwrap();
-    assert_eq!(items.len(), 1);
-    assert_eq!(items[0].city.name, "Beverley");
     assert_eq!(
-        items[0].city.admin2_division.as_ref().unwrap().name,
-        "East Riding of Yorkshire"
+        engine.suggest("Voronezh", 1, None, Some(&["ru"]))[0].id,
+        472045
     );
+    assert!(engine
+        .suggest("Voronezh", 1, None, Some(&["gb"]))
+        .is_empty());
 
-    let result = engine.reverse((53.84587, -0.42332), 1, None, Some(&["ar"]));
-    assert_eq!(result.unwrap().len(), 0);
+    let items = engine
+        .reverse((51.6372, 39.1937), 2, None, Some(&["RU"]))
+        .unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].city.id, 472045);
+    assert!(engine
+        .reverse((51.6372, 39.1937), 2, None, Some(&["GB"]))
+        .unwrap()
+        .is_empty());
 
-    let result = engine.reverse((53.84587, -0.42332), 1, None, Some(&["gb"]));
-    assert_eq!(result.unwrap().len(), 1);
+    // flag is restored on load
+    let storage = storage::bincode::Storage::new();
+    let filepath = temp_dir().join("test-single-country.bincode");
+    storage.dump_to(&filepath, &engine)?;
+    let engine = storage.load_from(&filepath)?;
+    assert!(engine
+        .suggest("Voronezh", 1, None, Some(&["gb"]))
+        .is_empty());
 
     Ok(())
 }
@@ -109,6 +1927,40 @@ fn geoip2_lookup() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test_log::test]
+#[cfg(feature = "geoip2_support")]
+fn geoip2_lookup_cached() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec![])?;
+    engine.set_geoip2_cache(10);
+    engine.load_geoip2("tests/misc/GeoLite2-City-Test.mmdb")?;
+
+    let addr = IpAddr::from_str("81.2.69.142")?;
+    for _ in 0..2 {
+        assert_eq!(engine.geoip2_lookup(addr).unwrap().name, "London");
+    }
+    assert_eq!(engine.geoip2_cache_hits(), 1);
+    // not found ip is cached too
+    for _ in 0..2 {
+        assert!(engine
+            .geoip2_lookup(IpAddr::from_str("127.0.0.1")?)
+            .is_none());
+    }
+    assert_eq!(engine.geoip2_cache_hits(), 2);
+
+    // reload invalidates cache
+    engine.load_geoip2("tests/misc/GeoLite2-City-Test.mmdb")?;
+    assert_eq!(engine.geoip2_lookup(addr).unwrap().name, "London");
+    assert_eq!(engine.geoip2_cache_hits(), 2);
+
+    // database read apart from engine, e.g. in parallel with index load
+    let data = GeoIP2Data::read("tests/misc/GeoLite2-City-Test.mmdb")?;
+    engine.set_geoip2(data)?;
+    assert_eq!(engine.geoip2_lookup(addr).unwrap().name, "London");
+    assert!(GeoIP2Data::read("tests/misc/cities.txt").is_err());
+
+    Ok(())
+}
+
 #[test_log::test]
 fn json_build_dump_load() -> Result<(), Box<dyn Error>> {
     let filepath = temp_dir().join("test-engine.json");
@@ -179,6 +2031,55 @@ fn bincode_build_dump_load() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test_log::test]
+fn reproducible_dump() -> Result<(), Box<dyn Error>> {
+    let dump = |compress: bool| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut engine = get_engine(None, None, None, vec!["ru", "de", "ja"])?;
+        engine.make_reproducible(std::time::UNIX_EPOCH);
+        if compress {
+            engine.compress_names()?;
+        }
+        let mut buff = Vec::new();
+        storage::bincode::Storage::new().dump(&engine, &mut buff)?;
+        Ok(buff)
+    };
+
+    assert_eq!(dump(false)?, dump(false)?);
+    assert_eq!(dump(true)?, dump(true)?);
+
+    let mut json = Vec::new();
+    let mut engine = get_engine(None, None, None, vec![])?;
+    engine.make_reproducible(std::time::UNIX_EPOCH);
+    storage::json::Storage::new().dump(&engine, &mut json)?;
+    let metadata: serde_json::Value =
+        serde_json::from_slice(json.split(|b| *b == b'\n').next().unwrap())?;
+    assert_eq!(metadata["created_at"]["secs_since_epoch"], 0);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn verify_against() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec!["ru", "de"])?;
+    engine.compress_names()?;
+
+    let mut buff = Vec::new();
+    storage::bincode::Storage::new().dump(&engine, &mut buff)?;
+    let loaded = storage::bincode::Storage::new().load(&mut buff.as_slice())?;
+    loaded.verify_against(&engine)?;
+
+    let mut buff = Vec::new();
+    storage::json::Storage::new().dump(&engine, &mut buff)?;
+    let loaded = storage::json::Storage::new().load(&mut buff.as_slice())?;
+    loaded.verify_against(&engine)?;
+
+    // divergence is reported
+    let other = get_engine(Some("tests/misc/population-weight.txt"), None, None, vec![])?;
+    assert!(other.verify_against(&engine).is_err());
+
+    Ok(())
+}
+
 #[test_log::test]
 fn population_weight() -> Result<(), Box<dyn Error>> {
     let engine = get_engine(Some("tests/misc/population-weight.txt"), None, None, vec![])?;
@@ -250,3 +2151,569 @@ fn country_info() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test_log::test]
+fn country_info_with_capital() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec!["ru"])?;
+
+    let item = engine.country_info_with_capital("rs").unwrap();
+    assert_eq!(item.country.info.name, "Serbia");
+
+    let capital = item.capital.unwrap();
+    assert_eq!(capital.name, "Belgrade");
+    assert_eq!(capital.latitude, 44.80401);
+    assert_eq!(capital.longitude, 20.46513);
+    assert_eq!(
+        capital.names.as_ref().unwrap().get("ru").unwrap(),
+        "Белград"
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn shared_translations() -> Result<(), Box<dyn Error>> {
+    let filepath = temp_dir().join("test-engine-shared-translations.bincode");
+    let storage = storage::bincode::Storage::new();
+    let engine = get_engine(None, None, None, vec!["ru"])?;
+    storage.dump_to(&filepath, &engine)?;
+    let from_dump = storage.load_from(&filepath)?;
+
+    for engine in [&engine, &from_dump] {
+        let city = engine.get(&472045).unwrap();
+        assert_eq!(
+            engine.country_names(city).unwrap().get("ru").unwrap(),
+            "Россия"
+        );
+        assert!(engine
+            .admin1_names(city)
+            .unwrap()
+            .get("ru")
+            .unwrap()
+            .starts_with("Воронежская"));
+        assert!(engine.admin2_names(city).is_none());
+
+        let city = engine.get(&2655785).unwrap();
+        assert_eq!(
+            engine.admin2_names(city).unwrap().get("ru").unwrap(),
+            "Ист-Райдинг-оф-Йоркшир"
+        );
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn compressed_names() -> Result<(), Box<dyn Error>> {
+    let filepath = temp_dir().join("test-engine-compressed-names.bincode");
+    let storage = storage::bincode::Storage::new();
+    let mut engine = get_engine(None, None, None, vec!["ru"])?;
+    engine.compress_names()?;
+    storage.dump_to(&filepath, &engine)?;
+    let from_dump = storage.load_from(&filepath)?;
+
+    for engine in [&engine, &from_dump] {
+        let city = engine.get(&472045).unwrap();
+        assert!(city.names.is_none());
+        assert_eq!(
+            engine.city_names(city).unwrap().get("ru").unwrap(),
+            "Воронеж"
+        );
+        assert_eq!(engine.city_name(city, "ru").unwrap(), "Воронеж");
+        assert!(engine.city_name(city, "xx").is_none());
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn admin_divisions() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec!["ru"])?;
+
+    let item = engine.admin1("ru.86").unwrap();
+    assert_eq!(item.division.id, 472039);
+    assert!(item
+        .names
+        .unwrap()
+        .get("ru")
+        .unwrap()
+        .starts_with("Воронежская"));
+    assert_eq!(item.country.unwrap().info.iso, "RU");
+
+    let item = engine.admin2("GB.ENG.E1").unwrap();
+    assert_eq!(item.division.name, "East Riding of Yorkshire");
+    assert_eq!(item.country.unwrap().info.iso, "GB");
+
+    assert!(engine.admin1("XX.00").is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn random_city() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..10 {
+        let city = engine.random_city(&mut rng, true, Some(&["gb"])).unwrap();
+        assert_eq!(city.country.as_ref().unwrap().code, "GB");
+    }
+
+    // reproducible by seed
+    let pick = |seed| {
+        engine
+            .random_city::<_, &str>(&mut StdRng::seed_from_u64(seed), false, None)
+            .map(|city| city.id)
+    };
+    assert_eq!(pick(7), pick(7));
+
+    assert!(engine.random_city(&mut rng, false, Some(&["jp"])).is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn malformed_rows() -> Result<(), Box<dyn Error>> {
+    let mut cities = std::fs::read_to_string("tests/misc/cities.txt")?;
+    cities.push_str("1\tBroken\tBroken\t\tnot-a-latitude\t0\tP\tPPL\tRU\n");
+
+    let build = |max_malformed_rows| {
+        Engine::new_from_files_content(SourceFileContentOptions {
+            cities: cities.clone(),
+            max_malformed_rows,
+            ..Default::default()
+        })
+    };
+
+    let engine = build(None)?;
+    let malformed = &engine.metadata.as_ref().unwrap().malformed_rows;
+    assert_eq!(malformed.len(), 1);
+    assert_eq!(malformed["cities"].count, 1);
+    assert_eq!(malformed["cities"].samples.len(), 1);
+    assert!(engine.get(&1).is_none());
+
+    assert!(build(Some(1)).is_ok());
+    let error = build(Some(0)).err().unwrap();
+    assert!(error.to_string().contains("malformed cities rows: 1"));
+
+    Ok(())
+}
+
+#[test_log::test]
+fn storage_validation() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec!["ru"])?;
+    engine.compress_names()?;
+    engine.validate()?;
+
+    let storage = storage::json::Storage::new();
+    let mut buff = Vec::new();
+    storage.dump(&engine, &mut buff)?;
+    let storage = storage.with_validation(storage::Validation::Full);
+    storage.load(&mut buff.as_slice())?;
+
+    // capital refers to unknown city
+    let content = String::from_utf8(buff)?;
+    let (metadata, payload) = content.split_once('\n').unwrap();
+    let mut payload: serde_json::Value = serde_json::from_str(payload)?;
+    payload["capitals"]["RU"] = serde_json::json!(1);
+    let content = format!("{metadata}\n{payload}");
+
+    let result = storage.load(&mut content.as_bytes());
+    assert!(result
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("Capital of RU refers to unknown city 1"));
+
+    // trusted by default
+    assert!(storage::json::Storage::new()
+        .load(&mut content.as_bytes())
+        .is_ok());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn storage_normalization_version() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec![])?;
+    engine.metadata.as_mut().unwrap().normalization_version = NORMALIZATION_VERSION + 1;
+
+    let mut buff = Vec::new();
+    storage::bincode::Storage::new().dump(&engine, &mut buff)?;
+    let err = storage::bincode::Storage::new()
+        .load(&mut buff.as_slice())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("rebuild index"), "{err}");
+
+    let mut buff = Vec::new();
+    storage::json::Storage::new().dump(&engine, &mut buff)?;
+    assert!(storage::json::Storage::new()
+        .load(&mut buff.as_slice())
+        .is_err());
+
+    // index built with other features, e.g. without kana folding of `cjk`
+    let mut engine = get_engine(None, None, None, vec![])?;
+    let metadata = engine.metadata.as_mut().unwrap();
+    assert_eq!(metadata.normalization_features, NORMALIZATION_FEATURES);
+    metadata.normalization_features.push("unknown".to_owned());
+
+    let mut buff = Vec::new();
+    storage::bincode::Storage::new().dump(&engine, &mut buff)?;
+    let err = storage::bincode::Storage::new()
+        .load(&mut buff.as_slice())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("features"), "{err}");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn storage_format_version() -> Result<(), Box<dyn Error>> {
+    let storage = storage::bincode::Storage::new();
+
+    // index dumped by geosuggest 0.6.5, before format version header
+    let filepath = std::path::Path::new("tests/misc/index-0.6.5.bincode");
+    let err = storage.read_metadata(filepath).err().unwrap();
+    assert!(err.to_string().contains("format version 0"), "{err}");
+    let err = storage.load_from(filepath).err().unwrap();
+    assert!(err.to_string().contains("rebuild index"), "{err}");
+
+    let engine = get_engine(None, None, None, vec![])?;
+    let mut buff = Vec::new();
+    storage.dump(&engine, &mut buff)?;
+    assert!(storage.load(&mut buff.as_slice()).is_ok());
+
+    // header of other format version
+    buff[4..8].copy_from_slice(&(INDEX_FORMAT_VERSION + 1).to_be_bytes());
+    let err = storage.load(&mut buff.as_slice()).err().unwrap();
+    assert!(err.to_string().contains("rebuild index"), "{err}");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn slim() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec!["ru"])?;
+    let expected = engine
+        .suggest::<&str>("Воронеж", 5, None, None)
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+
+    let storage = storage::bincode::Storage::new();
+    let full_path = temp_dir().join("test-engine-full.bincode");
+    let slim_path = temp_dir().join("test-engine-slim.bincode");
+    storage.dump_to(&full_path, &engine)?;
+    storage.dump_to(&slim_path, &engine.into_slim())?;
+    assert!(std::fs::metadata(&slim_path)?.len() < std::fs::metadata(&full_path)?.len());
+
+    let engine = storage.load_from(&slim_path)?;
+    assert_eq!(
+        engine
+            .suggest::<&str>("Воронеж", 5, None, None)
+            .iter()
+            .map(|c| c.id)
+            .collect::<Vec<_>>(),
+        expected
+    );
+    let items = engine.reverse::<&str>((51.6372, 39.1937), 1, None, Some(&["RU"]));
+    assert_eq!(items.unwrap()[0].city.id, 472045);
+
+    let city = engine.get(&472045).unwrap();
+    assert!(engine.city_names(city).is_none());
+    assert!(engine.admin1_names(city).is_none());
+    let country = engine.country_info("RU").unwrap();
+    assert!(country.names.is_none());
+    assert!(country.info.currency_code.is_empty());
+    assert_eq!(country.info.name, "Russia");
+    assert_eq!(
+        engine.metadata.as_ref().unwrap().extra.get("profile"),
+        Some(&"slim".to_owned())
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn with_languages() -> Result<(), Box<dyn Error>> {
+    let full = get_engine(None, None, None, vec!["ru", "de"])?;
+    let expected = get_engine(None, None, None, vec!["de"])?;
+    let variant = full.with_languages(&["de"])?;
+
+    for id in [472045, 2643743, 2655785] {
+        let city = variant.get(&id).unwrap();
+        let expected_city = expected.get(&id).unwrap();
+        assert_eq!(city.names, expected_city.names);
+        assert_eq!(
+            variant.country_names(city),
+            expected.country_names(expected_city)
+        );
+        assert_eq!(
+            variant.admin1_names(city),
+            expected.admin1_names(expected_city)
+        );
+    }
+    assert!(variant
+        .get(&472045)
+        .unwrap()
+        .names
+        .as_ref()
+        .unwrap()
+        .get("ru")
+        .is_none());
+
+    // search and reverse are untouched
+    assert_eq!(
+        variant.suggest::<&str>("voronezh", 1, None, None)[0].id,
+        472045
+    );
+    assert_eq!(
+        variant
+            .reverse::<&str>((51.6372, 39.1937), 1, None, None)
+            .unwrap()[0]
+            .city
+            .id,
+        472045
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn country_bbox() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+
+    // London and Beverley
+    let bbox = engine.country_info("GB").unwrap().bbox.unwrap();
+    assert_eq!(bbox.min_lat, 51.50853);
+    assert_eq!(bbox.max_lat, 53.84587);
+    assert_eq!(bbox.min_lng, -0.42332);
+    assert_eq!(bbox.max_lng, -0.12574);
+    assert!(bbox.contains(52.0, -0.2));
+
+    // country without indexed cities
+    assert!(engine.country_info("JP").unwrap().bbox.is_none());
+    let result = engine.reverse((51.6372, 39.1937), 1, None, Some(&["jp"]));
+    assert_eq!(result.unwrap().len(), 0);
+
+    // nearest city of requested country far away from the point (by great-circle, Beverley
+    // is to the north of London)
+    let result = engine.reverse((51.6372, 39.1937), 1, None, Some(&["gb"]));
+    assert_eq!(result.unwrap()[0].city.name, "Beverley");
+
+    Ok(())
+}
+
+#[test_log::test]
+fn memory_usage() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec!["ru"])?;
+
+    let usage = engine.memory_usage();
+    assert!(usage.cities > 0);
+    assert!(usage.entries > 0);
+    assert!(usage.tree > 0);
+    assert!(usage.auxiliary > 0);
+    assert_eq!(
+        usage.total(),
+        usage.cities + usage.entries + usage.tree + usage.auxiliary
+    );
+    assert!(engine.metadata.as_ref().unwrap().load_duration.is_none());
+
+    // translations moved out of records are still counted
+    engine.compress_names()?;
+    assert!(engine.memory_usage().cities > 0);
+
+    // load duration is recorded on load
+    let storage = storage::bincode::Storage::new();
+    let filepath = temp_dir().join("test-memory-usage.bincode");
+    storage.dump_to(&filepath, &engine)?;
+    let engine = storage.load_from(&filepath)?;
+    assert!(engine.metadata.as_ref().unwrap().load_duration.is_some());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn within_radius() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(None, None, None, vec![])?;
+    let loc = (51.6372, 39.1937);
+    let ids = |items: Vec<geosuggest_core::ReverseItem>| {
+        items.iter().map(|item| item.city.id).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        ids(engine.within_radius(loc, 10_000.0, None, RadiusFilters::new())),
+        vec![472045]
+    );
+
+    // nearest first, distance in meters is the score
+    let items = engine.within_radius(loc, 500_000.0, None, RadiusFilters::new());
+    assert_eq!(ids(items), vec![472045, 524901]);
+    let items = engine.within_radius(loc, 500_000.0, None, RadiusFilters::new());
+    assert!((460_000.0..480_000.0).contains(&items[1].distance_m));
+    assert_eq!(items[1].score, items[1].distance_m);
+
+    assert_eq!(
+        ids(engine.within_radius(loc, 500_000.0, Some(1), RadiusFilters::new())),
+        vec![472045]
+    );
+    assert_eq!(
+        ids(engine.within_radius(
+            loc,
+            500_000.0,
+            None,
+            RadiusFilters::new().min_population(1_000_000)
+        )),
+        vec![524901]
+    );
+    assert_eq!(
+        ids(engine.within_radius(
+            loc,
+            3_000_000.0,
+            None,
+            RadiusFilters::new().countries(&["gb"])
+        )),
+        vec![2655785, 2643743]
+    );
+    assert_eq!(
+        ids(engine.within_radius(
+            loc,
+            3_000_000.0,
+            None,
+            RadiusFilters::new()
+                .exclude_countries(&["RU", "GB"])
+                .blocked_ids(&[])
+        )),
+        vec![792680]
+    );
+    assert!(engine
+        .within_radius(loc, -1.0, None, RadiusFilters::new())
+        .is_empty());
+
+    // longitude degrees shrink near pole, Belgrade is out of radius
+    assert_eq!(
+        ids(engine.within_radius((89.0, 0.0), 4_500_000.0, None, RadiusFilters::new())),
+        vec![524901, 2655785, 2643743, 472045]
+    );
+
+    Ok(())
+}
+
+#[test_log::test]
+fn duplicate_ids() -> Result<(), Box<dyn Error>> {
+    let row = std::fs::read_to_string("tests/misc/cities.txt")?
+        .lines()
+        .find(|line| line.starts_with("472045\t"))
+        .unwrap()
+        .to_owned();
+    let variant = |name: &str, date: &str| {
+        let mut fields = row.split('\t').map(String::from).collect::<Vec<_>>();
+        fields[1] = name.to_owned();
+        *fields.last_mut().unwrap() = date.to_owned();
+        fields.join("\t")
+    };
+    // newer row comes first, e.g. delta update prepended to the dump
+    let cities = [
+        variant("Voronezh Newer", "2024-01-01"),
+        variant("Voronezh First", "2019-09-04"),
+        variant("Voronezh Last", "2019-09-04"),
+    ]
+    .join("\n");
+
+    let build = |duplicates| {
+        Engine::new_from_files_content(SourceFileContentOptions {
+            cities: cities.clone(),
+            duplicates,
+            ..Default::default()
+        })
+    };
+
+    for (duplicates, name) in [
+        (DuplicatePolicy::Newest, "Voronezh Newer"),
+        (DuplicatePolicy::First, "Voronezh Newer"),
+        (DuplicatePolicy::Last, "Voronezh Last"),
+    ] {
+        let engine = build(duplicates)?;
+        assert_eq!(engine.get(&472045).unwrap().name, name, "{duplicates:?}");
+        assert_eq!(engine.cities().len(), 1);
+        assert_eq!(engine.metadata.as_ref().unwrap().duplicate_rows, 2);
+        // no duplicate entries of dropped rows
+        assert_eq!(
+            engine
+                .suggest::<&str>("voronezh", 10, None, None)
+                .iter()
+                .map(|city| city.id)
+                .collect::<Vec<_>>(),
+            vec![472045]
+        );
+    }
+
+    // the later row wins on the same modification date
+    let cities = [
+        variant("Voronezh First", "2019-09-04"),
+        variant("Voronezh Last", "2019-09-04"),
+    ]
+    .join("\n");
+    let engine = Engine::new_from_files_content(SourceFileContentOptions {
+        cities,
+        duplicates: DuplicatePolicy::Newest,
+        ..Default::default()
+    })?;
+    assert_eq!(engine.get(&472045).unwrap().name, "Voronezh Last");
+
+    assert_eq!("LAST".parse::<DuplicatePolicy>(), Ok(DuplicatePolicy::Last));
+    assert!("latest".parse::<DuplicatePolicy>().is_err());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn reverse_batch() -> Result<(), Box<dyn Error>> {
+    let mut engine = get_engine(None, None, None, vec![])?;
+    let locations = [(51.6372, 39.1937), (51.5, 0.0), (55.7, 37.6), (44.8, 20.4)];
+    let ids = |items: &Option<Vec<geosuggest_core::ReverseItem>>| {
+        items
+            .iter()
+            .flatten()
+            .map(|item| item.city.id)
+            .collect::<Vec<_>>()
+    };
+
+    // same as reverse of each location, in order of locations
+    let options = || ReverseOptions::new(2).k(0.000000005);
+    let batch = engine.reverse_batch(&locations, options());
+    assert_eq!(batch.len(), locations.len());
+    for (loc, items) in locations.iter().zip(batch.iter()) {
+        assert_eq!(ids(items), ids(&engine.reverse_with(*loc, options())));
+    }
+    assert_eq!(ids(&batch[0])[0], 472045);
+
+    // filters are applied to each location
+    let batch = engine.reverse_batch(&locations, ReverseOptions::new(1).countries(&["gb"]));
+    assert_eq!(
+        batch.iter().map(ids).collect::<Vec<_>>(),
+        vec![vec![2655785], vec![2643743], vec![2655785], vec![2643743]]
+    );
+    let batch = engine.reverse_batch(&locations, ReverseOptions::new(1).countries(&["XX"]));
+    assert!(batch.iter().all(|items| ids(items).is_empty()));
+    assert!(engine
+        .reverse_batch(&locations, ReverseOptions::new(0))
+        .iter()
+        .all(Option::is_none));
+
+    // one event per batch
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    engine.set_query_hook(Some(Arc::new(move |event: &QueryEvent| {
+        events_clone
+            .lock()
+            .unwrap()
+            .push((event.kind, event.results));
+    })));
+    engine.reverse_batch(&locations, ReverseOptions::new(2));
+    assert_eq!(*events.lock().unwrap(), vec![("reverse_batch", 8)]);
+
+    Ok(())
+}
@@ -1,11 +1,19 @@
 #![doc = include_str!("../README.md")]
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Cursor, Read};
 
-use geosuggest_core::{Engine, EngineMetadata, EngineSourceMetadata, SourceFileContentOptions};
+use geosuggest_core::{
+    CaseFolding, DuplicatePolicy, Engine, EngineMetadata, EngineSourceMetadata, EntryFilter,
+    SourceFileContentOptions,
+};
 use serde::Serialize;
 
+#[cfg(feature = "database")]
+pub mod database;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
 #[derive(Serialize, Clone)]
 pub struct SourceItem<'a> {
     pub url: &'a str,
@@ -21,6 +29,14 @@ pub struct IndexUpdaterSettings<'a> {
     pub admin1_codes_url: Option<&'a str>,
     pub admin2_codes_url: Option<&'a str>,
     pub filter_languages: Vec<&'a str>,
+    pub case_folding: CaseFolding,
+    /// Fail build when malformed rows of any source exceed this count
+    pub max_malformed_rows: Option<usize>,
+    /// Cleanup of alternate names before indexing
+    pub entry_filter: EntryFilter,
+    /// Resolution of cities rows sharing geonameid
+    pub duplicates: DuplicatePolicy,
+    pub checks: IndexChecks<'a>,
 }
 
 impl Default for IndexUpdaterSettings<'_> {
@@ -41,11 +57,139 @@ impl Default for IndexUpdaterSettings<'_> {
             ),
             admin2_codes_url: Some("https://download.geonames.org/export/dump/admin2Codes.txt"),
             filter_languages: Vec::new(),
+            case_folding: CaseFolding::default(),
+            max_malformed_rows: None,
+            entry_filter: EntryFilter::default(),
+            duplicates: DuplicatePolicy::default(),
+            checks: IndexChecks::default(),
             // max_payload_size: 200 * 1024 * 1024,
         }
     }
 }
 
+/// Sanity checks of freshly built index, by default nothing is checked
+#[derive(Debug, Default, Serialize, Clone)]
+pub struct IndexChecks<'a> {
+    /// Minimum count of indexed cities
+    pub min_cities: usize,
+    /// Countries which should have indexed cities
+    pub required_countries: Vec<&'a str>,
+    /// Capitals of required countries should be found
+    pub check_capitals: bool,
+}
+
+impl IndexChecks<'_> {
+    /// Returns error with all violated checks
+    pub fn check(&self, engine: &Engine) -> Result<()> {
+        let mut errors = Vec::new();
+
+        let cities = engine.cities().len();
+        if cities < self.min_cities {
+            errors.push(format!(
+                "cities count {cities} is less than {}",
+                self.min_cities
+            ));
+        }
+
+        for code in self.required_countries.iter().map(|code| code.trim()) {
+            if !engine.cities().any(|c| {
+                c.country
+                    .as_ref()
+                    .is_some_and(|c| c.code.eq_ignore_ascii_case(code))
+            }) {
+                errors.push(format!("country {code} has no cities"));
+                continue;
+            }
+            if self.check_capitals && engine.capital(code).is_none() {
+                errors.push(format!("capital of country {code} not found"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Index sanity checks failed: {}",
+                errors.join("; ")
+            ))
+        }
+    }
+}
+
+/// Changes of freshly built index versus the previous one
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateReport {
+    /// Sources with changed ETAG: source -> (previous, new)
+    pub etag_changes: HashMap<String, (Option<String>, Option<String>)>,
+    /// Cities count of the previous index
+    pub cities_before: usize,
+    /// Cities count of the new index
+    pub cities_after: usize,
+    /// Cities present only in the new index
+    pub added_cities: usize,
+    /// Cities present only in the previous index
+    pub removed_cities: usize,
+}
+
+impl UpdateReport {
+    pub fn new(previous: Option<&Engine>, current: &Engine) -> Self {
+        let etag = |engine: Option<&Engine>| {
+            engine
+                .and_then(|e| e.metadata.as_ref())
+                .map(|m| m.source.etag.clone())
+                .unwrap_or_default()
+        };
+        let previous_etag = etag(previous);
+        let current_etag = etag(Some(current));
+
+        let etag_changes = previous_etag
+            .keys()
+            .chain(current_etag.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|source| {
+                let before = previous_etag.get(source);
+                let after = current_etag.get(source);
+                (before != after).then(|| (source.to_owned(), (before.cloned(), after.cloned())))
+            })
+            .collect();
+
+        let previous_ids = previous
+            .map(|e| e.cities().map(|c| c.id).collect::<HashSet<_>>())
+            .unwrap_or_default();
+        let current_ids = current.cities().map(|c| c.id).collect::<HashSet<_>>();
+
+        UpdateReport {
+            etag_changes,
+            cities_before: previous_ids.len(),
+            cities_after: current_ids.len(),
+            added_cities: current_ids.difference(&previous_ids).count(),
+            removed_cities: previous_ids.difference(&current_ids).count(),
+        }
+    }
+}
+
+impl std::fmt::Display for UpdateReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cities: {} -> {} (+{} -{})",
+            self.cities_before, self.cities_after, self.added_cities, self.removed_cities
+        )?;
+        let mut sources = self.etag_changes.iter().collect::<Vec<_>>();
+        sources.sort_unstable_by_key(|(source, _)| source.as_str());
+        for (source, (before, after)) in sources {
+            write!(
+                f,
+                "\n{source}: {} -> {}",
+                before.as_deref().unwrap_or("-"),
+                after.as_deref().unwrap_or("-")
+            )?;
+        }
+        Ok(())
+    }
+}
+
 pub struct IndexUpdater<'a> {
     http_client: reqwest::Client,
     settings: IndexUpdaterSettings<'a>,
@@ -85,7 +229,7 @@ impl<'a> IndexUpdater<'a> {
             results.push("admin1_codes");
         }
         let responses = futures::future::join_all(requests).await;
-        let results: HashMap<_, _> = results.into_iter().zip(responses.into_iter()).collect();
+        let results: HashMap<_, _> = results.into_iter().zip(responses).collect();
 
         for (entry, etag) in results {
             let current_etag = metadata
@@ -143,10 +287,12 @@ impl<'a> IndexUpdater<'a> {
             tracing::info!("Unzip {filename}");
             let cursor = Cursor::new(content);
             let mut archive = zip::read::ZipArchive::new(cursor)?;
-            let file = archive
+            let mut file = archive
                 .by_name(filename)
                 .map_err(|e| anyhow::anyhow!("On get file {filename} from archive: {e}"))?;
-            file.bytes().collect::<std::io::Result<Vec<_>>>()?
+            let mut content = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut content)?;
+            content
         } else {
             content
         };
@@ -154,6 +300,41 @@ impl<'a> IndexUpdater<'a> {
         Ok((etag, content))
     }
 
+    /// Build index and report changes versus the previous one.
+    pub async fn build_with_report(
+        self,
+        previous: Option<&Engine>,
+    ) -> Result<(Engine, UpdateReport)> {
+        let engine = self.build().await?;
+        let report = UpdateReport::new(previous, &engine);
+
+        #[cfg(feature = "tracing")]
+        tracing::info!("Update report: {report:?}");
+
+        Ok((engine, report))
+    }
+
+    /// Build several indexes with different `filter_languages` sets.
+    ///
+    /// Sources are downloaded and parsed once with union of all variants languages.
+    pub async fn build_variants(mut self, variants: &[Vec<&'a str>]) -> Result<Vec<Engine>> {
+        let mut languages = variants.iter().flatten().copied().collect::<Vec<_>>();
+        languages.sort_unstable();
+        languages.dedup();
+        self.settings.filter_languages = languages;
+
+        let engine = self.build().await?;
+
+        variants
+            .iter()
+            .map(|languages| {
+                engine
+                    .with_languages(languages)
+                    .map_err(|e| anyhow::anyhow!("Failed to build index variant: {e}"))
+            })
+            .collect()
+    }
+
     pub async fn build(self) -> Result<Engine> {
         let mut requests = vec![self.fetch(
             self.settings.cities.url,
@@ -177,7 +358,7 @@ impl<'a> IndexUpdater<'a> {
             results.push("admin2_codes");
         }
         let responses = futures::future::join_all(requests).await;
-        let mut results: HashMap<_, _> = results.into_iter().zip(responses.into_iter()).collect();
+        let mut results: HashMap<_, _> = results.into_iter().zip(responses).collect();
 
         let etag = results
             .iter()
@@ -219,9 +400,16 @@ impl<'a> IndexUpdater<'a> {
                 None
             },
             filter_languages: self.settings.filter_languages.clone(),
+            case_folding: self.settings.case_folding,
+            max_malformed_rows: self.settings.max_malformed_rows,
+            entry_filter: self.settings.entry_filter.clone(),
+            duplicates: self.settings.duplicates,
         })
         .map_err(|e| anyhow::anyhow!("Failed to build index: {e}"))?;
 
+        // keep metadata collected on build
+        let metadata = engine.metadata.take().unwrap_or_default();
+
         engine.metadata = Some(EngineMetadata {
             source: EngineSourceMetadata {
                 cities: self.settings.cities.url.to_owned(),
@@ -237,9 +425,11 @@ impl<'a> IndexUpdater<'a> {
                     .collect::<Vec<_>>(),
                 etag,
             },
-            ..Default::default()
+            ..metadata
         });
 
+        self.settings.checks.check(&engine)?;
+
         Ok(engine)
     }
 }
@@ -0,0 +1,163 @@
+//! Export index to SQLite database
+//!
+//! Tables:
+//!  - `countries` - countries info with capital and bounding box of indexed cities
+//!  - `admin_divisions` - admin divisions (`level` 1 or 2) of indexed cities
+//!  - `cities` - indexed cities
+//!  - `names` - translations of cities, countries and admin divisions by geonameid
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::Path;
+
+use geosuggest_core::{AdminDivision, Engine};
+
+const SCHEMA: &str = "
+CREATE TABLE countries (
+    id INTEGER PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    iso3 TEXT NOT NULL,
+    name TEXT NOT NULL,
+    capital_id INTEGER,
+    population INTEGER NOT NULL,
+    continent TEXT NOT NULL,
+    currency_code TEXT NOT NULL,
+    languages TEXT NOT NULL,
+    min_lat REAL,
+    min_lng REAL,
+    max_lat REAL,
+    max_lng REAL
+);
+
+CREATE TABLE admin_divisions (
+    id INTEGER PRIMARY KEY,
+    code TEXT NOT NULL,
+    level INTEGER NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE cities (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    country_id INTEGER REFERENCES countries (id),
+    admin1_id INTEGER REFERENCES admin_divisions (id),
+    admin2_id INTEGER REFERENCES admin_divisions (id),
+    timezone TEXT NOT NULL,
+    population INTEGER NOT NULL
+);
+
+CREATE TABLE names (
+    id INTEGER NOT NULL,
+    lang TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (id, lang)
+);
+
+CREATE INDEX admin_divisions_code ON admin_divisions (code);
+CREATE INDEX cities_country_id ON cities (country_id);
+";
+
+/// Export index into new SQLite database file (existed file is replaced)
+pub fn export_sqlite<P: AsRef<Path>>(engine: &Engine, path: P) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let now = std::time::Instant::now();
+
+    if path.as_ref().exists() {
+        std::fs::remove_file(&path)?;
+    }
+
+    let mut conn = Connection::open(&path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert_name =
+            tx.prepare("INSERT OR IGNORE INTO names (id, lang, name) VALUES (?1, ?2, ?3)")?;
+        let mut insert_names = |id: u32, names: Option<&HashMap<String, String>>| {
+            for (lang, name) in names.into_iter().flatten() {
+                insert_name.execute(params![id, lang, name])?;
+            }
+            Ok::<_, rusqlite::Error>(())
+        };
+
+        let mut insert_country = tx.prepare(
+            "INSERT INTO countries (
+                id, code, iso3, name, capital_id, population, continent,
+                currency_code, languages, min_lat, min_lng, max_lat, max_lng
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        for country in engine.countries() {
+            let info = &country.info;
+            let bbox = country.bbox.as_ref();
+            insert_country.execute(params![
+                info.geonameid,
+                info.iso,
+                info.iso3,
+                info.name,
+                engine.capital(&info.iso).map(|c| c.id),
+                info.population,
+                info.continent,
+                info.currency_code,
+                info.languages,
+                bbox.map(|b| b.min_lat),
+                bbox.map(|b| b.min_lng),
+                bbox.map(|b| b.max_lat),
+                bbox.map(|b| b.max_lng),
+            ])?;
+            insert_names(info.geonameid, country.names.as_ref())?;
+        }
+
+        let mut insert_division = tx.prepare(
+            "INSERT OR IGNORE INTO admin_divisions (id, code, level, name) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut insert_city = tx.prepare(
+            "INSERT INTO cities (
+                id, name, latitude, longitude, country_id, admin1_id, admin2_id, timezone, population
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?;
+        for city in engine.cities() {
+            let divisions: [(u8, Option<&AdminDivision>, _); 2] = [
+                (1, city.admin_division.as_ref(), engine.admin1_names(city)),
+                (2, city.admin2_division.as_ref(), engine.admin2_names(city)),
+            ];
+            for (level, division, names) in divisions {
+                if let Some(division) = division {
+                    insert_division.execute(params![
+                        division.id,
+                        division.code,
+                        level,
+                        division.name
+                    ])?;
+                    insert_names(division.id, names)?;
+                }
+            }
+
+            insert_city.execute(params![
+                city.id,
+                city.name,
+                city.latitude,
+                city.longitude,
+                city.country.as_ref().map(|c| c.id),
+                city.admin_division.as_ref().map(|d| d.id),
+                city.admin2_division.as_ref().map(|d| d.id),
+                city.timezone,
+                city.population,
+            ])?;
+            if let Some(names) = engine.city_names(city) {
+                insert_names(city.id, Some(&names))?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        "Exported index to sqlite {:?} took {}ms",
+        path.as_ref(),
+        now.elapsed().as_millis()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use geosuggest_core::{Engine, EngineMetadata, SourceFileContentOptions, SourceFileOptions};
+use geosuggest_utils::{IndexChecks, UpdateReport};
+use std::error::Error;
+
+fn get_engine(filter_languages: Vec<&str>) -> Result<Engine, Box<dyn Error>> {
+    Engine::new_from_files(SourceFileOptions {
+        cities: "../geosuggest-core/tests/misc/cities.txt",
+        names: Some("../geosuggest-core/tests/misc/names.txt"),
+        countries: Some("../geosuggest-core/tests/misc/country-info.txt"),
+        filter_languages,
+        admin1_codes: Some("../geosuggest-core/tests/misc/admin1-codes.txt"),
+        admin2_codes: Some("../geosuggest-core/tests/misc/admin2-codes.txt"),
+        ..Default::default()
+    })
+}
+
+#[test_log::test]
+fn update_report() -> Result<(), Box<dyn Error>> {
+    let cities = std::fs::read_to_string("../geosuggest-core/tests/misc/cities.txt")?;
+    let build = |cities: String, etag: &[(&str, &str)]| -> Result<Engine, Box<dyn Error>> {
+        let mut engine = Engine::new_from_files_content(SourceFileContentOptions {
+            cities,
+            ..Default::default()
+        })?;
+        let mut metadata = EngineMetadata::default();
+        for (source, value) in etag {
+            metadata
+                .source
+                .etag
+                .insert(source.to_string(), value.to_string());
+        }
+        engine.metadata = Some(metadata);
+        Ok(engine)
+    };
+
+    // Voronezh removed, the rest cities are kept
+    let previous = build(cities.clone(), &[("cities", "1"), ("names", "1")])?;
+    let current = build(
+        cities.lines().skip(1).collect::<Vec<_>>().join("\n"),
+        &[("cities", "2"), ("names", "1")],
+    )?;
+
+    let report = UpdateReport::new(Some(&previous), &current);
+    assert_eq!(report.cities_before, previous.cities().len());
+    assert_eq!(report.cities_after, previous.cities().len() - 1);
+    assert_eq!(report.added_cities, 0);
+    assert_eq!(report.removed_cities, 1);
+    assert_eq!(report.etag_changes.len(), 1);
+    assert_eq!(
+        report.etag_changes.get("cities").unwrap(),
+        &(Some("1".to_owned()), Some("2".to_owned()))
+    );
+
+    assert_eq!(
+        report.to_string(),
+        format!(
+            "cities: {} -> {} (+0 -1)\ncities: 1 -> 2",
+            previous.cities().len(),
+            current.cities().len()
+        )
+    );
+
+    // first build
+    let report = UpdateReport::new(None, &current);
+    assert_eq!(report.cities_before, 0);
+    assert_eq!(report.added_cities, current.cities().len());
+
+    Ok(())
+}
+
+#[test_log::test]
+fn index_checks() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(vec![])?;
+
+    IndexChecks::default().check(&engine)?;
+
+    let checks = IndexChecks {
+        min_cities: engine.cities().len(),
+        required_countries: vec!["RU", "GB"],
+        check_capitals: true,
+    };
+    checks.check(&engine)?;
+
+    // codes as split from `--require-countries "ru, gb"`
+    IndexChecks {
+        required_countries: vec!["ru", " gb"],
+        ..checks.clone()
+    }
+    .check(&engine)?;
+
+    let result = IndexChecks {
+        min_cities: engine.cities().len() + 1,
+        ..checks.clone()
+    }
+    .check(&engine);
+    assert!(result.is_err());
+
+    let result = IndexChecks {
+        required_countries: vec!["US"],
+        ..checks
+    }
+    .check(&engine);
+    assert!(result.unwrap_err().to_string().contains("country US"));
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "sqlite")]
+fn export_sqlite() -> Result<(), Box<dyn Error>> {
+    let engine = get_engine(vec!["ru"])?;
+
+    let path = std::env::temp_dir().join("test-geosuggest-index.sqlite");
+    geosuggest_utils::sqlite::export_sqlite(&engine, &path)?;
+
+    let conn = rusqlite::Connection::open(&path)?;
+    let cities: usize = conn.query_row("SELECT count(*) FROM cities", [], |r| r.get(0))?;
+    assert_eq!(cities, engine.cities().len());
+
+    let (name, country): (String, String) = conn.query_row(
+        "SELECT n.name, c.code FROM cities AS t
+            JOIN names AS n ON n.id = t.id AND n.lang = 'ru'
+            JOIN countries AS c ON c.id = t.country_id
+            WHERE t.id = 472045",
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    assert_eq!(name, "Воронеж");
+    assert_eq!(country, "RU");
+
+    let capital: u32 = conn.query_row(
+        "SELECT capital_id FROM countries WHERE code = 'RU'",
+        [],
+        |r| r.get(0),
+    )?;
+    assert_eq!(capital, engine.capital("RU").unwrap().id);
+
+    Ok(())
+}
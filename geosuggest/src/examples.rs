@@ -0,0 +1,186 @@
+//! Examples of openapi3 responses rendered from the misc dataset (trimmed subset of
+//! `geosuggest-core` test dataset: all its cities and the countries, admin codes and names
+//! rows used by them, see `misc` directory of the crate) at spec-render time,
+//! so Swagger UI and generated SDK docs show realistic payloads.
+
+use std::error::Error;
+
+use geosuggest_core::{
+    Engine, PlaceWeights, ReverseOptions, SourceFileContentOptions, SuggestOptions,
+};
+use oaph::OpenApiPlaceHolder;
+use serde::Serialize;
+
+use super::{
+    AutocompleteResult, CityResultItem, GetCityResult, PlaceResultItem, PlacesResult,
+    ResolveResult, ResolvedBy, ReverseResult, ReverseResultItem, SuggestResult, Truncation,
+    DEFAULT_K,
+};
+
+const CITIES: &str = include_str!("../misc/cities.txt");
+const NAMES: &str = include_str!("../misc/names.txt");
+const COUNTRIES: &str = include_str!("../misc/country-info.txt");
+const ADMIN1_CODES: &str = include_str!("../misc/admin1-codes.txt");
+const ADMIN2_CODES: &str = include_str!("../misc/admin2-codes.txt");
+
+/// Voronezh
+const CITY_ID: u32 = 472045;
+const PATTERN: &str = "voronezh";
+const LANG: &str = "ru";
+const LOCATION: (f32, f32) = (51.6372, 39.1937);
+
+/// Engine of the misc test dataset (embedded into binary)
+pub fn engine() -> Result<Engine, Box<dyn Error>> {
+    Engine::new_from_files_content(SourceFileContentOptions {
+        cities: CITIES.to_owned(),
+        names: Some(NAMES.to_owned()),
+        countries: Some(COUNTRIES.to_owned()),
+        admin1_codes: Some(ADMIN1_CODES.to_owned()),
+        admin2_codes: Some(ADMIN2_CODES.to_owned()),
+        filter_languages: vec![LANG],
+        ..Default::default()
+    })
+}
+
+/// Substitute examples placeholders of openapi3 template
+pub fn substitute(aoph: OpenApiPlaceHolder) -> Result<OpenApiPlaceHolder, Box<dyn Error>> {
+    let engine = engine()?;
+    Ok(examples(&engine)?
+        .into_iter()
+        .fold(aoph, |aoph, (name, examples)| {
+            aoph.substitute(format!("{name}Examples"), examples)
+        }))
+}
+
+/// Media type `examples` objects (single line JSON, valid YAML flow mapping) by result schema name
+pub fn examples(engine: &Engine) -> Result<Vec<(&'static str, String)>, Box<dyn Error>> {
+    let city = engine.get(&CITY_ID).ok_or("Example city is missing")?;
+
+    let get = GetCityResult {
+        city: Some(CityResultItem::from_city(engine, city, Some(LANG))),
+        time: 0,
+    };
+
+    let suggest = SuggestResult {
+        items: engine
+            .suggest_with_scores(PATTERN, SuggestOptions::<&str>::new(1))
+            .into_iter()
+            .map(|item| {
+                CityResultItem::from_city(engine, item.city, None)
+                    .with_highlight(engine, PATTERN)
+                    .with_matched(engine, item.matched, PATTERN)
+            })
+            .collect(),
+        candidates: None,
+        timing: None,
+        truncation: Truncation::default(),
+        time: 0,
+    };
+
+    let autocomplete = AutocompleteResult {
+        items: engine
+            .suggest_with(PATTERN, SuggestOptions::<&str>::new(1))
+            .into_iter()
+            .map(|item| CityResultItem::from_city(engine, item, None).into())
+            .collect(),
+        truncation: Truncation::default(),
+        time: 0,
+    };
+
+    let places = PlacesResult {
+        items: engine
+            .suggest_places(
+                PATTERN,
+                SuggestOptions::<&str>::new(2),
+                PlaceWeights::default(),
+            )
+            .into_iter()
+            .filter_map(|item| PlaceResultItem::from_place(engine, item, None))
+            .collect(),
+        timing: None,
+        truncation: Truncation::default(),
+        time: 0,
+    };
+
+    let options = ReverseOptions::new(1).k(DEFAULT_K);
+    let reverse = ReverseResult {
+        items: engine
+            .reverse_with(LOCATION, options)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| ReverseResultItem {
+                city: CityResultItem::from_city(engine, item.city, None),
+                distance: item.distance,
+                distance_m: item.distance_m,
+                score: item.score,
+            })
+            .collect(),
+        address: None,
+        regions: None,
+        admin2_division: None,
+        timing: None,
+        truncation: Truncation::default(),
+        time: 0,
+    };
+
+    let resolve = ResolveResult {
+        resolved_by: ResolvedBy::Pattern,
+        items: vec![CityResultItem::from_city(engine, city, None)],
+        truncation: Truncation::default(),
+        time: 0,
+    };
+
+    let (lat, lng) = LOCATION;
+    Ok(vec![
+        (
+            "GetCityResult",
+            example("/api/city/get", &format!("id={CITY_ID}&lang={LANG}"), &get)?,
+        ),
+        (
+            "SuggestResult",
+            example(
+                "/api/city/suggest",
+                &format!("pattern={PATTERN}&limit=1"),
+                &suggest,
+            )?,
+        ),
+        (
+            "AutocompleteResult",
+            example(
+                "/api/city/autocomplete",
+                &format!("pattern={PATTERN}&limit=1"),
+                &autocomplete,
+            )?,
+        ),
+        (
+            "PlacesResult",
+            example(
+                "/api/suggest",
+                &format!("pattern={PATTERN}&limit=2"),
+                &places,
+            )?,
+        ),
+        (
+            "ReverseResult",
+            example(
+                "/api/city/reverse",
+                &format!("lat={lat}&lng={lng}&limit=1"),
+                &reverse,
+            )?,
+        ),
+        (
+            "ResolveResult",
+            example("/api/city/resolve", &format!("pattern={PATTERN}"), &resolve)?,
+        ),
+    ])
+}
+
+/// Example named by dataset with request line as summary
+fn example<T: Serialize>(path: &str, query: &str, value: &T) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&serde_json::json!({
+        "fixture": {
+            "summary": format!("GET {path}?{query}"),
+            "value": value,
+        }
+    }))
+}
@@ -1,23 +1,34 @@
+use std::borrow::Cow;
 use std::boxed::Box;
-use std::sync::Arc;
-use std::time::Instant;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{LineWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "tracing")]
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[cfg(feature = "geoip2_support")]
 use std::net::IpAddr;
-#[cfg(feature = "geoip2_support")]
 use std::str::FromStr;
 
+use chrono::Offset;
 use ntex::web::{self, middleware, App, HttpRequest, HttpResponse};
 use ntex_cors::Cors;
 use ntex_files as fs;
+use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
 use geosuggest_core::{
+    canonical_language, rollup_admin1,
     storage::{self, IndexStorage},
-    CitiesRecord, Engine,
+    vote_admin2, AdminDivisionInfo, BBox, CitiesRecord, CountryRecord, CountryRecordRaw, Engine,
+    EngineMetadata, GeoEngine, MatchSpan, MemoryUsage, Overrides, Place, PlaceItem, PlaceKind,
+    PlaceWeights, PopulationRank, QueryEvent, QueryHook, ReverseOptions, ReverseScoring,
+    SimilarityMetric, SuggestOptions, SuggestRanking, INDEX_FORMAT_VERSION,
 };
 
 // openapi3
@@ -26,26 +37,84 @@ use oaph::{
     OpenApiPlaceHolder,
 };
 
+mod examples;
 mod settings;
+use settings::JsonCase;
 
 const DEFAULT_K: f32 = 0.000000005;
 const DEFAULT_NEAREST_CITIES_LIMIT: usize = 10;
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+const DEFAULT_RESOLVE_LIMIT: usize = 1;
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// `lang` of official language of each city country
+const LOCAL_LANG: &str = "local";
 
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct GetCityQuery {
     /// geonameid of the City
     id: u32,
-    /// isolanguage code
+    /// isolanguage code, `local` for official language of each city country
     lang: Option<String>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
+    /// return no city when name translation to `lang` is missing
+    strict_lang: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct GetCapitalQuery {
     /// geonameid of the City
     country_code: String,
+    /// isolanguage code, `local` for official language of each city country
+    lang: Option<String>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CountryInfoQuery {
+    /// country code (2-letter)
+    code: String,
+    /// isolanguage code, `local` for official language of the country
+    lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CountryStatsQuery {
+    /// country code (2-letter)
+    code: String,
     /// isolanguage code
     lang: Option<String>,
+    /// count of largest cities (by default 10)
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RandomCityQuery {
+    /// isolanguage code, `local` for official language of each city country
+    lang: Option<String>,
+    /// pick big cities more often (by default false)
+    weight_by_population: Option<bool>,
+    /// comma separated country code (2-letter) to pre-filter cities
+    countries: Option<String>,
+    /// seed to get reproducible result
+    seed: Option<u64>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AdminDivisionQuery {
+    /// admin division code, e.g. `US.CA` or `US.CA.037`
+    code: String,
+    /// isolanguage code, `local` for official language of the division country
+    lang: Option<String>,
 }
 
 // TODO self.countries.split(",").as_slice()
@@ -54,33 +123,275 @@ fn get_countries_filter(countries: &Option<String>) -> Option<Vec<&str>> {
     countries.as_deref().map(|c| c.split(',').collect())
 }
 
+fn get_bbox_filter(bbox: &Option<String>) -> Result<Option<BBox>, String> {
+    let Some(bbox) = bbox else {
+        return Ok(None);
+    };
+    let values = bbox
+        .split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid bbox: {bbox} error: {e}"))?;
+    match values[..] {
+        [min_lat, min_lng, max_lat, max_lng] => Ok(Some(BBox {
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng,
+        })),
+        _ => Err(format!(
+            "Invalid bbox: {bbox} expected `min_lat,min_lng,max_lat,max_lng`"
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordOrder {
+    #[default]
+    LatLng,
+    LonLat,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReverseRollup {
+    Admin1,
+}
+
+fn get_point(
+    lat: Option<f32>,
+    lng: Option<f32>,
+    point: &Option<String>,
+    order: Option<CoordOrder>,
+) -> Result<(f32, f32), String> {
+    let (lat, lng) = match (point, lat, lng) {
+        (Some(point), _, _) => {
+            let value = point.trim();
+            // WKT is always ordered as `lng lat`
+            let (values, order) = if value
+                .get(..5)
+                .is_some_and(|v| v.eq_ignore_ascii_case("point"))
+            {
+                let values = value[5..]
+                    .trim()
+                    .strip_prefix('(')
+                    .and_then(|v| v.strip_suffix(')'))
+                    .ok_or_else(|| format!("Invalid point: {point} expected `POINT(lng lat)`"))?;
+                (values.split_whitespace().collect(), CoordOrder::LonLat)
+            } else {
+                (
+                    value.split(',').collect::<Vec<_>>(),
+                    order.unwrap_or_default(),
+                )
+            };
+            let values = values
+                .iter()
+                .map(|v| v.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid point: {point} error: {e}"))?;
+            match (values.as_slice(), order) {
+                (&[lat, lng], CoordOrder::LatLng) | (&[lng, lat], CoordOrder::LonLat) => (lat, lng),
+                _ => return Err(format!("Invalid point: {point} expected two coordinates")),
+            }
+        }
+        (None, Some(lat), Some(lng)) => (lat, lng),
+        _ => return Err("Either `lat` and `lng` or `point` required".to_string()),
+    };
+
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+        return Err(format!(
+            "Coordinates out of range: lat={lat} lng={lng}, check `coord_order`"
+        ));
+    }
+    Ok((lat, lng))
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct SuggestQuery {
     pattern: String,
     limit: Option<usize>,
-    /// isolanguage code
+    /// isolanguage code, `local` for official language of each city country
+    lang: Option<String>,
+    /// min score of similarity (by default 0.8)
+    min_score: Option<f32>,
+    /// accept names within this count of Levenshtein edits instead of `min_score`, e.g. `1`
+    max_edits: Option<usize>,
+    /// similarity metric (by default `jaro_winkler`)
+    metric: Option<SimilarityMetric>,
+    /// comma separated country code (2-letter) to pre-filter search
+    countries: Option<String>,
+    /// comma separated country code (2-letter) to skip cities of
+    exclude_countries: Option<String>,
+    /// admin division (first level) code to pre-filter search, e.g. `US.CA`
+    admin1: Option<String>,
+    /// min city population to pre-filter search
+    min_population: Option<u32>,
+    /// comma separated geonames feature codes to pre-filter search, e.g. `PPLC,PPLA`
+    feature_codes: Option<String>,
+    /// match only names in `lang` (and default names)
+    match_lang: Option<bool>,
+    /// also match historic names (e.g. `Leningrad`) and suggest current cities
+    former_names: Option<bool>,
+    /// retry empty result by transliteration to latin, e.g. `moskva` matches `Москва` (server built with `translit` feature)
+    transliterate: Option<bool>,
+    /// rank by `blend_alpha * similarity + blend_beta * population` (log scaled to `0..=1`),
+    /// any of them enables population blend (by default 0.8 and 0.2)
+    blend_alpha: Option<f32>,
+    /// see `blend_alpha`
+    blend_beta: Option<f32>,
+    /// name of ranking profile embedded into index (see `ranking_profiles` of `/api/metadata`),
+    /// overrides `blend_alpha` and `blend_beta`
+    profile: Option<String>,
+    /// bias point of `profile` proximity bonus: WKT `POINT(lng lat)` or `lat,lng`, rejected
+    /// without `profile`
+    near: Option<String>,
+    /// comma separated `min_lat,min_lng,max_lat,max_lng` to pre-filter search, e.g. map viewport
+    bbox: Option<String>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
+    /// on empty result return top `limit` candidates below `min_score` with their scores
+    debug: Option<bool>,
+    /// include elapsed time breakdown to `timing` (serialization time is reported by `Server-Timing` header)
+    debug_timing: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlacesQuery {
+    pattern: String,
+    limit: Option<usize>,
+    /// isolanguage code, `local` for official language of each place country
+    lang: Option<String>,
+    /// min score of similarity (by default 0.8)
+    min_score: Option<f32>,
+    /// similarity metric (by default `jaro_winkler`)
+    metric: Option<SimilarityMetric>,
+    /// comma separated country code (2-letter) to pre-filter search
+    countries: Option<String>,
+    /// comma separated country code (2-letter) to skip cities of
+    exclude_countries: Option<String>,
+    /// comma separated kinds of places: `city`, `admin1`, `admin2` and `country`
+    /// (by default all except `admin2`), e.g. `country,admin1` for address forms
+    types: Option<String>,
+    /// weight of cities score (by default 1.0), 0 to exclude cities
+    city_weight: Option<f32>,
+    /// weight of admin divisions score (by default 1.0), 0 to exclude admin divisions
+    admin1_weight: Option<f32>,
+    /// weight of second level admin divisions score (by default 0, excluded)
+    admin2_weight: Option<f32>,
+    /// weight of countries score (by default 1.0), 0 to exclude countries
+    country_weight: Option<f32>,
+    /// include elapsed time breakdown to `timing` (serialization time is reported by `Server-Timing` header)
+    debug_timing: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AutocompleteQuery {
+    pattern: String,
+    limit: Option<usize>,
+    /// isolanguage code, `local` for official language of each city country
     lang: Option<String>,
-    /// min score of Jaro Winkler similarity (by default 0.8)
+    /// min score of similarity (by default 0.8)
     min_score: Option<f32>,
+    /// similarity metric (by default `jaro_winkler`)
+    metric: Option<SimilarityMetric>,
     /// comma separated country code (2-letter) to pre-filter search
     countries: Option<String>,
+    /// comma separated country code (2-letter) to skip cities of
+    exclude_countries: Option<String>,
+    /// min city population to pre-filter search
+    min_population: Option<u32>,
+    /// comma separated `min_lat,min_lng,max_lat,max_lng` to pre-filter search, e.g. map viewport
+    bbox: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct ReverseQuery {
-    lat: f32,
-    lng: f32,
+    /// latitude, required unless `point` is declared
+    lat: Option<f32>,
+    /// longitude, required unless `point` is declared
+    lng: Option<f32>,
+    /// WKT `POINT(lng lat)` or comma separated pair of coordinates ordered by `coord_order`
+    point: Option<String>,
+    /// order of coordinates pair in `point`: `latlng` (default) or `lonlat` (GeoJSON)
+    coord_order: Option<CoordOrder>,
     limit: Option<usize>,
-    /// isolanguage code
+    /// isolanguage code, `local` for official language of each city country
     lang: Option<String>,
     /// distance correction coefficient by city population `score(item) = item.distance - k * item.city.population`
     /// by default `0.000000005`
     k: Option<f32>,
+    /// scale `k` term by local density of cities around queried location, so the same `k` fits dense
+    /// and sparse regions
+    adaptive_k: Option<bool>,
+    /// score formula of nearest cities (by default `linear` by `k`, score in squared degrees),
+    /// `log_population` (score in meters) or `gravity` (score in squared meters per inhabitant)
+    /// don't use `k` and are rejected with it
+    scoring: Option<ReverseScoring>,
     /// neareset cities to apply distance correction coefficient by population
     /// by default 10
     nearest_limit: Option<usize>,
     /// comma separated country code (2-letter) to pre-filter search
     countries: Option<String>,
+    /// comma separated country code (2-letter) to skip cities of
+    exclude_countries: Option<String>,
+    /// min city population to pre-filter search, e.g. to skip hamlets
+    min_population: Option<u32>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
+    /// include structured address (city, admin divisions and country) of the best item
+    address: Option<bool>,
+    /// aggregate nearest cities to `regions`: `admin1` (each admin division once with its nearest city)
+    rollup: Option<ReverseRollup>,
+    /// include `admin2_division` (county) of the point voted by `nearest_limit` nearest cities
+    /// (weighted by distance) instead of the nearest city one, e.g. near county borders
+    admin2_vote: Option<bool>,
+    /// include elapsed time breakdown to `timing` (serialization time is reported by `Server-Timing` header)
+    debug_timing: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TimezoneQuery {
+    /// latitude, required unless `point` is declared
+    lat: Option<f32>,
+    /// longitude, required unless `point` is declared
+    lng: Option<f32>,
+    /// WKT `POINT(lng lat)` or comma separated pair of coordinates ordered by `coord_order`
+    point: Option<String>,
+    /// order of coordinates pair in `point`: `latlng` (default) or `lonlat` (GeoJSON)
+    coord_order: Option<CoordOrder>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResolveQuery {
+    /// text input, resolved by suggest
+    pattern: Option<String>,
+    /// latitude, resolved by reverse (with `lng` or `point`) unless `pattern` is declared
+    lat: Option<f32>,
+    /// longitude, see `lat`
+    lng: Option<f32>,
+    /// WKT `POINT(lng lat)` or comma separated pair of coordinates ordered by `coord_order`
+    point: Option<String>,
+    /// order of coordinates pair in `point`: `latlng` (default) or `lonlat` (GeoJSON)
+    coord_order: Option<CoordOrder>,
+    /// IP, resolved by geoip2 unless `pattern` or coordinates are declared, if not declared then
+    /// `Forwarded` header or peer ip is used (server built with `geoip2_support` feature)
+    #[cfg_attr(not(feature = "geoip2_support"), allow(dead_code))]
+    ip: Option<String>,
+    /// max count of cities, by default 1
+    limit: Option<usize>,
+    /// isolanguage code, `local` for official language of each city country
+    lang: Option<String>,
+    /// comma separated country code (2-letter) to pre-filter suggest and reverse
+    countries: Option<String>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
 }
 
 #[cfg(feature = "geoip2_support")]
@@ -88,8 +399,12 @@ pub struct ReverseQuery {
 pub struct GeoIP2Query {
     /// IP to check, if not declared then `Forwarded` header will used or peer ip as last chance
     ip: Option<String>,
-    /// isolanguage code
+    /// isolanguage code, `local` for official language of each city country
     lang: Option<String>,
+    /// comma separated nested objects to expand: `country`
+    expand: Option<String>,
+    /// include current local time and UTC offset of the city timezone
+    include_local_time: Option<bool>,
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -106,32 +421,734 @@ pub struct GetCapitalResult<'a> {
     time: usize,
 }
 
+#[derive(Serialize, JsonSchema)]
+pub struct RandomCityResult<'a> {
+    city: Option<CityResultItem<'a>>,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CountryInfoResult<'a> {
+    country: Option<CountryInfoItem<'a>>,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CountryStatsItem<'a> {
+    /// count of indexed cities
+    cities: usize,
+    /// total population of indexed cities
+    population: u64,
+    /// largest cities (population desc)
+    largest_cities: Vec<CityResultItem<'a>>,
+    /// share of cities with name translation by language (0..1)
+    translation_coverage: HashMap<String, f32>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CountryStatsResult<'a> {
+    stats: Option<CountryStatsItem<'a>>,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct LanguageItem {
+    /// isolanguage code
+    lang: String,
+    /// count of cities with name translation
+    cities: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct LanguagesResult {
+    /// languages of index translations (cities count desc)
+    items: Vec<LanguageItem>,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct MetadataResult<'a> {
+    /// index was built on version, absent for index without metadata
+    geosuggest_version: Option<&'a str>,
+    /// index creation time (unix timestamp in seconds)
+    created_at: Option<u64>,
+    /// duration of index load in ms
+    load_duration: Option<f32>,
+    /// names of suggest ranking profiles embedded into index, see `profile` of suggest
+    ranking_profiles: Vec<&'a str>,
+    memory: MemoryItem,
+    /// elapsed time in ms
+    time: usize,
+}
+
+/// Approximate heap bytes of index parts
+#[derive(Serialize, JsonSchema)]
+pub struct MemoryItem {
+    #[serde(flatten)]
+    usage: MemoryUsage,
+    total: usize,
+}
+
+impl From<MemoryUsage> for MemoryItem {
+    fn from(usage: MemoryUsage) -> Self {
+        MemoryItem {
+            total: usage.total(),
+            usage,
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct AdminDivisionResult<'a> {
+    admin_division: Option<AdminDivisionInfoItem<'a>>,
+    /// elapsed time in ms
+    time: usize,
+}
+
 #[derive(Serialize, JsonSchema)]
 pub struct SuggestResult<'a> {
     items: Vec<CityResultItem<'a>>,
+    /// candidates below `min_score` (only with `debug` on empty result)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidates: Option<Vec<SuggestCandidateItem<'a>>>,
+    /// present on `debug_timing=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<TimingItem>,
+    #[serde(flatten)]
+    truncation: Truncation,
+    /// elapsed time in ms
+    time: usize,
+}
+
+/// Elapsed time breakdown in ms
+#[derive(Serialize, JsonSchema)]
+pub struct TimingItem {
+    /// engine query
+    engine: f32,
+    /// resolution of result items: translated names, expanded objects, highlights
+    names: f32,
+}
+
+impl TimingItem {
+    fn new(engine: Duration, names: Duration) -> Self {
+        TimingItem {
+            engine: engine.as_secs_f32() * 1000.0,
+            names: names.as_secs_f32() * 1000.0,
+        }
+    }
+}
+
+/// Keys case of JSON response: `case` query param (`snake` or `camel`) or `json_case` setting
+fn json_case(req: &HttpRequest) -> JsonCase {
+    let param = req
+        .query_string()
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("case="));
+    match param {
+        Some("camel") => JsonCase::Camel,
+        Some("snake") => JsonCase::Snake,
+        _ => req.app_state::<JsonCase>().copied().unwrap_or_default(),
+    }
+}
+
+/// Ranking by index embedded profile with optional bias point, see [`get_ranking`] otherwise
+fn get_profile_ranking<E: GeoEngine>(
+    engine: &E,
+    query: &SuggestQuery,
+) -> Result<SuggestRanking, String> {
+    let Some(name) = query.profile.as_deref() else {
+        if query.near.is_some() {
+            return Err("`near` requires `profile`".to_owned());
+        }
+        return Ok(get_ranking(query.blend_alpha, query.blend_beta));
+    };
+    let profile = engine
+        .ranking_profile(name)
+        .ok_or_else(|| format!("Unknown ranking profile `{name}`"))?;
+    let bias = match &query.near {
+        Some(_) => Some(get_point(None, None, &query.near, None)?),
+        None => None,
+    };
+    Ok(SuggestRanking::Profile { profile, bias })
+}
+
+/// Population blend ranking if any of its coefficients is set
+fn get_ranking(alpha: Option<f32>, beta: Option<f32>) -> SuggestRanking {
+    match (SuggestRanking::POPULATION_BLEND, alpha.or(beta)) {
+        (SuggestRanking::PopulationBlend { alpha: a, beta: b }, Some(_)) => {
+            SuggestRanking::PopulationBlend {
+                alpha: alpha.unwrap_or(a),
+                beta: beta.unwrap_or(b),
+            }
+        }
+        _ => SuggestRanking::Similarity,
+    }
+}
+
+/// Cap of requested limits, `max_limit` setting
+#[derive(Debug, Clone, Copy)]
+struct MaxLimit(Option<usize>);
+
+/// Limits of response capped by `max_limit` setting
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct Truncation {
+    /// `true` when requested `limit` (or `nearest_limit` of reverse) exceeds `max_limit` of server
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+    /// applied `limit`, present when it's capped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    /// applied `nearest_limit` of reverse, present when it's capped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nearest_limit: Option<usize>,
+}
+
+impl Truncation {
+    /// By applied `limit` of [`apply_limit`]
+    fn new(limit: Option<usize>) -> Self {
+        Truncation {
+            truncated: limit.is_some(),
+            limit,
+            nearest_limit: None,
+        }
+    }
+}
+
+/// Requested limit capped by `max_limit` setting, `Some` applied limit when it's capped
+fn apply_limit(req: &HttpRequest, limit: usize) -> (usize, Option<usize>) {
+    match req.app_state::<MaxLimit>() {
+        Some(MaxLimit(Some(max_limit))) if limit > *max_limit => (*max_limit, Some(*max_limit)),
+        _ => (limit, None),
+    }
+}
+
+/// Sorted geonameids of cities skipped by suggest/reverse, `blocked_ids` setting
+#[derive(Debug, Clone, Default)]
+struct BlockedIds(Arc<[u32]>);
+
+impl BlockedIds {
+    fn parse(value: &str) -> Result<Self, String> {
+        let mut ids = value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse::<u32>()
+                    .map_err(|e| format!("Invalid geonameid {id:?}: {e}"))
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+        // sorted once, so the engine looks them up by binary search as is
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(BlockedIds(ids.into()))
+    }
+}
+
+fn blocked_ids(req: &HttpRequest) -> &[u32] {
+    req.app_state::<BlockedIds>()
+        .map(|ids| ids.0.as_ref())
+        .unwrap_or_default()
+}
+
+/// Endpoints counted by country of found cities, see [`track_results`]
+const TRACKED_ENDPOINTS: [&str; 7] = [
+    "autocomplete",
+    "geoip2",
+    "get",
+    "places",
+    "resolve",
+    "reverse",
+    "suggest",
+];
+
+/// Requests by country of found cities and geoip2 lookups by country, exposed by `/metrics`
+#[derive(Debug, Clone)]
+struct CountryUsage(Arc<CountryCounters>);
+
+/// Counters are allocated up front for countries of index, cities of other countries are
+/// not counted
+#[derive(Debug)]
+struct CountryCounters {
+    /// Sorted country codes, counters are in the same order
+    countries: Vec<String>,
+    /// Requests by [`TRACKED_ENDPOINTS`] then by country
+    results: Vec<Vec<AtomicU64>>,
+    /// Lookups by country of resolved city, the last one is of not resolved IP
+    geoip2: Vec<AtomicU64>,
+}
+
+impl CountryUsage {
+    fn new<'a>(countries: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut countries = countries.into_iter().map(str::to_owned).collect::<Vec<_>>();
+        countries.sort_unstable();
+        countries.dedup();
+        let counters = |n| (0..n).map(|_| AtomicU64::new(0)).collect::<Vec<_>>();
+        CountryUsage(Arc::new(CountryCounters {
+            results: TRACKED_ENDPOINTS
+                .iter()
+                .map(|_| counters(countries.len()))
+                .collect(),
+            geoip2: counters(countries.len() + 1),
+            countries,
+        }))
+    }
+
+    fn country_index(&self, code: &str) -> Option<usize> {
+        self.0
+            .countries
+            .binary_search_by(|country| country.as_str().cmp(code))
+            .ok()
+    }
+}
+
+/// Count request once per distinct country of found places
+fn track_countries<'a>(
+    req: &HttpRequest,
+    endpoint: &'static str,
+    countries: impl IntoIterator<Item = &'a str>,
+) {
+    let Some(usage) = req.app_state::<CountryUsage>() else {
+        return;
+    };
+    let Some(endpoint) = TRACKED_ENDPOINTS.iter().position(|e| *e == endpoint) else {
+        return;
+    };
+    let mut indexes = countries
+        .into_iter()
+        .filter_map(|code| usage.country_index(code))
+        .collect::<Vec<_>>();
+    indexes.sort_unstable();
+    indexes.dedup();
+    for index in indexes {
+        usage.0.results[endpoint][index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count request once per distinct country of found cities
+fn track_results<'a>(
+    req: &HttpRequest,
+    endpoint: &'static str,
+    cities: impl IntoIterator<Item = &'a CitiesRecord>,
+) {
+    track_countries(
+        req,
+        endpoint,
+        cities
+            .into_iter()
+            .filter_map(|city| city.country.as_ref().map(|c| c.code.as_str())),
+    );
+}
+
+#[cfg(feature = "geoip2_support")]
+fn track_geoip2(req: &HttpRequest, city: Option<&CitiesRecord>) {
+    let Some(usage) = req.app_state::<CountryUsage>() else {
+        return;
+    };
+    let index = city
+        .and_then(|city| city.country.as_ref())
+        .and_then(|c| usage.country_index(&c.code));
+    // not resolved IP (or unknown country) is counted by the last counter
+    let counter = index.map_or(usage.0.geoip2.last(), |index| usage.0.geoip2.get(index));
+    if let Some(counter) = counter {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// `name_lang` -> `nameLang`
+fn camel_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' && !result.is_empty() {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn camel_case_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (camel_case(&key), camel_case_keys(value)))
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(camel_case_keys).collect())
+        }
+        value => value,
+    }
+}
+
+/// JSON response in requested keys case, timing breakdown with serialization time is also
+/// reported by `Server-Timing` header
+fn json_response<T: Serialize>(
+    req: &HttpRequest,
+    result: &T,
+    timing: Option<&TimingItem>,
+) -> HttpResponse {
+    let case = json_case(req);
+    if case == JsonCase::Snake && timing.is_none() {
+        return HttpResponse::Ok().json(result);
+    }
+
+    let now = Instant::now();
+    let body = match case {
+        JsonCase::Snake => serde_json::to_vec(result),
+        JsonCase::Camel => serde_json::to_value(result)
+            .and_then(|value| serde_json::to_vec(&camel_case_keys(value))),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Failed to serialize: {e}"))
+        }
+    };
+
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/json");
+    if let Some(timing) = timing {
+        response.header(
+            "Server-Timing",
+            format!(
+                "engine;dur={:.3}, names;dur={:.3}, serialize;dur={:.3}",
+                timing.engine,
+                timing.names,
+                now.elapsed().as_secs_f32() * 1000.0
+            ),
+        );
+    }
+    response.body(body)
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SuggestCandidateItem<'a> {
+    city: CityResultItem<'a>,
+    /// similarity by `metric`
+    score: f32,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct PlacesResult<'a> {
+    items: Vec<PlaceResultItem<'a>>,
+    /// present on `debug_timing=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<TimingItem>,
+    #[serde(flatten)]
+    truncation: Truncation,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaceResultItem<'a> {
+    City {
+        city: Box<CityResultItem<'a>>,
+        /// weighted similarity
+        score: f32,
+    },
+    Admin1 {
+        admin_division: AdminDivisionInfoItem<'a>,
+        /// weighted similarity
+        score: f32,
+    },
+    Admin2 {
+        admin_division: AdminDivisionInfoItem<'a>,
+        /// weighted similarity
+        score: f32,
+    },
+    Country {
+        country: CountryItem<'a>,
+        /// weighted similarity
+        score: f32,
+    },
+}
+
+impl<'a> PlaceResultItem<'a> {
+    pub fn from_place<E: GeoEngine>(
+        engine: &'a E,
+        item: PlaceItem<'a>,
+        lang: Option<&'a str>,
+    ) -> Option<Self> {
+        Some(match item.place {
+            Place::City(city) => PlaceResultItem::City {
+                city: Box::new(CityResultItem::from_city(engine, city, lang)),
+                score: item.score,
+            },
+            Place::Admin1(info) => PlaceResultItem::Admin1 {
+                admin_division: AdminDivisionInfoItem::from_info(info, lang),
+                score: item.score,
+            },
+            Place::Admin2(info) => PlaceResultItem::Admin2 {
+                admin_division: AdminDivisionInfoItem::from_info(info, lang),
+                score: item.score,
+            },
+            Place::Country(country) => PlaceResultItem::Country {
+                country: CountryItem::from_record(country, lang),
+                score: item.score,
+            },
+            // unknown kinds of newer engine are skipped
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct AutocompleteResult {
+    items: Vec<AutocompleteItem>,
+    #[serde(flatten)]
+    truncation: Truncation,
     /// elapsed time in ms
     time: usize,
 }
 
+#[derive(Serialize, JsonSchema)]
+pub struct AutocompleteItem {
+    id: u32,
+    /// localized city, admin division and country names, e.g. `Voronezh, Voronezh Oblast, Russia`
+    display_name: String,
+    latitude: f32,
+    longitude: f32,
+}
+
+impl From<CityResultItem<'_>> for AutocompleteItem {
+    fn from(item: CityResultItem<'_>) -> Self {
+        let mut parts = [
+            Some(item.name.as_ref()),
+            item.admin_division.as_ref().map(|a| a.name),
+            item.country.as_ref().map(|c| c.name),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+        // e.g. city-state
+        parts.dedup();
+
+        AutocompleteItem {
+            id: item.id,
+            display_name: parts.join(", "),
+            latitude: item.latitude,
+            longitude: item.longitude,
+        }
+    }
+}
+
 #[derive(Serialize, JsonSchema)]
 pub struct ReverseResult<'a> {
     items: Vec<ReverseResultItem<'a>>,
+    /// present on `address=true` and non empty items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<AddressItem<'a>>,
+    /// present on `rollup=admin1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regions: Option<Vec<RegionResultItem<'a>>>,
+    /// present on `admin2_vote=true` and found county
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin2_division: Option<AdminDivisionItem<'a>>,
+    /// present on `debug_timing=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<TimingItem>,
+    #[serde(flatten)]
+    truncation: Truncation,
     /// elapsed time in ms
     time: usize,
 }
 
+/// Admin hierarchy of the city as separate objects
+#[derive(Serialize, JsonSchema)]
+pub struct AddressItem<'a> {
+    city: CityResultItem<'a>,
+    admin2_division: Option<AdminDivisionItem<'a>>,
+    admin_division: Option<AdminDivisionItem<'a>>,
+    country: Option<CountryItem<'a>>,
+}
+
+impl<'a> From<CityResultItem<'a>> for AddressItem<'a> {
+    fn from(mut city: CityResultItem<'a>) -> Self {
+        AddressItem {
+            admin2_division: city.admin2_division.take(),
+            admin_division: city.admin_division.take(),
+            country: city.country.take(),
+            city,
+        }
+    }
+}
+
 #[derive(Serialize, JsonSchema)]
 pub struct ReverseResultItem<'a> {
     city: CityResultItem<'a>,
+    /// squared euclidean distance in degrees, longitude is wrapped at antimeridian (used by `k` score)
     distance: f32,
+    /// great-circle distance in meters
+    distance_m: f32,
+    /// rank of the city, lower is better: squared degrees for `linear` scoring, meters for
+    /// `log_population`, squared meters per inhabitant for `gravity`
     score: f32,
 }
 
+/// Admin division of the nearest cities
+#[derive(Serialize, JsonSchema)]
+pub struct RegionResultItem<'a> {
+    /// absent for cities without admin division
+    admin_division: Option<AdminDivisionItem<'a>>,
+    /// the nearest city of division
+    city: CityResultItem<'a>,
+    distance: f32,
+    /// great-circle distance in meters
+    distance_m: f32,
+    score: f32,
+    /// combined population of division cities within `nearest_limit`
+    population: u64,
+    /// count of division cities within `nearest_limit`
+    cities: usize,
+}
+
 #[derive(Serialize, JsonSchema)]
 pub struct CountryItem<'a> {
     id: u32,
     code: &'a str,
     name: &'a str,
+    /// present on `expand=country`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<CountryDetailsItem<'a>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CountryDetailsItem<'a> {
+    iso3: &'a str,
+    continent: &'a str,
+    tld: &'a str,
+    currency_code: &'a str,
+    currency_name: &'a str,
+    /// phone prefix
+    phone: &'a str,
+    /// neighbours country codes (2-letter)
+    neighbours: Vec<&'a str>,
+}
+
+impl<'a> From<&'a CountryRecordRaw> for CountryDetailsItem<'a> {
+    fn from(info: &'a CountryRecordRaw) -> Self {
+        CountryDetailsItem {
+            iso3: &info.iso3,
+            continent: &info.continent,
+            tld: &info.tld,
+            currency_code: &info.currency_code,
+            currency_name: &info.currency_name,
+            phone: &info.phone,
+            neighbours: info
+                .neighbours
+                .split(',')
+                .filter(|c| !c.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct CountryInfoItem<'a> {
+    id: u32,
+    code: &'a str,
+    name: &'a str,
+    population: u32,
+    /// area in sq km
+    area: &'a str,
+    /// bounding box of indexed cities
+    bbox: Option<BBox>,
+    details: CountryDetailsItem<'a>,
+    capital: Option<CityResultItem<'a>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct AdminDivisionInfoItem<'a> {
+    id: u32,
+    code: &'a str,
+    name: &'a str,
+    country: Option<CountryItem<'a>>,
+}
+
+/// First official language (countryInfo `languages`) of the city country with city name
+/// translation, e.g. `de` for Berlin
+fn local_language<'a, E: GeoEngine>(engine: &'a E, item: &'a CitiesRecord) -> Option<Cow<'a, str>> {
+    let country = engine.country_info(&item.country.as_ref()?.code)?;
+    country
+        .info
+        .languages
+        .split(',')
+        .filter_map(|lang| canonical_language(lang).ok())
+        .find(|lang| engine.city_name(item, lang).is_some())
+}
+
+/// Translation of country or admin division name to `lang` (`local` is the first official
+/// language of `country` with translation), `default` name when it's missing
+fn translated_name<'a>(
+    names: Option<&'a HashMap<String, String>>,
+    lang: Option<&str>,
+    country: Option<&CountryRecord>,
+    default: &'a str,
+) -> &'a str {
+    let (Some(names), Some(lang)) = (names, lang) else {
+        return default;
+    };
+    let name = if lang.eq_ignore_ascii_case(LOCAL_LANG) {
+        country.and_then(|country| {
+            country
+                .info
+                .languages
+                .split(',')
+                .filter_map(|lang| canonical_language(lang).ok())
+                .find_map(|lang| names.get(lang.as_ref()))
+        })
+    } else {
+        names.get(
+            canonical_language(lang)
+                .unwrap_or(Cow::Borrowed(lang))
+                .as_ref(),
+        )
+    };
+    name.map_or(default, String::as_str)
+}
+
+impl<'a> CountryItem<'a> {
+    pub fn from_record(country: &'a CountryRecord, lang: Option<&str>) -> Self {
+        let name = translated_name(
+            country.names.as_ref(),
+            lang,
+            Some(country),
+            &country.info.name,
+        );
+        CountryItem {
+            id: country.info.geonameid,
+            code: &country.info.iso,
+            name,
+            details: None,
+        }
+    }
+}
+
+impl<'a> AdminDivisionInfoItem<'a> {
+    pub fn from_info(item: AdminDivisionInfo<'a>, lang: Option<&str>) -> Self {
+        let division = item.division;
+        let name = translated_name(item.names, lang, item.country, &division.name);
+        AdminDivisionInfoItem {
+            id: division.id,
+            code: &division.code,
+            name,
+            country: item
+                .country
+                .map(|country| CountryItem::from_record(country, lang)),
+        }
+    }
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -144,7 +1161,10 @@ pub struct AdminDivisionItem<'a> {
 #[derive(Serialize, JsonSchema)]
 pub struct CityResultItem<'a> {
     id: u32,
-    name: &'a str,
+    name: Cow<'a, str>,
+    /// language of the translated name (canonical isolanguage code), absent when default name is returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_lang: Option<Cow<'a, str>>,
     country: Option<CountryItem<'a>>,
     admin_division: Option<AdminDivisionItem<'a>>,
     admin2_division: Option<AdminDivisionItem<'a>>,
@@ -152,178 +1172,938 @@ pub struct CityResultItem<'a> {
     latitude: f32,
     longitude: f32,
     population: u32,
+    /// geonames feature code, e.g. `PPLC`
+    feature_code: &'a str,
+    /// population percentiles within the country and globally
+    #[serde(skip_serializing_if = "Option::is_none")]
+    population_rank: Option<PopulationRank>,
+    /// historic names, e.g. `Leningrad` (index is built with former names)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    former_names: Option<&'a [String]>,
+    /// current local time (RFC 3339), present on `include_local_time=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_time: Option<String>,
+    /// current UTC offset in seconds, present on `include_local_time=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    utc_offset: Option<i32>,
+    /// matched part of the name, present on suggest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlight: Option<MatchSpan>,
+    /// matched alternate name, present on suggest when `name` doesn't contain the pattern
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched: Option<MatchedName<'a>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct MatchedName<'a> {
+    /// normalized (lowercased) alternate name
+    value: &'a str,
+    /// matched part of the value
+    highlight: Option<MatchSpan>,
+}
+
+/// Resolution path of [`resolve`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolvedBy {
+    Pattern,
+    Coordinates,
+    Ip,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct TimezoneResult<'a> {
+    /// IANA timezone id of the nearest city, e.g. `Europe/Moscow`
+    timezone: Option<&'a str>,
+    /// current UTC offset in seconds
+    utc_offset: Option<i32>,
+    /// current local time (RFC 3339)
+    local_time: Option<String>,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ResolveResult<'a> {
+    /// `pattern`, `coordinates` or `ip`
+    resolved_by: ResolvedBy,
+    items: Vec<CityResultItem<'a>>,
+    #[serde(flatten)]
+    truncation: Truncation,
+    /// elapsed time in ms
+    time: usize,
+}
+
+#[cfg(feature = "geoip2_support")]
+#[derive(Serialize, JsonSchema)]
+pub struct GeoIP2Result<'a> {
+    city: Option<CityResultItem<'a>>,
+    for_ip: String,
+    /// elapsed time in ms
+    time: usize,
+}
+
+impl<'a> CityResultItem<'a> {
+    pub fn from_city<E: GeoEngine>(
+        engine: &'a E,
+        item: &'a CitiesRecord,
+        lang: Option<&'a str>,
+    ) -> Self {
+        // e.g. `en-US` and `eng` are looked up as `en`
+        let lang = match lang {
+            Some(lang) if lang.eq_ignore_ascii_case(LOCAL_LANG) => local_language(engine, item),
+            lang => lang.map(|lang| canonical_language(lang).unwrap_or(Cow::Borrowed(lang))),
+        };
+
+        let translated = lang
+            .clone()
+            .and_then(|lang| Some((engine.city_name(item, &lang)?, lang)));
+        let (name, name_lang) = match translated {
+            Some((name, lang)) => (name, Some(lang)),
+            None => (Cow::Borrowed(item.name.as_str()), None),
+        };
+        let lang = lang.as_deref();
+
+        let country = if let Some(ref country) = item.country {
+            let country_name = match (lang, engine.country_names(item)) {
+                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&country.name),
+                _ => &country.name,
+            };
+            Some(CountryItem {
+                id: country.id,
+                code: &country.code,
+                name: country_name,
+                details: None,
+            })
+        } else {
+            None
+        };
+
+        let admin_division = if let Some(ref admin1) = item.admin_division {
+            let admin1_name = match (lang, engine.admin1_names(item)) {
+                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&admin1.name),
+                _ => &admin1.name,
+            };
+            Some(AdminDivisionItem {
+                id: admin1.id,
+                code: &admin1.code,
+                name: admin1_name,
+            })
+        } else {
+            None
+        };
+
+        let admin2_division = if let Some(ref admin2) = item.admin2_division {
+            let admin2_name = match (lang, engine.admin2_names(item)) {
+                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&admin2.name),
+                _ => &admin2.name,
+            };
+            Some(AdminDivisionItem {
+                id: admin2.id,
+                code: &admin2.code,
+                name: admin2_name,
+            })
+        } else {
+            None
+        };
+
+        CityResultItem {
+            id: item.id,
+            name,
+            name_lang,
+            country,
+            admin_division,
+            admin2_division,
+            timezone: &item.timezone,
+            latitude: item.latitude,
+            longitude: item.longitude,
+            population: item.population,
+            feature_code: &item.feature_code,
+            population_rank: engine.population_rank(item),
+            former_names: item.former_names.as_deref(),
+            local_time: None,
+            utc_offset: None,
+            highlight: None,
+            matched: None,
+        }
+    }
+
+    /// Highlight matched part of the (localized) name.
+    pub fn with_highlight<E: GeoEngine>(mut self, engine: &E, pattern: &str) -> Self {
+        self.highlight = engine.match_span(&self.name, pattern);
+        self
+    }
+
+    /// Highlight matched alternate name if the (localized) name isn't highlighted.
+    pub fn with_matched<E: GeoEngine>(
+        mut self,
+        engine: &E,
+        matched: &'a str,
+        pattern: &str,
+    ) -> Self {
+        if self.highlight.is_none() {
+            self.matched = Some(MatchedName {
+                value: matched,
+                highlight: engine.match_span(matched, pattern),
+            });
+        }
+        self
+    }
+
+    /// Add current local time by city timezone on `include_local_time=true`.
+    pub fn with_local_time(mut self, include_local_time: Option<bool>) -> Self {
+        if !include_local_time.unwrap_or(false) {
+            return self;
+        }
+
+        if let Ok(tz) = self.timezone.parse::<chrono_tz::Tz>() {
+            let now = chrono::Utc::now().with_timezone(&tz);
+            self.utc_offset = Some(now.offset().fix().local_minus_utc());
+            self.local_time = Some(now.to_rfc3339());
+        }
+
+        self
+    }
+
+    /// Inline nested objects requested by comma separated `expand` param.
+    pub fn expand<E: GeoEngine>(mut self, engine: &'a E, expand: Option<&str>) -> Self {
+        let Some(expand) = expand else {
+            return self;
+        };
+
+        for item in expand.split(',') {
+            match item.trim() {
+                "country" => {
+                    if let Some(country) = self.country.as_mut() {
+                        country.details = engine
+                            .country_info(country.code)
+                            .map(|c| CountryDetailsItem::from(&c.info));
+                    }
+                }
+                _ => continue,
+            }
+        }
+
+        self
+    }
+}
+
+pub async fn city_get<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<GetCityQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let city = engine.get(&query.id);
+    track_results(&req, "get", city);
+
+    let city = city
+        .map(|city| {
+            CityResultItem::from_city(engine, city, query.lang.as_deref())
+                .expand(engine, query.expand.as_deref())
+                .with_local_time(query.include_local_time)
+        })
+        .filter(|city| {
+            !(query.strict_lang.unwrap_or(false)
+                && query.lang.is_some()
+                && city.name_lang.is_none())
+        });
+
+    json_response(
+        &req,
+        &GetCityResult {
+            time: now.elapsed().as_millis() as usize,
+            city,
+        },
+        None,
+    )
+}
+
+pub async fn capital<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<GetCapitalQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let city = engine.capital(&query.country_code).map(|city| {
+        CityResultItem::from_city(engine, city, query.lang.as_deref())
+            .expand(engine, query.expand.as_deref())
+            .with_local_time(query.include_local_time)
+    });
+
+    json_response(
+        &req,
+        &GetCapitalResult {
+            time: now.elapsed().as_millis() as usize,
+            city,
+        },
+        None,
+    )
+}
+
+pub async fn random<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<RandomCityQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let mut rng = match query.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let city = engine
+        .random_city(
+            &mut rng,
+            query.weight_by_population.unwrap_or(false),
+            get_countries_filter(&query.countries).as_deref(),
+        )
+        .map(|city| {
+            CityResultItem::from_city(engine, city, query.lang.as_deref())
+                .expand(engine, query.expand.as_deref())
+                .with_local_time(query.include_local_time)
+        });
+
+    json_response(
+        &req,
+        &RandomCityResult {
+            time: now.elapsed().as_millis() as usize,
+            city,
+        },
+        None,
+    )
+}
+
+pub async fn country_info<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<CountryInfoQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let lang = query.lang.as_deref();
+    let country = engine.country_info_with_capital(&query.code).map(|item| {
+        let info = &item.country.info;
+        let name = translated_name(
+            item.country.names.as_ref(),
+            lang,
+            Some(item.country),
+            &info.name,
+        );
+        CountryInfoItem {
+            id: info.geonameid,
+            code: &info.iso,
+            name,
+            population: info.population,
+            area: &info.area,
+            bbox: item.country.bbox,
+            details: CountryDetailsItem::from(info),
+            capital: item
+                .capital
+                .map(|city| CityResultItem::from_city(engine, city, lang)),
+        }
+    });
+
+    json_response(
+        &req,
+        &CountryInfoResult {
+            time: now.elapsed().as_millis() as usize,
+            country,
+        },
+        None,
+    )
+}
+
+pub async fn country_stats<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<CountryStatsQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let lang = query.lang.as_deref();
+    let stats = engine
+        .country_stats(&query.code, query.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT))
+        .map(|stats| CountryStatsItem {
+            translation_coverage: stats
+                .translations
+                .into_iter()
+                .map(|(lang, count)| (lang, count as f32 / stats.cities as f32))
+                .collect(),
+            cities: stats.cities,
+            population: stats.population,
+            largest_cities: stats
+                .largest_cities
+                .into_iter()
+                .map(|city| CityResultItem::from_city(engine, city, lang))
+                .collect(),
+        });
+
+    json_response(
+        &req,
+        &CountryStatsResult {
+            time: now.elapsed().as_millis() as usize,
+            stats,
+        },
+        None,
+    )
 }
 
-#[cfg(feature = "geoip2_support")]
-#[derive(Serialize, JsonSchema)]
-pub struct GeoIP2Result<'a> {
-    city: Option<CityResultItem<'a>>,
-    for_ip: String,
-    /// elapsed time in ms
-    time: usize,
+pub async fn languages<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let mut items = engine
+        .language_coverage()
+        .iter()
+        .map(|(lang, cities)| LanguageItem {
+            lang: lang.to_owned(),
+            cities: *cities,
+        })
+        .collect::<Vec<_>>();
+    items.sort_unstable_by(|a, b| b.cities.cmp(&a.cities).then_with(|| a.lang.cmp(&b.lang)));
+
+    json_response(
+        &req,
+        &LanguagesResult {
+            time: now.elapsed().as_millis() as usize,
+            items,
+        },
+        None,
+    )
 }
 
-impl<'a> CityResultItem<'a> {
-    pub fn from_city(item: &'a CitiesRecord, lang: Option<&'a str>) -> Self {
-        let name = match (lang, item.names.as_ref()) {
-            (Some(lang), Some(names)) => names.get(lang).unwrap_or(&item.name),
-            _ => &item.name,
-        };
+pub async fn index_metadata<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let metadata = engine.metadata();
+    let mut ranking_profiles = metadata
+        .map(|m| {
+            m.ranking_profiles
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    ranking_profiles.sort_unstable();
+
+    json_response(
+        &req,
+        &MetadataResult {
+            geosuggest_version: metadata.map(|m| m.geosuggest_version.as_str()),
+            created_at: metadata.and_then(|m| {
+                m.created_at
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs())
+            }),
+            load_duration: metadata
+                .and_then(|m| m.load_duration)
+                .map(|d| d.as_secs_f32() * 1000.0),
+            ranking_profiles,
+            memory: engine.memory_usage().into(),
+            time: now.elapsed().as_millis() as usize,
+        },
+        None,
+    )
+}
 
-        let country = if let Some(ref country) = item.country {
-            let country_name = match (lang, item.country_names.as_ref()) {
-                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&country.name),
-                _ => &country.name,
-            };
-            Some(CountryItem {
-                id: country.id,
-                code: &country.code,
-                name: country_name,
-            })
-        } else {
-            None
-        };
+/// Engine gauges and usage counters in Prometheus text format, or in OpenMetrics text format
+/// when it's accepted
+pub async fn metrics<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let openmetrics = req
+        .headers()
+        .get(ntex::http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/openmetrics-text"));
+
+    let usage = engine.memory_usage();
+    let mut body = String::from(
+        "# HELP geosuggest_engine_memory_bytes Approximate heap bytes of index parts\n\
+         # TYPE geosuggest_engine_memory_bytes gauge\n",
+    );
+    for (part, bytes) in [
+        ("cities", usage.cities),
+        ("entries", usage.entries),
+        ("tree", usage.tree),
+        ("auxiliary", usage.auxiliary),
+    ] {
+        body.push_str(&format!(
+            "geosuggest_engine_memory_bytes{{part=\"{part}\"}} {bytes}\n"
+        ));
+    }
 
-        let admin_division = if let Some(ref admin1) = item.admin_division {
-            let admin1_name = match (lang, item.admin1_names.as_ref()) {
-                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&admin1.name),
-                _ => &admin1.name,
-            };
-            Some(AdminDivisionItem {
-                id: admin1.id,
-                code: &admin1.code,
-                name: admin1_name,
-            })
-        } else {
-            None
-        };
+    if let Some(duration) = engine.metadata().and_then(|m| m.load_duration) {
+        body.push_str(&format!(
+            "# HELP geosuggest_engine_load_duration_seconds Duration of index load\n\
+             # TYPE geosuggest_engine_load_duration_seconds gauge\n\
+             geosuggest_engine_load_duration_seconds {}\n",
+            duration.as_secs_f64()
+        ));
+    }
 
-        let admin2_division = if let Some(ref admin2) = item.admin2_division {
-            let admin2_name = match (lang, item.admin2_names.as_ref()) {
-                (Some(lang), Some(names)) => names.get(lang).unwrap_or(&admin2.name),
-                _ => &admin2.name,
-            };
-            Some(AdminDivisionItem {
-                id: admin2.id,
-                code: &admin2.code,
-                name: admin2_name,
-            })
-        } else {
-            None
+    if let Some(CountryUsage(usage)) = req.app_state::<CountryUsage>() {
+        // OpenMetrics counter family is named without `_total` suffix of its samples
+        let family = |name: &str| {
+            if openmetrics {
+                name.trim_end_matches("_total").to_owned()
+            } else {
+                name.to_owned()
+            }
         };
+        body.push_str(&format!(
+            "# HELP {0} Requests by country of found cities\n\
+             # TYPE {0} counter\n",
+            family("geosuggest_country_requests_total")
+        ));
+        for (endpoint, counters) in TRACKED_ENDPOINTS.iter().zip(&usage.results) {
+            for (country, counter) in usage.countries.iter().zip(counters) {
+                let count = counter.load(Ordering::Relaxed);
+                if count > 0 {
+                    body.push_str(&format!(
+                        "geosuggest_country_requests_total{{endpoint=\"{endpoint}\",country=\"{country}\"}} {count}\n"
+                    ));
+                }
+            }
+        }
 
-        CityResultItem {
-            id: item.id,
-            name,
-            country,
-            admin_division,
-            admin2_division,
-            timezone: &item.timezone,
-            latitude: item.latitude,
-            longitude: item.longitude,
-            population: item.population,
+        body.push_str(&format!(
+            "# HELP {0} GeoIP2 lookups by country of resolved city\n\
+             # TYPE {0} counter\n",
+            family("geosuggest_geoip2_lookups_total")
+        ));
+        // not resolved IP is reported with empty country
+        let countries = std::iter::once("").chain(usage.countries.iter().map(String::as_str));
+        let counters = usage.geoip2.iter().rev().take(1).chain(&usage.geoip2);
+        for (country, counter) in countries.zip(counters) {
+            let count = counter.load(Ordering::Relaxed);
+            if count > 0 {
+                body.push_str(&format!(
+                    "geosuggest_geoip2_lookups_total{{country=\"{country}\"}} {count}\n"
+                ));
+            }
         }
     }
+
+    if openmetrics {
+        body.push_str("# EOF\n");
+        HttpResponse::Ok()
+            .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+            .body(body)
+    } else {
+        HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body)
+    }
 }
 
-pub async fn city_get(
-    engine: web::types::State<Arc<Engine>>,
-    web::types::Query(query): web::types::Query<GetCityQuery>,
-    _req: HttpRequest,
+pub async fn admin1_get<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<AdminDivisionQuery>,
+    req: HttpRequest,
 ) -> HttpResponse {
     let now = Instant::now();
+    let engine: &E = &engine;
 
-    let city = engine
-        .get(&query.id)
-        .map(|city| CityResultItem::from_city(city, query.lang.as_deref()));
+    let admin_division = engine
+        .admin1(&query.code)
+        .map(|item| AdminDivisionInfoItem::from_info(item, query.lang.as_deref()));
 
-    HttpResponse::Ok().json(&GetCityResult {
-        time: now.elapsed().as_millis() as usize,
-        city,
-    })
+    json_response(
+        &req,
+        &AdminDivisionResult {
+            time: now.elapsed().as_millis() as usize,
+            admin_division,
+        },
+        None,
+    )
 }
 
-pub async fn capital(
-    engine: web::types::State<Arc<Engine>>,
-    web::types::Query(query): web::types::Query<GetCapitalQuery>,
-    _req: HttpRequest,
+pub async fn admin2_get<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<AdminDivisionQuery>,
+    req: HttpRequest,
 ) -> HttpResponse {
     let now = Instant::now();
+    let engine: &E = &engine;
 
-    let city = engine
-        .capital(&query.country_code)
-        .map(|city| CityResultItem::from_city(city, query.lang.as_deref()));
+    let admin_division = engine
+        .admin2(&query.code)
+        .map(|item| AdminDivisionInfoItem::from_info(item, query.lang.as_deref()));
 
-    HttpResponse::Ok().json(&GetCapitalResult {
-        time: now.elapsed().as_millis() as usize,
-        city,
-    })
+    json_response(
+        &req,
+        &AdminDivisionResult {
+            time: now.elapsed().as_millis() as usize,
+            admin_division,
+        },
+        None,
+    )
 }
 
-pub async fn suggest(
-    engine: web::types::State<Arc<Engine>>,
+pub async fn suggest<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
     web::types::Query(query): web::types::Query<SuggestQuery>,
-    _req: HttpRequest,
+    req: HttpRequest,
 ) -> HttpResponse {
     let now = Instant::now();
+    let engine: &E = &engine;
 
-    let result = engine
-        .suggest(
-            query.pattern.as_str(),
-            query.limit.unwrap_or(10),
-            query.min_score,
-            get_countries_filter(&query.countries).as_deref(),
-        )
-        .iter()
-        .map(|item| CityResultItem::from_city(item, query.lang.as_deref()))
+    let bbox = match get_bbox_filter(&query.bbox) {
+        Ok(bbox) => bbox,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let countries = get_countries_filter(&query.countries);
+    let exclude_countries = get_countries_filter(&query.exclude_countries);
+    let feature_codes = query
+        .feature_codes
+        .as_deref()
+        .map(|c| c.split(',').collect::<Vec<_>>());
+    let ranking = match get_profile_ranking(engine, &query) {
+        Ok(ranking) => ranking,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let (limit, truncated) = apply_limit(&req, query.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT));
+    let mut options = SuggestOptions::new(limit)
+        .metric(query.metric.unwrap_or_default())
+        .former_names(query.former_names.unwrap_or(false))
+        .transliterate(query.transliterate.unwrap_or(false))
+        .blocked_ids(blocked_ids(&req))
+        .ranking(ranking);
+    // optional filters of request
+    options.min_score = query.min_score;
+    options.countries = countries.as_deref();
+    options.exclude_countries = exclude_countries.as_deref();
+    options.admin1 = query.admin1.as_deref();
+    options.min_population = query.min_population;
+    options.feature_codes = feature_codes.as_deref();
+    options.lang = query
+        .lang
+        .as_deref()
+        .filter(|lang| query.match_lang.unwrap_or(false) && !lang.eq_ignore_ascii_case(LOCAL_LANG));
+    options.bbox = bbox;
+    options.max_edits = query.max_edits;
+
+    let to_item = |item| {
+        CityResultItem::from_city(engine, item, query.lang.as_deref())
+            .expand(engine, query.expand.as_deref())
+            .with_local_time(query.include_local_time)
+            .with_highlight(engine, &query.pattern)
+    };
+
+    let found = engine.suggest_with_scores(query.pattern.as_str(), options.clone());
+    let engine_time = now.elapsed();
+    track_results(&req, "suggest", found.iter().map(|item| item.city));
+    let result = found
+        .into_iter()
+        .map(|item| to_item(item.city).with_matched(engine, item.matched, &query.pattern))
         .collect::<Vec<CityResultItem>>();
+    let timing = query
+        .debug_timing
+        .unwrap_or(false)
+        .then(|| TimingItem::new(engine_time, now.elapsed() - engine_time));
+
+    let candidates = (result.is_empty() && query.debug.unwrap_or(false)).then(|| {
+        engine
+            .suggest_candidates(query.pattern.as_str(), options)
+            .into_iter()
+            .map(|item| SuggestCandidateItem {
+                city: to_item(item.city),
+                score: item.score,
+            })
+            .collect()
+    });
 
-    HttpResponse::Ok().json(&SuggestResult {
+    let result = SuggestResult {
         time: now.elapsed().as_millis() as usize,
         items: result,
-    })
+        candidates,
+        timing,
+        truncation: Truncation::new(truncated),
+    };
+    json_response(&req, &result, result.timing.as_ref())
+}
+
+pub async fn places<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<PlacesQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let countries = get_countries_filter(&query.countries);
+    let exclude_countries = get_countries_filter(&query.exclude_countries);
+    let (limit, truncated) = apply_limit(&req, query.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT));
+    let mut options = SuggestOptions::new(limit)
+        .metric(query.metric.unwrap_or_default())
+        .blocked_ids(blocked_ids(&req));
+    options.min_score = query.min_score;
+    options.countries = countries.as_deref();
+    options.exclude_countries = exclude_countries.as_deref();
+    let default_weights = match query.types.as_deref() {
+        Some(types) => match types
+            .split(',')
+            .map(PlaceKind::from_str)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(kinds) => PlaceWeights::only(&kinds),
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        },
+        None => PlaceWeights::default(),
+    };
+    let weights = PlaceWeights {
+        city: query.city_weight.unwrap_or(default_weights.city),
+        admin1: query.admin1_weight.unwrap_or(default_weights.admin1),
+        admin2: query.admin2_weight.unwrap_or(default_weights.admin2),
+        country: query.country_weight.unwrap_or(default_weights.country),
+    };
+
+    let lang = query.lang.as_deref();
+    let found = engine.suggest_places(query.pattern.as_str(), options, weights);
+    let engine_time = now.elapsed();
+    track_countries(
+        &req,
+        "places",
+        found.iter().filter_map(|item| match &item.place {
+            Place::City(city) => city.country.as_ref().map(|c| c.code.as_str()),
+            Place::Admin1(info) | Place::Admin2(info) => info.country.map(|c| c.info.iso.as_str()),
+            Place::Country(country) => Some(country.info.iso.as_str()),
+            _ => None,
+        }),
+    );
+    let items = found
+        .into_iter()
+        .filter_map(|item| PlaceResultItem::from_place(engine, item, lang))
+        .collect();
+
+    let timing = query
+        .debug_timing
+        .unwrap_or(false)
+        .then(|| TimingItem::new(engine_time, now.elapsed() - engine_time));
+
+    let result = PlacesResult {
+        time: now.elapsed().as_millis() as usize,
+        items,
+        timing,
+        truncation: Truncation::new(truncated),
+    };
+    json_response(&req, &result, result.timing.as_ref())
+}
+
+pub async fn autocomplete<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<AutocompleteQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let bbox = match get_bbox_filter(&query.bbox) {
+        Ok(bbox) => bbox,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let countries = get_countries_filter(&query.countries);
+    let exclude_countries = get_countries_filter(&query.exclude_countries);
+    let (limit, truncated) = apply_limit(&req, query.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT));
+
+    let mut options = SuggestOptions::new(limit)
+        .metric(query.metric.unwrap_or_default())
+        .blocked_ids(blocked_ids(&req));
+    options.min_score = query.min_score;
+    options.countries = countries.as_deref();
+    options.exclude_countries = exclude_countries.as_deref();
+    options.min_population = query.min_population;
+    options.bbox = bbox;
+
+    let found = engine.suggest_with(query.pattern.as_str(), options);
+    track_results(&req, "autocomplete", found.iter().copied());
+
+    let items = found
+        .into_iter()
+        .map(|item| CityResultItem::from_city(engine, item, query.lang.as_deref()).into())
+        .collect::<Vec<AutocompleteItem>>();
+
+    json_response(
+        &req,
+        &AutocompleteResult {
+            time: now.elapsed().as_millis() as usize,
+            items,
+            truncation: Truncation::new(truncated),
+        },
+        None,
+    )
 }
 
-pub async fn reverse(
-    engine: web::types::State<Arc<Engine>>,
+pub async fn reverse<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
     web::types::Query(query): web::types::Query<ReverseQuery>,
-    _req: HttpRequest,
+    req: HttpRequest,
 ) -> HttpResponse {
     let now = Instant::now();
+    let engine: &E = &engine;
 
-    let items = engine
-        .reverse(
-            (query.lat, query.lng),
-            query.nearest_limit.unwrap_or(DEFAULT_NEAREST_CITIES_LIMIT),
-            Some(query.k.unwrap_or(DEFAULT_K)),
-            get_countries_filter(&query.countries).as_deref(),
-        )
-        .unwrap_or_default();
+    let loc = match get_point(query.lat, query.lng, &query.point, query.coord_order) {
+        Ok(loc) => loc,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let scoring = query.scoring.unwrap_or_default();
+    if query.k.is_some() && scoring != ReverseScoring::Linear {
+        return HttpResponse::BadRequest().body("`k` is used by `linear` scoring only");
+    }
 
-    HttpResponse::Ok().json(&ReverseResult {
-        time: now.elapsed().as_millis() as usize,
-        items: items
-            .iter()
-            .take(query.limit.unwrap_or(DEFAULT_NEAREST_CITIES_LIMIT))
-            .map(|item| ReverseResultItem {
-                city: CityResultItem::from_city(item.city, query.lang.as_deref()),
-                distance: item.distance,
-                score: item.score,
-            })
-            .collect(),
+    let countries = get_countries_filter(&query.countries);
+    let exclude_countries = get_countries_filter(&query.exclude_countries);
+    let (limit, truncated) = apply_limit(&req, query.limit.unwrap_or(DEFAULT_NEAREST_CITIES_LIMIT));
+    let (nearest_limit, nearest_truncated) = apply_limit(
+        &req,
+        query.nearest_limit.unwrap_or(DEFAULT_NEAREST_CITIES_LIMIT),
+    );
+    let truncation = Truncation {
+        truncated: truncated.is_some() || nearest_truncated.is_some(),
+        limit: truncated,
+        nearest_limit: nearest_truncated,
+    };
+    // regions and voted county aggregate the whole pool of nearest cities
+    let pooled = query.rollup.is_some() || query.admin2_vote.unwrap_or(false);
+    let mut options = ReverseOptions::new(if pooled {
+        nearest_limit
+    } else {
+        limit.min(nearest_limit)
     })
+    .nearest_limit(nearest_limit)
+    .k(query.k.unwrap_or(DEFAULT_K))
+    .blocked_ids(blocked_ids(&req))
+    .adaptive_k(query.adaptive_k.unwrap_or(false))
+    .scoring(scoring);
+    options.countries = countries.as_deref();
+    options.exclude_countries = exclude_countries.as_deref();
+    options.min_population = query.min_population;
+    let items = engine.reverse_with(loc, options).unwrap_or_default();
+    let engine_time = now.elapsed();
+    track_results(
+        &req,
+        "reverse",
+        items.iter().take(limit).map(|item| item.city),
+    );
+
+    let to_city = |city| {
+        CityResultItem::from_city(engine, city, query.lang.as_deref())
+            .expand(engine, query.expand.as_deref())
+            .with_local_time(query.include_local_time)
+    };
+
+    let address = items
+        .first()
+        .filter(|_| query.address.unwrap_or(false))
+        .map(|item| to_city(item.city).into());
+
+    let regions = query.rollup.map(|ReverseRollup::Admin1| {
+        rollup_admin1(&items)
+            .into_iter()
+            .take(limit)
+            .map(|item| {
+                let mut city = to_city(item.city);
+                RegionResultItem {
+                    admin_division: city.admin_division.take(),
+                    city,
+                    distance: item.distance,
+                    distance_m: item.distance_m,
+                    score: item.score,
+                    population: item.population,
+                    cities: item.cities,
+                }
+            })
+            .collect()
+    });
+
+    let admin2_division = if query.admin2_vote.unwrap_or(false) {
+        vote_admin2(&items).and_then(|city| to_city(city).admin2_division)
+    } else {
+        None
+    };
+
+    let items = items
+        .iter()
+        .take(limit)
+        .map(|item| ReverseResultItem {
+            city: to_city(item.city),
+            distance: item.distance,
+            distance_m: item.distance_m,
+            score: item.score,
+        })
+        .collect();
+    let timing = query
+        .debug_timing
+        .unwrap_or(false)
+        .then(|| TimingItem::new(engine_time, now.elapsed() - engine_time));
+
+    let result = ReverseResult {
+        time: now.elapsed().as_millis() as usize,
+        items,
+        address,
+        regions,
+        admin2_division,
+        timing,
+        truncation,
+    };
+    json_response(&req, &result, result.timing.as_ref())
 }
 
 #[cfg(feature = "geoip2_support")]
-pub async fn geoip2(
-    engine: web::types::State<Arc<Engine>>,
+pub async fn geoip2<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
     web::types::Query(query): web::types::Query<GeoIP2Query>,
     req: HttpRequest,
 ) -> HttpResponse {
     let now = Instant::now();
+    let engine: &E = &engine;
+
+    let addr = match request_ip(&req, query.ip.as_deref()) {
+        Ok(addr) => addr,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
 
-    let ip = match query.ip.as_ref() {
-        Some(ip) => Some(ip.as_str()),
+    let result = engine.geoip2_lookup(addr);
+    track_geoip2(&req, result);
+    track_results(&req, "geoip2", result);
+
+    json_response(
+        &req,
+        &GeoIP2Result {
+            time: now.elapsed().as_millis() as usize,
+            for_ip: addr.to_string(),
+            city: result.map(|item| {
+                CityResultItem::from_city(engine, item, query.lang.as_deref())
+                    .expand(engine, query.expand.as_deref())
+                    .with_local_time(query.include_local_time)
+            }),
+        },
+        None,
+    )
+}
+
+/// Declared IP, `Forwarded` header or peer ip as last chance
+#[cfg(feature = "geoip2_support")]
+fn request_ip(req: &HttpRequest, ip: Option<&str>) -> Result<IpAddr, String> {
+    let ip = match ip {
+        Some(ip) => Some(ip),
         None => {
             // fallback to headers
             if let Some(forwarded) = req.headers().get(ntex::http::header::FORWARDED) {
@@ -334,58 +2114,421 @@ pub async fn geoip2(
         }
     };
 
-    let addr = match ip {
-        Some(ip) => match IpAddr::from_str(ip) {
-            Ok(addr) => addr,
-            Err(e) => {
-                return HttpResponse::BadRequest()
-                    .body(format!("Invalid ip addr: {} error: {}", ip, e))
-            }
-        },
+    match ip {
+        Some(ip) => {
+            IpAddr::from_str(ip).map_err(|e| format!("Invalid ip addr: {} error: {}", ip, e))
+        }
         None => {
             if let Some(v) = req.connection_info().remote() {
-                if let Ok(ip) = IpAddr::from_str(v.split(':').take(1).next().unwrap_or("")) {
-                    ip
-                } else {
-                    return HttpResponse::BadRequest().body(
-                        "IP address is not declared in request and field to get peer addr"
-                            .to_string(),
-                    );
-                }
+                IpAddr::from_str(v.split(':').take(1).next().unwrap_or("")).map_err(|_| {
+                    "IP address is not declared in request and field to get peer addr".to_string()
+                })
             } else if let Some(peer_addr) = req.peer_addr() {
-                peer_addr.ip()
+                Ok(peer_addr.ip())
             } else {
-                return HttpResponse::BadRequest().body(
-                    "IP address is not declared in request and field to get peer addr".to_string(),
-                );
+                Err("IP address is not declared in request and field to get peer addr".to_string())
             }
         }
+    }
+}
+
+/// Timezone of location by the nearest city
+pub async fn timezone<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<TimezoneQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+
+    let (lat, lng) = match get_point(query.lat, query.lng, &query.point, query.coord_order) {
+        Ok(loc) => loc,
+        Err(e) => return HttpResponse::BadRequest().body(e),
     };
 
-    let result = engine.geoip2_lookup(addr);
+    let timezone = engine.timezone_at(lat, lng);
+    let local = timezone
+        .and_then(|tz| tz.parse::<chrono_tz::Tz>().ok())
+        .map(|tz| chrono::Utc::now().with_timezone(&tz));
+
+    json_response(
+        &req,
+        &TimezoneResult {
+            timezone,
+            utc_offset: local.map(|local| local.offset().fix().local_minus_utc()),
+            local_time: local.map(|local| local.to_rfc3339()),
+            time: now.elapsed().as_millis() as usize,
+        },
+        None,
+    )
+}
 
-    HttpResponse::Ok().json(&GeoIP2Result {
-        time: now.elapsed().as_millis() as usize,
-        for_ip: addr.to_string(),
-        city: result.map(|item| CityResultItem::from_city(item, query.lang.as_deref())),
-    })
+/// Resolve city by `pattern` (suggest), coordinates (reverse) or IP (geoip2), whichever is
+/// declared first
+pub async fn resolve<E: GeoEngine + 'static>(
+    engine: web::types::State<Arc<E>>,
+    web::types::Query(query): web::types::Query<ResolveQuery>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let now = Instant::now();
+    let engine: &E = &engine;
+
+    let (limit, truncated) = apply_limit(&req, query.limit.unwrap_or(DEFAULT_RESOLVE_LIMIT));
+    let countries = get_countries_filter(&query.countries);
+
+    let (resolved_by, cities) = if let Some(pattern) = query.pattern.as_deref() {
+        let mut options = SuggestOptions::new(limit).blocked_ids(blocked_ids(&req));
+        options.countries = countries.as_deref();
+        (ResolvedBy::Pattern, engine.suggest_with(pattern, options))
+    } else if query.lat.is_some() || query.lng.is_some() || query.point.is_some() {
+        let loc = match get_point(query.lat, query.lng, &query.point, query.coord_order) {
+            Ok(loc) => loc,
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        };
+        let mut options = ReverseOptions::new(limit)
+            .nearest_limit(DEFAULT_NEAREST_CITIES_LIMIT)
+            .k(DEFAULT_K)
+            .blocked_ids(blocked_ids(&req));
+        options.countries = countries.as_deref();
+        let items = engine.reverse_with(loc, options).unwrap_or_default();
+        (
+            ResolvedBy::Coordinates,
+            items.into_iter().map(|item| item.city).collect(),
+        )
+    } else {
+        #[cfg(not(feature = "geoip2_support"))]
+        return HttpResponse::BadRequest().body("`pattern` or coordinates are required");
+
+        #[cfg(feature = "geoip2_support")]
+        match request_ip(&req, query.ip.as_deref()) {
+            Ok(addr) => {
+                let city = engine.geoip2_lookup(addr);
+                track_geoip2(&req, city);
+                (ResolvedBy::Ip, city.into_iter().collect())
+            }
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        }
+    };
+
+    track_results(&req, "resolve", cities.iter().copied());
+
+    let items = cities
+        .into_iter()
+        .map(|city| {
+            CityResultItem::from_city(engine, city, query.lang.as_deref())
+                .expand(engine, query.expand.as_deref())
+                .with_local_time(query.include_local_time)
+        })
+        .collect();
+
+    json_response(
+        &req,
+        &ResolveResult {
+            resolved_by,
+            items,
+            truncation: Truncation::new(truncated),
+            time: now.elapsed().as_millis() as usize,
+        },
+        None,
+    )
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ApiIndexResult {
+    version: &'static str,
+    /// available api endpoints
+    endpoints: Vec<&'static str>,
+    /// enabled optional features, e.g. `geoip2`
+    features: Vec<&'static str>,
+    limits: ApiLimits,
+    openapi_url: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ApiLimits {
+    /// default `limit` of suggest and autocomplete
+    suggest_limit: usize,
+    /// default `limit` and `nearest_limit` of reverse
+    nearest_limit: usize,
+    /// cap of requested limits, see `truncated` of responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_limit: Option<usize>,
+}
+
+/// Route patterns of registered api endpoints, see [`api_config`]
+#[derive(Debug, Clone)]
+struct ApiEndpoints(Vec<&'static str>);
+
+pub async fn api_index(req: HttpRequest) -> HttpResponse {
+    let endpoints = req
+        .app_state::<ApiEndpoints>()
+        .map(|endpoints| endpoints.0.clone())
+        .unwrap_or_default();
+
+    let features = [
+        ("geoip2", cfg!(feature = "geoip2_support")),
+        ("cjk", cfg!(feature = "cjk")),
+        ("romanize", cfg!(feature = "romanize")),
+        ("translit", cfg!(feature = "translit")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect();
+
+    // served next to `/api` under the same url path prefix
+    let prefix = req.path().trim_end_matches('/');
+    let prefix = prefix.strip_suffix("/api").unwrap_or(prefix);
+
+    json_response(
+        &req,
+        &ApiIndexResult {
+            version: VERSION,
+            endpoints,
+            features,
+            limits: ApiLimits {
+                suggest_limit: DEFAULT_SUGGEST_LIMIT,
+                nearest_limit: DEFAULT_NEAREST_CITIES_LIMIT,
+                max_limit: req
+                    .app_state::<MaxLimit>()
+                    .and_then(|max_limit| max_limit.0),
+            },
+            openapi_url: format!("{prefix}/openapi3.yaml"),
+        },
+        None,
+    )
+}
+
+/// Route patterns of api path with and without trailing slash, which some HTTP clients and
+/// health checkers append
+fn api_path(path: &str) -> Vec<String> {
+    vec![path.to_owned(), format!("{path}/")]
+}
+
+type ApiResource = web::Resource<web::DefaultError>;
+/// Attaches handler to resource of api endpoint
+type ApiHandler = fn(ApiResource) -> ApiResource;
+
+/// Api endpoints of engine `E` by route pattern
+fn api_endpoints<E: GeoEngine + 'static>() -> Vec<(&'static str, ApiResource)> {
+    let endpoints: Vec<(&'static str, ApiHandler)> = vec![
+        ("/api/city/get", |r| r.to(city_get::<E>)),
+        ("/api/city/capital", |r| r.to(capital::<E>)),
+        ("/api/city/suggest", |r| r.to(suggest::<E>)),
+        ("/api/city/autocomplete", |r| r.to(autocomplete::<E>)),
+        ("/api/suggest", |r| r.to(places::<E>)),
+        ("/api/city/reverse", |r| r.to(reverse::<E>)),
+        ("/api/city/random", |r| r.to(random::<E>)),
+        ("/api/city/resolve", |r| r.to(resolve::<E>)),
+        #[cfg(feature = "geoip2_support")]
+        ("/api/city/geoip2", |r| r.to(geoip2::<E>)),
+        ("/api/timezone", |r| r.to(timezone::<E>)),
+        ("/api/country/info", |r| r.to(country_info::<E>)),
+        ("/api/country/stats", |r| r.to(country_stats::<E>)),
+        ("/api/admin1/get", |r| r.to(admin1_get::<E>)),
+        ("/api/admin2/get", |r| r.to(admin2_get::<E>)),
+        ("/api/metadata", |r| r.to(index_metadata::<E>)),
+        ("/api/metadata/languages", |r| r.to(languages::<E>)),
+        ("/api/schema/{name}", |r| r.to(schema)),
+    ];
+    endpoints
+        .into_iter()
+        .map(|(pattern, to)| (pattern, to(web::resource(api_path(pattern)))))
+        .collect()
+}
+
+/// Registers api endpoints of engine `E` and `/api` index listing them
+fn api_config<E: GeoEngine + 'static>(cfg: &mut web::ServiceConfig) {
+    let endpoints = api_endpoints::<E>();
+    cfg.state(ApiEndpoints(
+        endpoints.iter().map(|(pattern, _)| *pattern).collect(),
+    ))
+    .service(web::resource(api_path("/api")).to(api_index));
+    for (_, resource) in endpoints {
+        cfg.service(resource);
+    }
+}
+
+/// JSON Schema of response type by name
+fn response_schema(name: &str) -> Option<schemars::schema::RootSchema> {
+    let schema = match name {
+        "GetCityResult" => schemars::schema_for!(GetCityResult),
+        "GetCapitalResult" => schemars::schema_for!(GetCapitalResult),
+        "SuggestResult" => schemars::schema_for!(SuggestResult),
+        "AutocompleteResult" => schemars::schema_for!(AutocompleteResult),
+        "ReverseResult" => schemars::schema_for!(ReverseResult),
+        "RandomCityResult" => schemars::schema_for!(RandomCityResult),
+        "CountryInfoResult" => schemars::schema_for!(CountryInfoResult),
+        "CountryStatsResult" => schemars::schema_for!(CountryStatsResult),
+        "AdminDivisionResult" => schemars::schema_for!(AdminDivisionResult),
+        "LanguagesResult" => schemars::schema_for!(LanguagesResult),
+        "MetadataResult" => schemars::schema_for!(MetadataResult),
+        "PlacesResult" => schemars::schema_for!(PlacesResult),
+        "ResolveResult" => schemars::schema_for!(ResolveResult),
+        "TimezoneResult" => schemars::schema_for!(TimezoneResult),
+        "ApiIndexResult" => schemars::schema_for!(ApiIndexResult),
+        #[cfg(feature = "geoip2_support")]
+        "GeoIP2Result" => schemars::schema_for!(GeoIP2Result),
+        _ => return None,
+    };
+    Some(schema)
+}
+
+pub async fn schema(name: web::types::Path<String>, _req: HttpRequest) -> HttpResponse {
+    match response_schema(&name) {
+        Some(schema) => HttpResponse::Ok().json(&schema),
+        None => HttpResponse::NotFound().body(format!("Unknown schema: {}", name.as_str())),
+    }
+}
+
+/// Lines queued to query log writer, the rest are dropped while the file is slow
+const QUERY_LOG_BUFFER: usize = 10_000;
+
+/// Query hook appending events as JSON lines to file.
+///
+/// Lines are written by a background thread, so queries never wait for file I/O.
+pub struct QueryLog {
+    sender: Option<mpsc::SyncSender<String>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl QueryLog {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::sync_channel::<String>(QUERY_LOG_BUFFER);
+        let writer = std::thread::Builder::new()
+            .name("query-log".to_owned())
+            .spawn(move || {
+                let mut file = LineWriter::new(file);
+                for line in receiver {
+                    if let Err(_e) = writeln!(file, "{line}") {
+                        #[cfg(feature = "tracing")]
+                        tracing::error!("On write query log: {_e}");
+                    }
+                }
+            })?;
+        Ok(QueryLog {
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+}
+
+impl QueryHook for QueryLog {
+    fn on_query(&self, event: &QueryEvent<'_>) {
+        let (Some(sender), Ok(line)) = (self.sender.as_ref(), serde_json::to_string(event)) else {
+            return;
+        };
+        if let Err(_e) = sender.try_send(line) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("Query log line is dropped: {_e}");
+        }
+    }
+}
+
+impl Drop for QueryLog {
+    /// Write queued lines before exit
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Index is built with supported layout version and components required by settings.
+fn preflight(
+    settings: &settings::Settings,
+    metadata: Option<&EngineMetadata>,
+) -> Result<(), String> {
+    let requires_languages = settings
+        .require_languages
+        .as_deref()
+        .map(|l| {
+            l.split(',')
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let requires_countries = settings.require_countries.unwrap_or(false);
+    let requires_admin_codes = settings.require_admin_codes.unwrap_or(false);
+
+    let Some(metadata) = metadata else {
+        if requires_countries || requires_admin_codes || !requires_languages.is_empty() {
+            return Err("index doesn't have metadata to check required components".to_owned());
+        }
+        return Ok(());
+    };
+
+    if metadata.index_format_version != INDEX_FORMAT_VERSION {
+        return Err(format!(
+            "index is built by geosuggest {} with format version {}, server supports {} (rebuild index)",
+            metadata.geosuggest_version, metadata.index_format_version, INDEX_FORMAT_VERSION
+        ));
+    }
+
+    if requires_countries && metadata.source.countries.is_none() {
+        return Err("index is built without countries info".to_owned());
+    }
+    if requires_admin_codes && metadata.source.admin1_codes.is_none() {
+        return Err("index is built without admin divisions codes".to_owned());
+    }
+    let missing = requires_languages
+        .iter()
+        .filter(|lang| {
+            metadata.source.names.is_none()
+                || !metadata
+                    .source
+                    .filter_languages
+                    .iter()
+                    .any(|l| canonical_language(l).ok() == canonical_language(lang).ok())
+        })
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(format!("index is built without languages: {missing:?}"));
+    }
+
+    Ok(())
 }
 
 fn generate_openapi_files(settings: &settings::Settings) -> Result<(), Box<dyn std::error::Error>> {
     let openapi3_yaml_path = std::env::temp_dir().join("openapi3.yaml");
+    let base_url = settings
+        .public_url
+        .as_deref()
+        .unwrap_or(&settings.url_path_prefix);
 
     // render openapi3 yaml to temporary file
     let aoph = OpenApiPlaceHolder::new()
         .substitute("version", VERSION)
-        .substitute("url_path_prefix", &settings.url_path_prefix)
+        .substitute("base_url", base_url)
         .query_params::<GetCityQuery>("GetCityQuery")?
         .query_params::<GetCapitalQuery>("GetCapitalQuery")?
         .query_params::<SuggestQuery>("SuggestQuery")?
         .query_params::<ReverseQuery>("ReverseQuery")?
+        .query_params::<AutocompleteQuery>("AutocompleteQuery")?
+        .query_params::<PlacesQuery>("PlacesQuery")?
+        .query_params::<CountryInfoQuery>("CountryInfoQuery")?
+        .query_params::<CountryStatsQuery>("CountryStatsQuery")?
+        .query_params::<AdminDivisionQuery>("AdminDivisionQuery")?
+        .query_params::<RandomCityQuery>("RandomCityQuery")?
+        .query_params::<ResolveQuery>("ResolveQuery")?
+        .query_params::<TimezoneQuery>("TimezoneQuery")?
         .schema::<GetCityResult>("GetCityResult")?
         .schema::<GetCapitalResult>("GetCapitalResult")?
         .schema::<SuggestResult>("SuggestResult")?
-        .schema::<ReverseResult>("ReverseResult")?;
+        .schema::<ReverseResult>("ReverseResult")?
+        .schema::<AutocompleteResult>("AutocompleteResult")?
+        .schema::<PlacesResult>("PlacesResult")?
+        .schema::<CountryInfoResult>("CountryInfoResult")?
+        .schema::<CountryStatsResult>("CountryStatsResult")?
+        .schema::<AdminDivisionResult>("AdminDivisionResult")?
+        .schema::<LanguagesResult>("LanguagesResult")?
+        .schema::<MetadataResult>("MetadataResult")?
+        .schema::<ApiIndexResult>("ApiIndexResult")?
+        .schema::<RandomCityResult>("RandomCityResult")?
+        .schema::<ResolveResult>("ResolveResult")?
+        .schema::<TimezoneResult>("TimezoneResult")?;
+    let aoph = examples::substitute(aoph)?;
 
     #[cfg(feature = "geoip2_support")]
     let aoph = {
@@ -400,10 +2543,8 @@ fn generate_openapi_files(settings: &settings::Settings) -> Result<(), Box<dyn s
 
     let title = format!("geosuggest v{}", VERSION);
 
-    let openapi3_url_path = std::path::Path::new(&settings.url_path_prefix).join("openapi3.yaml");
-    let openapi3_url_path = openapi3_url_path
-        .to_str()
-        .ok_or("Failed to build openapi3 url")?;
+    let openapi3_url_path = format!("{}/openapi3.yaml", base_url.trim_end_matches('/'));
+    let openapi3_url_path = openapi3_url_path.as_str();
 
     // render swagger ui html to temporary file
     OpenApiPlaceHolder::swagger_ui_html_to_file(
@@ -446,19 +2587,78 @@ async fn main() -> std::io::Result<()> {
         panic!("Please set `index_file`");
     }
 
-    let storage = storage::bincode::Storage::new();
+    let storage = storage::bincode::Storage::new().with_validation(
+        if settings.validate_index.unwrap_or(false) {
+            storage::Validation::Full
+        } else {
+            storage::Validation::Trusted
+        },
+    );
+
+    // fail fast on incompatible index, before loading it
+    let metadata = storage.read_metadata(&settings.index_file).unwrap_or_else(|e| {
+        panic!(
+            "On read index metadata {}: {} (index is missing or built by incompatible geosuggest version)",
+            settings.index_file, e
+        )
+    });
+    if let Err(e) = preflight(&settings, metadata.as_ref()) {
+        panic!("Index {} is not supported: {}", settings.index_file, e);
+    }
+
+    // read geoip2 database while index is loaded (and its kd-tree is built)
+    #[cfg(feature = "geoip2_support")]
+    let geoip2_data = settings.geoip2_file.clone().map(|geoip2_file| {
+        std::thread::spawn(move || {
+            geosuggest_core::GeoIP2Data::read(&geoip2_file)
+                .map_err(|e| format!("On read geoip2 file from {}: {}", geoip2_file, e))
+        })
+    });
 
     let mut engine = storage
         .load_from(&settings.index_file)
         .unwrap_or_else(|e| panic!("On build engine from file: {} - {}", settings.index_file, e));
 
+    if let Some(overrides_file) = settings.overrides_file.as_ref() {
+        let overrides = Overrides::from_file(overrides_file)
+            .unwrap_or_else(|e| panic!("On read overrides file {}: {}", overrides_file, e));
+        let _applied = engine.apply_overrides(&overrides);
+        #[cfg(feature = "tracing")]
+        tracing::info!("Applied {} overrides from {}", _applied, overrides_file);
+    }
+
     #[cfg(feature = "geoip2_support")]
-    if let Some(geoip2_file) = settings.geoip2_file.as_ref() {
+    if let Some(geoip2_data) = geoip2_data {
+        let geoip2_data = geoip2_data
+            .join()
+            .expect("On read geoip2 file")
+            .unwrap_or_else(|e| panic!("{}", e));
         engine
-            .load_geoip2(geoip2_file)
-            .unwrap_or_else(|_| panic!("On read geoip2 file from {}", geoip2_file));
+            .set_geoip2(geoip2_data)
+            .unwrap_or_else(|e| panic!("On init geoip2 reader: {}", e));
+
+        if let Some(size) = settings.geoip2_cache_size {
+            engine.set_geoip2_cache(size);
+        }
+    }
+
+    if let Some(query_log_file) = settings.query_log_file.as_ref() {
+        let query_log = QueryLog::open(query_log_file)
+            .unwrap_or_else(|e| panic!("On open query log file {}: {}", query_log_file, e));
+        engine.set_query_hook(Some(Arc::new(query_log)));
     }
 
+    let blocked_ids = match settings.blocked_ids.as_deref() {
+        Some(value) => BlockedIds::parse(value)
+            .unwrap_or_else(|e| panic!("On read `blocked_ids` setting: {}", e)),
+        None => BlockedIds::default(),
+    };
+
+    // lazily built parts of engine would delay the first requests
+    engine.warm_up();
+
+    let country_usage = CountryUsage::new(engine.countries().map(|c| c.info.iso.as_str()));
+
     let shared_engine = Arc::new(engine);
     let shared_engine_clone = shared_engine.clone();
 
@@ -474,19 +2674,20 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .state(shared_engine)
+            .state(settings.json_case.unwrap_or_default())
+            .state(blocked_ids.clone())
+            .state(country_usage.clone())
+            .state(MaxLimit(settings.max_limit))
             // enable logger
             .wrap(middleware::Logger::default())
             .wrap(Cors::default())
             .service(
                 web::scope(&settings.url_path_prefix)
+                    // api
+                    .configure(api_config::<Engine>)
                     .service((
-                        // api
-                        web::resource("/api/city/get").to(city_get),
-                        web::resource("/api/city/capital").to(capital),
-                        web::resource("/api/city/suggest").to(suggest),
-                        web::resource("/api/city/reverse").to(reverse),
-                        #[cfg(feature = "geoip2_support")]
-                        web::resource("/api/city/geoip2").to(geoip2),
+                        // prometheus
+                        web::resource("/metrics").to(metrics::<Engine>),
                         // serve openapi3 yaml and ui from files
                         fs::Files::new("/openapi3.yaml", std::env::temp_dir())
                             .index_file("openapi3.yaml"),
@@ -6,15 +6,50 @@ const CONFIG_PREFIX: &str = "GEOSUGGEST";
 const CONFIG_FILE_PATH: &str = "./defaults.toml";
 const CONFIG_FILE_ENV_PATH_KEY: &str = "GEOSUGGEST_CONFIG_FILE";
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub host: String,
     pub port: usize,
     pub index_file: String,
+    /// Check index consistency on load (slower start), for indexes from untrusted sources
+    pub validate_index: Option<bool>,
+    /// JSON file of cities corrections applied on index load, see `geosuggest_core::Overrides`
+    pub overrides_file: Option<String>,
+    /// Fail on start if index is built without countries info
+    pub require_countries: Option<bool>,
+    /// Fail on start if index is built without admin divisions codes
+    pub require_admin_codes: Option<bool>,
+    /// Fail on start if index is built without translations of languages (comma separated), e.g. `ru,de`
+    pub require_languages: Option<String>,
+    /// Geonameids of cities never returned by suggest/reverse (comma separated), e.g. duplicates
+    /// or known-bad records, no index rebuild required
+    pub blocked_ids: Option<String>,
+    /// Cap of requested `limit` (and `nearest_limit`), capped responses have `truncated: true`
+    /// and applied `limit`
+    pub max_limit: Option<usize>,
     pub static_dir: Option<String>,
     pub url_path_prefix: String,
+    /// Public base url (with scheme) behind reverse proxy, e.g. `https://example.com/geo`,
+    /// used by OpenAPI servers and Swagger/ReDoc UIs instead of `url_path_prefix`
+    pub public_url: Option<String>,
+    /// Keys case of JSON responses: `snake` (default) or `camel`, could be overridden by
+    /// `case` query param
+    pub json_case: Option<JsonCase>,
+    /// Append every suggest/reverse query (pattern, filters, results count, latency) as JSON line
+    pub query_log_file: Option<String>,
     #[cfg(feature = "geoip2_support")]
     pub geoip2_file: Option<String>,
+    /// Max cached geoip2 lookups, disabled by default
+    #[cfg(feature = "geoip2_support")]
+    pub geoip2_cache_size: Option<usize>,
 }
 
 impl Settings {
@@ -53,10 +88,22 @@ impl Default for Settings {
             host: "localhost".to_owned(),
             port: 8080,
             index_file: "".to_string(),
+            validate_index: None,
+            overrides_file: None,
+            require_countries: None,
+            require_admin_codes: None,
+            require_languages: None,
+            blocked_ids: None,
+            max_limit: None,
             static_dir: None,
             url_path_prefix: "/".to_string(),
+            public_url: None,
+            json_case: None,
+            query_log_file: None,
             #[cfg(feature = "geoip2_support")]
             geoip2_file: None,
+            #[cfg(feature = "geoip2_support")]
+            geoip2_cache_size: None,
         }
     }
 }
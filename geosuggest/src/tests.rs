@@ -1,7 +1,15 @@
-use geosuggest_core::{Engine, SourceFileOptions};
+use geosuggest_core::{
+    AdminDivisionInfo, CitiesRecord, CountryRecord, CountryStats, Engine, EngineMetadata,
+    GeoEngine, MemoryUsage, Place, PlaceItem, PlaceWeights, PopulationRank, RankingProfile,
+    ReverseItem, ReverseOptions, SourceFileOptions, SuggestItem, SuggestOptions,
+    INDEX_FORMAT_VERSION,
+};
 use ntex::web::{test, App, Error, ServiceConfig};
 use ntex::{http, web};
 
+use rand::Rng;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 fn app_config(cfg: &mut ServiceConfig) {
@@ -12,23 +20,56 @@ fn app_config(cfg: &mut ServiceConfig) {
         filter_languages: vec!["ru"],
         admin1_codes: Some("../geosuggest-core/tests/misc/admin1-codes.txt"),
         admin2_codes: Some("../geosuggest-core/tests/misc/admin2-codes.txt"),
+        ..Default::default()
     })
     .unwrap();
 
+    engine
+        .metadata
+        .get_or_insert_with(Default::default)
+        .ranking_profiles
+        .insert(
+            "nearby".to_owned(),
+            RankingProfile {
+                similarity: 0.0,
+                bias_distance: 100.0,
+                ..RankingProfile::default()
+            },
+        );
+
     #[cfg(feature = "geoip2_support")]
     engine
         .load_geoip2("../geosuggest-core/tests/misc/GeoLite2-City-Test.mmdb")
         .unwrap();
 
+    let country_usage = super::CountryUsage::new(engine.countries().map(|c| c.info.iso.as_str()));
     let engine = Arc::new(engine);
-    cfg.state(engine).service((
-        web::resource("/get").to(super::city_get),
-        web::resource("/capital").to(super::capital),
-        web::resource("/suggest").to(super::suggest),
-        web::resource("/reverse").to(super::reverse),
-        #[cfg(feature = "geoip2_support")]
-        web::resource("/geoip2").to(super::geoip2),
-    ));
+    super::api_config::<Engine>(cfg);
+    cfg.state(engine)
+        .state(country_usage)
+        .service((
+            web::resource(super::api_path("/get")).to(super::city_get::<Engine>),
+            web::resource(super::api_path("/capital")).to(super::capital::<Engine>),
+            web::resource(super::api_path("/suggest")).to(super::suggest::<Engine>),
+            web::resource(super::api_path("/autocomplete")).to(super::autocomplete::<Engine>),
+            web::resource(super::api_path("/places")).to(super::places::<Engine>),
+            web::resource(super::api_path("/reverse")).to(super::reverse::<Engine>),
+            web::resource(super::api_path("/random")).to(super::random::<Engine>),
+            web::resource(super::api_path("/resolve")).to(super::resolve::<Engine>),
+            #[cfg(feature = "geoip2_support")]
+            web::resource(super::api_path("/geoip2")).to(super::geoip2::<Engine>),
+            web::resource(super::api_path("/country/info")).to(super::country_info::<Engine>),
+            web::resource(super::api_path("/country/stats")).to(super::country_stats::<Engine>),
+            web::resource(super::api_path("/admin1/get")).to(super::admin1_get::<Engine>),
+            web::resource(super::api_path("/admin2/get")).to(super::admin2_get::<Engine>),
+        ))
+        .service((
+            web::resource(super::api_path("/timezone")).to(super::timezone::<Engine>),
+            web::resource(super::api_path("/metadata")).to(super::index_metadata::<Engine>),
+            web::resource(super::api_path("/metadata/languages")).to(super::languages::<Engine>),
+            web::resource("/metrics").to(super::metrics::<Engine>),
+            web::resource(super::api_path("/schema/{name}")).to(super::schema),
+        ));
 }
 
 #[test_log::test(ntex::test)]
@@ -47,6 +88,138 @@ async fn api_get() -> Result<(), Error> {
     assert!(city.is_some());
     let city = city.unwrap();
     assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Voronezh");
+    assert_eq!(
+        city.get("population_rank").unwrap(),
+        &serde_json::json!({"country": 50.0, "global": 40.0})
+    );
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_get_name_lang() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/get?id=472045&lang=ru&strict_lang=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let city = result.get("city").unwrap();
+    assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Воронеж");
+    assert_eq!(city.get("name_lang").unwrap().as_str().unwrap(), "ru");
+
+    // language tag is canonicalized
+    let req = test::TestRequest::get()
+        .uri("/get?id=472045&lang=ru-RU")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let city = result.get("city").unwrap();
+    assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Воронеж");
+    assert_eq!(city.get("name_lang").unwrap().as_str().unwrap(), "ru");
+
+    // Moscow isn't translated, fallback to default name
+    let req = test::TestRequest::get()
+        .uri("/get?id=524901&lang=ru")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let city = result.get("city").unwrap();
+    assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Moscow");
+    assert!(city.get("name_lang").is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/get?id=524901&lang=ru&strict_lang=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("city").unwrap().is_null());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_get_expand_country() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/get?id=472045&expand=country")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let details = result
+        .get("city")
+        .unwrap()
+        .get("country")
+        .unwrap()
+        .get("details")
+        .unwrap();
+    assert_eq!(
+        details.get("currency_code").unwrap().as_str().unwrap(),
+        "RUB"
+    );
+    assert_eq!(details.get("phone").unwrap().as_str().unwrap(), "7");
+    assert_eq!(details.get("tld").unwrap().as_str().unwrap(), ".ru");
+    assert!(details
+        .get("neighbours")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!("UA")));
+
+    // not expanded by default
+    let req = test::TestRequest::get().uri("/get?id=472045").to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert!(result
+        .get("city")
+        .unwrap()
+        .get("country")
+        .unwrap()
+        .get("details")
+        .is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_get_local_time() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/get?id=472045&include_local_time=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let city = result.get("city").unwrap();
+    // Europe/Moscow
+    assert_eq!(city.get("utc_offset").unwrap().as_i64().unwrap(), 3 * 3600);
+    assert!(city
+        .get("local_time")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .ends_with("+03:00"));
+
+    // not included by default
+    let req = test::TestRequest::get().uri("/get?id=472045").to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert!(result.get("city").unwrap().get("local_time").is_none());
 
     Ok(())
 }
@@ -118,6 +291,34 @@ async fn api_get_lang() -> Result<(), Error> {
     Ok(())
 }
 
+#[test_log::test(ntex::test)]
+async fn api_local_lang() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    // russian city in russian, english city (no translations in index) by default name
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&lang=local&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let city = &result.get("items").unwrap()[0];
+    assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Воронеж");
+    assert_eq!(city.get("name_lang").unwrap().as_str().unwrap(), "ru");
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Beverley&lang=local&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let city = &result.get("items").unwrap()[0];
+    assert_eq!(city.get("name").unwrap().as_str().unwrap(), "Beverley");
+    assert!(city.get("name_lang").is_none());
+
+    Ok(())
+}
+
 #[test_log::test(ntex::test)]
 async fn api_suggest() -> Result<(), Error> {
     let app = test::init_service(App::new().configure(app_config)).await;
@@ -139,6 +340,390 @@ async fn api_suggest() -> Result<(), Error> {
     Ok(())
 }
 
+#[test_log::test(ntex::test)]
+async fn api_suggest_bbox() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&bbox=50,38,53,40")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0].get("name").unwrap().as_str().unwrap(), "Voronezh");
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&bbox=54,38,56,40")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&bbox=50,38")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_debug() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezx&min_score=0.99&limit=2")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+    assert!(result.get("candidates").is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezx&min_score=0.99&limit=2&debug=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+    let candidates = result.get("candidates").unwrap().as_array().unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0]["city"]["name"].as_str().unwrap(), "Voronezh");
+    let score = candidates[0]["score"].as_f64().unwrap();
+    assert!(score > 0.9 && score < 0.99);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_metric() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Vornoezh&min_score=0.85&metric=normalized_damerau")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0].get("name").unwrap().as_str().unwrap(), "Voronezh");
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Vornoezh&metric=soundex")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_match_lang() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=%D0%92%D0%BE%D1%80%D0%BE%D0%BD%D0%B5%D0%B6&lang=ru&match_lang=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["items"][0]["name"], "Воронеж");
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=%D0%92%D0%BE%D1%80%D0%BE%D0%BD%D0%B5%D0%B6&lang=de&match_lang=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result["items"].as_array().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_matched() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Woronesch&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let item = &result["items"][0];
+    assert_eq!(item["name"], "Voronezh");
+    assert!(item.get("highlight").is_none());
+    assert_eq!(
+        item["matched"],
+        serde_json::json!({
+            "value": "woronesch",
+            "highlight": {"start": 0, "end": 9, "byte_start": 0, "byte_end": 9}
+        })
+    );
+
+    // name is highlighted, alternate name is skipped
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voron&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result["items"][0].get("highlight").is_some());
+    assert!(result["items"][0].get("matched").is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_admin1() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&admin1=ru.86")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0].get("name").unwrap().as_str().unwrap(), "Voronezh");
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&admin1=RU.48")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_exclude_countries() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&exclude_countries=RU")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1&exclude_countries=gb,%20ru")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0]["city"]["country"]["code"], "RS");
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_blocked_ids() -> Result<(), Error> {
+    assert!(super::BlockedIds::parse("1, x").is_err());
+    let blocked_ids = super::BlockedIds::parse("472045, ").unwrap();
+    let app = test::init_service(App::new().state(blocked_ids).configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("items").unwrap().as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_ne!(items[0]["city"]["id"], 472045);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_max_limit() -> Result<(), Error> {
+    let app = test::init_service(
+        App::new()
+            .state(super::MaxLimit(Some(2)))
+            .configure(app_config),
+    )
+    .await;
+
+    for uri in [
+        "/suggest?pattern=a&min_score=0&limit=5",
+        "/places?pattern=a&min_score=0&limit=5",
+        "/autocomplete?pattern=a&min_score=0&limit=5",
+        "/reverse?lat=51.6372&lng=39.1937&limit=5&nearest_limit=2",
+        "/resolve?pattern=a&limit=5",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["truncated"], true, "{uri}");
+        assert_eq!(result["limit"], 2, "{uri}");
+        assert!(result.get("nearest_limit").is_none(), "{uri}");
+        assert!(result["items"].as_array().unwrap().len() <= 2, "{uri}");
+    }
+
+    // reverse reports which of its limits is capped
+    for (uri, limit, nearest_limit) in [
+        ("/reverse?lat=51.6372&lng=39.1937&limit=5", Some(2), Some(2)),
+        (
+            "/reverse?lat=51.6372&lng=39.1937&limit=1&nearest_limit=5",
+            None,
+            Some(2),
+        ),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["truncated"], true, "{uri}");
+        assert_eq!(result.get("limit").and_then(|v| v.as_u64()), limit, "{uri}");
+        assert_eq!(
+            result.get("nearest_limit").and_then(|v| v.as_u64()),
+            nearest_limit,
+            "{uri}"
+        );
+    }
+
+    // within limit
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&limit=2")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("truncated").is_none());
+    assert!(result.get("limit").is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_debug_timing() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for uri in [
+        "/suggest?pattern=Voronezh&debug_timing=true",
+        "/places?pattern=Voronezh&debug_timing=true",
+        "/reverse?lat=51.6372&lng=39.1937&debug_timing=true",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let server_timing = resp
+            .headers()
+            .get("server-timing")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert!(server_timing.contains("serialize;dur="), "{uri}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert!(result["timing"]["engine"].is_number(), "{uri}");
+        assert!(result["timing"]["names"].is_number(), "{uri}");
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert!(resp.headers().get("server-timing").is_none());
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("timing").is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_camel_case() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&limit=1&include_local_time=true&case=camel")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let item = &result["items"][0];
+    assert!(item.get("adminDivision").is_some());
+    assert!(item.get("admin_division").is_none());
+    assert!(item["localTime"].is_string());
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Voronezh&limit=1&case=snake")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result["items"][0].get("admin_division").is_some());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_highlight() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=%D0%B2%D0%BE%D1%80%D0%BE&lang=ru&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0].get("name").unwrap().as_str().unwrap(), "Воронеж");
+    assert_eq!(
+        items[0].get("highlight").unwrap(),
+        &serde_json::json!({"start": 0, "end": 4, "byte_start": 0, "byte_end": 8})
+    );
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_autocomplete() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/autocomplete?pattern=Voronezh&lang=ru&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    let item = items[0].as_object().unwrap();
+    assert_eq!(item.len(), 4);
+    assert_eq!(item.get("id").unwrap().as_u64().unwrap(), 472045);
+    let display_name = item.get("display_name").unwrap().as_str().unwrap();
+    assert!(display_name.starts_with("Воронеж, Воронежская"));
+    assert!(display_name.ends_with(", Россия"));
+
+    Ok(())
+}
+
 #[test_log::test(ntex::test)]
 async fn api_suggest_lang() -> Result<(), Error> {
     let app = test::init_service(App::new().configure(app_config)).await;
@@ -177,41 +762,447 @@ async fn api_suggest_lang() -> Result<(), Error> {
             .get("name")
             .unwrap()
             .as_str()
-            .unwrap(),
-        "Воронежская область"
-    );
+            .unwrap(),
+        "Воронежская область"
+    );
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1&countries=ru,jp")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0]
+            .get("city")
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .get("name")
+            .unwrap()
+            .as_str()
+            .unwrap(),
+        "Voronezh"
+    );
+    let distance_m = items[0]["distance_m"].as_f64().unwrap();
+    assert!((3800.0..4000.0).contains(&distance_m));
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_address() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1&address=true&expand=country")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+
+    let address = result.get("address").unwrap();
+    assert_eq!(address["city"]["name"], "Voronezh");
+    assert!(address["city"]["country"].is_null());
+    assert_eq!(address["admin_division"]["id"], 472039);
+    assert_eq!(address["country"]["code"], "RU");
+    assert_eq!(address["country"]["details"]["iso3"], "RUS");
+
+    // items are untouched
+    let items = result.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0]["city"]["country"]["code"], "RU");
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("address").is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_rollup() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&rollup=admin1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+
+    let regions = result.get("regions").unwrap().as_array().unwrap();
+    assert_eq!(regions[0]["admin_division"]["id"], 472039);
+    assert_eq!(regions[0]["city"]["name"], "Voronezh");
+    assert!(regions[0]["population"].as_u64().unwrap() >= 848752);
+
+    // each division once
+    let mut ids = regions
+        .iter()
+        .map(|r| r["admin_division"]["id"].as_u64())
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), regions.len());
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&rollup=admin2")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_admin2_vote() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=53.84587&lng=-0.42332&limit=1&admin2_vote=true")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["admin2_division"]["id"], 2650345);
+    assert_eq!(
+        result["admin2_division"]["name"],
+        "East Riding of Yorkshire"
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=53.84587&lng=-0.42332&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("admin2_division").is_none());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_min_population() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1&k=0&min_population=1000000")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["city"]["name"], "Moscow");
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_scoring() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for (scoring, expected) in [("linear", "Beverley"), ("gravity", "London")] {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/reverse?lat=53.1446&lng=-0.334&limit=1&scoring={scoring}"
+            ))
+            .to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["items"][0]["city"]["name"], expected, "{scoring}");
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/reverse?lat=53.1446&lng=-0.334&scoring=unknown")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    // `k` isn't used by other scorings
+    for scoring in ["log_population", "gravity"] {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/reverse?lat=53.1446&lng=-0.334&k=0.1&scoring={scoring}"
+            ))
+            .to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{scoring}");
+    }
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_suggest_profile() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for (query, expected) in [
+        ("", "Beverley"),
+        ("&profile=nearby&near=51.5,-0.12", "London"),
+        ("&profile=nearby&near=POINT(20.46 44.8)", "Belgrade"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/suggest?pattern=Beverley&min_score=0&limit=1{}",
+                query.replace(' ', "%20")
+            ))
+            .to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{query}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["items"][0]["name"], expected, "{query}");
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Beverley&profile=unknown")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    // bias point is applied by profile only
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=Beverley&near=51.5,-0.12")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_timezone() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/timezone?lat=51.6372&lng=39.1937")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["timezone"], "Europe/Moscow");
+    assert_eq!(result["utc_offset"], 3 * 3600);
+    assert!(result["local_time"].as_str().unwrap().ends_with("+03:00"));
+
+    let req = test::TestRequest::get()
+        .uri("/timezone?point=POINT(-0.42%2053.84)")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["timezone"], "Europe/London");
+
+    let req = test::TestRequest::get()
+        .uri("/timezone?lat=51.6")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_reverse_point() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for uri in [
+        "/reverse?point=POINT(39.1937%2051.6372)&limit=1",
+        "/reverse?point=point%20(39.1937%2051.6372)&limit=1",
+        "/reverse?point=51.6372,39.1937&limit=1",
+        "/reverse?point=39.1937,51.6372&coord_order=lonlat&limit=1",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["items"][0]["city"]["name"], "Voronezh", "{uri}");
+    }
+
+    for uri in [
+        "/reverse?limit=1",
+        "/reverse?lat=51.6372&limit=1",
+        "/reverse?point=POINT(39.1937)&limit=1",
+        "/reverse?point=51.6372;39.1937&limit=1",
+        "/reverse?lat=139.1937&lng=51.6372&limit=1",
+        "/reverse?point=51.6372,139.1937&coord_order=lonlat&limit=1",
+        "/reverse?point=51.6372,39.1937&coord_order=xy&limit=1",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{uri}");
+    }
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_resolve() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let mut uris = vec![
+        ("/resolve?pattern=Voronezh", "pattern"),
+        ("/resolve?lat=51.6372&lng=39.1937", "coordinates"),
+        (
+            "/resolve?point=39.1937,51.6372&coord_order=lonlat",
+            "coordinates",
+        ),
+    ];
+    if cfg!(feature = "geoip2_support") {
+        uris.push(("/resolve?ip=81.2.69.142", "ip"));
+    }
+    for (uri, resolved_by) in uris {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let result: serde_json::Value =
+            serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+        assert_eq!(result["resolved_by"], resolved_by);
+        let items = result["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        let name = items[0]["name"].as_str().unwrap();
+        assert_eq!(
+            name,
+            if resolved_by == "ip" {
+                "London"
+            } else {
+                "Voronezh"
+            }
+        );
+    }
+
+    // pattern takes precedence
+    let req = test::TestRequest::get()
+        .uri("/resolve?pattern=London&lat=51.6372&lng=39.1937&limit=2")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["resolved_by"], "pattern");
+    assert_eq!(result["items"][0]["name"], "London");
+
+    let req = test::TestRequest::get()
+        .uri("/resolve?lat=51.6372")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_index() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get().uri("/api").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+
+    let endpoints = result["endpoints"].as_array().unwrap();
+    assert!(endpoints.contains(&serde_json::json!("/api/city/suggest")));
+    assert_eq!(result["limits"]["suggest_limit"], 10);
+    assert_eq!(result["openapi_url"], "/openapi3.yaml");
+    for (feature, enabled) in [
+        ("geoip2", cfg!(feature = "geoip2_support")),
+        ("translit", cfg!(feature = "translit")),
+    ] {
+        assert_eq!(
+            result["features"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!(feature)),
+            enabled,
+            "{feature}"
+        );
+    }
+
+    // listed endpoints are served
+    for endpoint in endpoints {
+        let uri = endpoint
+            .as_str()
+            .unwrap()
+            .replace("{name}", "SuggestResult");
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_ne!(resp.status(), http::StatusCode::NOT_FOUND, "{uri}");
+    }
 
     Ok(())
 }
 
 #[test_log::test(ntex::test)]
-async fn api_reverse() -> Result<(), Error> {
+async fn api_places() -> Result<(), Error> {
     let app = test::init_service(App::new().configure(app_config)).await;
 
     let req = test::TestRequest::get()
-        .uri("/reverse?lat=51.6372&lng=39.1937&limit=1&countries=ru,jp")
+        .uri("/places?pattern=Voronez&lang=ru")
         .to_request();
     let resp = app.call(req).await.unwrap();
-
     assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result["items"].as_array().unwrap();
+    assert_eq!(items[0]["type"], "city");
+    assert_eq!(items[0]["city"]["name"], "Воронеж");
+    let admin1 = items.iter().find(|i| i["type"] == "admin1").unwrap();
+    assert_eq!(admin1["admin_division"]["id"], 472039);
+
+    // prefer regions over cities
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=Voronez&city_weight=0.5")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["items"][0]["type"], "admin1");
 
-    let bytes = test::read_body(resp).await;
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=Russia&city_weight=0&admin1_weight=0")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result["items"].as_array().unwrap();
+    assert!(items.iter().all(|i| i["type"] == "country"));
+    assert_eq!(items[0]["country"]["code"], "RU");
 
-    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
-    let items = result.get("items").unwrap().as_array().unwrap();
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=East%20Riding&types=admin2")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result["items"].as_array().unwrap();
     assert_eq!(items.len(), 1);
-    assert_eq!(
-        items[0]
-            .get("city")
-            .unwrap()
-            .as_object()
-            .unwrap()
-            .get("name")
-            .unwrap()
-            .as_str()
-            .unwrap(),
-        "Voronezh"
-    );
+    assert_eq!(items[0]["type"], "admin2");
+    assert_eq!(items[0]["admin_division"]["code"], "GB.ENG.E1");
+
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=Voronez&types=country,admin1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let items = result["items"].as_array().unwrap();
+    assert!(!items.is_empty());
+    assert!(items.iter().all(|i| i["type"] != "city"));
+
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=Voronez&types=street")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
 
     Ok(())
 }
@@ -421,3 +1412,679 @@ async fn api_reverse_filter_by_countries() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test_log::test(ntex::test)]
+async fn api_admin_division() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/admin2/get?code=GB.ENG.E1&lang=ru")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let item = result.get("admin_division").unwrap();
+    assert_eq!(
+        item.get("name").unwrap().as_str().unwrap(),
+        "Ист-Райдинг-оф-Йоркшир"
+    );
+    assert_eq!(
+        item.get("country")
+            .unwrap()
+            .get("code")
+            .unwrap()
+            .as_str()
+            .unwrap(),
+        "GB"
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/admin1/get?code=XX.00")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert!(result.get("admin_division").unwrap().is_null());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_random() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let req = test::TestRequest::get()
+            .uri("/random?seed=42&countries=gb&weight_by_population=true")
+            .to_request();
+        let resp = app.call(req).await.unwrap();
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let bytes = test::read_body(resp).await;
+
+        let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+        let city = result.get("city").unwrap();
+        assert_eq!(
+            city.get("country")
+                .unwrap()
+                .get("code")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            "GB"
+        );
+        ids.push(city.get("id").unwrap().as_u64().unwrap());
+    }
+    // same seed same city
+    assert_eq!(ids[0], ids[1]);
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_country_stats() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/country/stats?code=ru&lang=ru&limit=1")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    let stats = result.get("stats").unwrap();
+    assert_eq!(stats.get("cities").unwrap(), 2);
+    assert_eq!(stats.get("population").unwrap(), 10381222 + 848752);
+    let largest = stats.get("largest_cities").unwrap().as_array().unwrap();
+    assert_eq!(largest.len(), 1);
+    assert_eq!(largest[0].get("name").unwrap().as_str().unwrap(), "Moscow");
+    assert_eq!(
+        stats.get("translation_coverage").unwrap(),
+        &serde_json::json!({"ru": 0.5})
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/country/stats?code=us")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert!(result.get("stats").unwrap().is_null());
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_languages() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/metadata/languages")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(
+        result.get("items").unwrap(),
+        &serde_json::json!([{"lang": "ru", "cities": 3}])
+    );
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_country_info() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/country/info?code=rs&lang=ru")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    let country = result.get("country").unwrap().as_object().unwrap();
+    assert_eq!(country.get("name").unwrap().as_str().unwrap(), "Сербия");
+    assert!(country.get("bbox").unwrap().get("min_lat").is_some());
+
+    let capital = country.get("capital").unwrap().as_object().unwrap();
+    assert_eq!(capital.get("name").unwrap().as_str().unwrap(), "Белград");
+    assert_eq!(
+        capital.get("latitude").unwrap().as_f64().unwrap() as f32,
+        44.80401
+    );
+
+    // official language of the country
+    let req = test::TestRequest::get()
+        .uri("/country/info?code=ru&lang=local")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await)?;
+    assert_eq!(result["country"]["name"], "Россия");
+
+    let req = test::TestRequest::get()
+        .uri("/admin1/get?code=RU.86&lang=local")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let result: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await)?;
+    assert_eq!(result["admin_division"]["name"], "Воронежская область");
+    assert_eq!(result["admin_division"]["country"]["name"], "Россия");
+
+    Ok(())
+}
+
+struct MockEngine {
+    city: CitiesRecord,
+}
+
+impl GeoEngine for MockEngine {
+    fn get(&self, id: &u32) -> Option<&CitiesRecord> {
+        (*id == self.city.id).then_some(&self.city)
+    }
+
+    fn capital(&self, _country_code: &str) -> Option<&CitiesRecord> {
+        None
+    }
+
+    fn suggest_with_scores<T: AsRef<str>>(
+        &self,
+        _pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        std::iter::once(SuggestItem {
+            city: &self.city,
+            score: 1.0,
+            matched: &self.city.name,
+            matched_name: &self.city.name,
+        })
+        .take(options.limit)
+        .collect()
+    }
+
+    fn reverse_with<T: AsRef<str>>(
+        &self,
+        _loc: (f32, f32),
+        options: ReverseOptions<'_, T>,
+    ) -> Option<Vec<ReverseItem<'_>>> {
+        Some(
+            std::iter::once(ReverseItem::new(&self.city, 0.0, 0.0, 0.0))
+                .take(options.limit)
+                .collect(),
+        )
+    }
+
+    fn country_info(&self, _country_code: &str) -> Option<&CountryRecord> {
+        None
+    }
+
+    fn city_name<'a>(&'a self, city: &'a CitiesRecord, lang: &str) -> Option<Cow<'a, str>> {
+        city.names
+            .as_ref()?
+            .get(lang)
+            .map(|name| Cow::Borrowed(name.as_str()))
+    }
+
+    fn admin1_names(&self, _city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        None
+    }
+
+    fn admin2_names(&self, _city: &CitiesRecord) -> Option<&HashMap<String, String>> {
+        None
+    }
+
+    fn admin1(&self, _code: &str) -> Option<AdminDivisionInfo<'_>> {
+        None
+    }
+
+    fn admin2(&self, _code: &str) -> Option<AdminDivisionInfo<'_>> {
+        None
+    }
+
+    fn random_city<R: Rng + ?Sized, T: AsRef<str>>(
+        &self,
+        _rng: &mut R,
+        _weight_by_population: bool,
+        _countries: Option<&[T]>,
+    ) -> Option<&CitiesRecord> {
+        Some(&self.city)
+    }
+
+    fn population_rank(&self, _city: &CitiesRecord) -> Option<PopulationRank> {
+        Some(PopulationRank {
+            country: 100.0,
+            global: 100.0,
+        })
+    }
+
+    fn country_stats(&self, _country_code: &str, _largest: usize) -> Option<CountryStats<'_>> {
+        None
+    }
+
+    fn suggest_candidates<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+    ) -> Vec<SuggestItem<'_>> {
+        self.suggest_with_scores(pattern, options)
+    }
+
+    fn language_coverage(&self) -> Cow<'_, HashMap<String, usize>> {
+        Cow::Owned(HashMap::new())
+    }
+
+    fn suggest_places<T: AsRef<str>>(
+        &self,
+        pattern: &str,
+        options: SuggestOptions<'_, T>,
+        weights: PlaceWeights,
+    ) -> Vec<PlaceItem<'_>> {
+        self.suggest_with_scores(pattern, options)
+            .into_iter()
+            .map(|item| PlaceItem {
+                place: Place::City(item.city),
+                score: item.score * weights.city,
+            })
+            .collect()
+    }
+
+    fn metadata(&self) -> Option<&EngineMetadata> {
+        None
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    #[cfg(feature = "geoip2_support")]
+    fn geoip2_lookup(&self, _addr: std::net::IpAddr) -> Option<&CitiesRecord> {
+        Some(&self.city)
+    }
+
+    fn ranking_profile(&self, _name: &str) -> Option<RankingProfile> {
+        None
+    }
+}
+
+#[test_log::test(ntex::test)]
+async fn api_mock_engine() -> Result<(), Error> {
+    let engine = Arc::new(MockEngine {
+        city: CitiesRecord {
+            id: 1,
+            name: "Mock".to_owned(),
+            latitude: 0.0,
+            longitude: 0.0,
+            country: None,
+            admin_division: None,
+            admin2_division: None,
+            timezone: "UTC".to_owned(),
+            names: None,
+            population: 0,
+            feature_code: "PPL".to_owned(),
+            former_names: None,
+        },
+    });
+    let app = test::init_service(App::new().state(engine).service((
+        web::resource(super::api_path("/get")).to(super::city_get::<MockEngine>),
+        web::resource(super::api_path("/suggest")).to(super::suggest::<MockEngine>),
+        web::resource(super::api_path("/resolve")).to(super::resolve::<MockEngine>),
+        web::resource(super::api_path("/timezone")).to(super::timezone::<MockEngine>),
+        web::resource(super::api_path("/random")).to(super::random::<MockEngine>),
+        web::resource(super::api_path("/places")).to(super::places::<MockEngine>),
+        web::resource(super::api_path("/metadata")).to(super::index_metadata::<MockEngine>),
+        web::resource("/metrics").to(super::metrics::<MockEngine>),
+    )))
+    .await;
+
+    let req = test::TestRequest::get().uri("/get?id=1").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert_eq!(
+        result.get("city").unwrap().get("name").unwrap().as_str(),
+        Some("Mock")
+    );
+    assert_eq!(result["city"]["population_rank"]["global"], 100.0);
+
+    let req = test::TestRequest::get()
+        .uri("/suggest?pattern=anything")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert_eq!(result.get("items").unwrap().as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::get()
+        .uri("/places?pattern=anything")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await)?;
+    assert_eq!(result["items"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::get().uri("/random").to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await)?;
+    assert_eq!(result["city"]["name"], "Mock");
+
+    for (uri, key, expected) in [
+        ("/resolve?pattern=anything", "resolved_by", "pattern"),
+        ("/resolve?lat=1&lng=1", "resolved_by", "coordinates"),
+        ("/timezone?lat=1&lng=1", "timezone", "UTC"),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        let result: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await)?;
+        assert_eq!(result[key], expected, "{uri}");
+    }
+
+    for uri in ["/metadata", "/metrics"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+    }
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_schema() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/schema/SuggestResult")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+
+    let result: serde_json::Value = serde_json::from_slice(bytes.as_ref())?;
+    assert_eq!(
+        result.get("title").unwrap().as_str().unwrap(),
+        "SuggestResult"
+    );
+    assert!(result
+        .get("definitions")
+        .unwrap()
+        .get("CityResultItem")
+        .is_some());
+
+    let req = test::TestRequest::get().uri("/schema/Unknown").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[test_log::test]
+fn index_preflight() {
+    let engine = Engine::new_from_files(SourceFileOptions {
+        cities: "../geosuggest-core/tests/misc/cities.txt",
+        names: Some("../geosuggest-core/tests/misc/names.txt"),
+        countries: Some("../geosuggest-core/tests/misc/country-info.txt"),
+        filter_languages: vec!["ru"],
+        ..Default::default()
+    })
+    .unwrap();
+    let mut metadata = engine.metadata.clone().unwrap();
+
+    let mut settings = super::settings::Settings {
+        require_countries: Some(true),
+        require_languages: Some("ru".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(super::preflight(&settings, Some(&metadata)), Ok(()));
+    assert!(super::preflight(&Default::default(), None).is_ok());
+    assert!(super::preflight(&settings, None).is_err());
+
+    settings.require_languages = Some("ru,de".to_owned());
+    let err = super::preflight(&settings, Some(&metadata)).unwrap_err();
+    assert!(err.contains("\"de\""), "{err}");
+
+    settings.require_languages = None;
+    settings.require_admin_codes = Some(true);
+    let err = super::preflight(&settings, Some(&metadata)).unwrap_err();
+    assert!(err.contains("admin"), "{err}");
+
+    // other geosuggest version with same index layout is fine
+    metadata.geosuggest_version = "999.0.0".to_owned();
+    assert!(super::preflight(&Default::default(), Some(&metadata)).is_ok());
+
+    metadata.index_format_version = INDEX_FORMAT_VERSION + 1;
+    let err = super::preflight(&Default::default(), Some(&metadata)).unwrap_err();
+    assert!(err.contains("format version"), "{err}");
+}
+
+#[test_log::test]
+fn openapi_examples_dataset() {
+    // trimmed subset of geosuggest-core test dataset
+    for file in [
+        "cities.txt",
+        "names.txt",
+        "country-info.txt",
+        "admin1-codes.txt",
+        "admin2-codes.txt",
+    ] {
+        let read = |dir: &str| std::fs::read_to_string(format!("{dir}/{file}")).unwrap();
+        let source = read("../geosuggest-core/tests/misc");
+        let source = source.lines().collect::<std::collections::HashSet<_>>();
+        for line in read("misc").lines() {
+            assert!(source.contains(line), "{file}: {line}");
+        }
+    }
+}
+
+#[test_log::test]
+fn openapi_examples() {
+    let engine = super::examples::engine().unwrap();
+    let examples = super::examples::examples(&engine).unwrap();
+    let template = include_str!("openapi3.yaml");
+
+    assert_eq!(examples.len(), 6);
+    for (name, example) in examples {
+        assert!(
+            template.contains(&format!("{{{{{name}Examples}}}}")),
+            "{name}"
+        );
+        assert!(!example.contains('\n'), "{name}");
+
+        let example: serde_json::Value = serde_json::from_str(&example).unwrap();
+        let fixture = &example["fixture"];
+        assert!(
+            fixture["summary"]
+                .as_str()
+                .unwrap()
+                .starts_with("GET /api/"),
+            "{name}"
+        );
+        assert!(fixture["value"].to_string().contains("472045"), "{name}");
+    }
+}
+
+#[test_log::test(ntex::test)]
+async fn api_metadata() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    let req = test::TestRequest::get().uri("/metadata").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+    let result: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(result["ranking_profiles"], serde_json::json!(["nearby"]));
+    let memory = &result["memory"];
+    assert!(memory["cities"].as_u64().unwrap() > 0);
+    assert_eq!(
+        memory["total"].as_u64().unwrap(),
+        ["cities", "entries", "tree", "auxiliary"]
+            .iter()
+            .map(|part| memory[part].as_u64().unwrap())
+            .sum::<u64>()
+    );
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+    let body = std::str::from_utf8(&bytes).unwrap();
+    assert!(
+        body.contains("geosuggest_engine_memory_bytes{part=\"cities\"} "),
+        "{body}"
+    );
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_metrics_countries() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for uri in [
+        "/suggest?pattern=voronezh&limit=1",
+        "/suggest?pattern=voronezh&limit=1",
+        "/get?id=472045",
+        "/places?pattern=voronezh&limit=1",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[cfg(feature = "geoip2_support")]
+    {
+        let req = test::TestRequest::get()
+            .uri("/geoip2?ip=81.2.69.142")
+            .to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+    let body = std::str::from_utf8(&bytes).unwrap();
+    assert!(body.contains("# TYPE geosuggest_country_requests_total counter\n"));
+    assert!(
+        body.contains("geosuggest_country_requests_total{endpoint=\"suggest\",country=\"RU\"} 2\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains("geosuggest_country_requests_total{endpoint=\"get\",country=\"RU\"} 1\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains("geosuggest_country_requests_total{endpoint=\"places\",country=\"RU\"} 1\n"),
+        "{body}"
+    );
+    #[cfg(feature = "geoip2_support")]
+    assert!(
+        body.contains("geosuggest_geoip2_lookups_total{country=\"GB\"} 1\n"),
+        "{body}"
+    );
+
+    // OpenMetrics
+    let req = test::TestRequest::get()
+        .uri("/metrics")
+        .header("Accept", "application/openmetrics-text; version=1.0.0")
+        .to_request();
+    let resp = app.call(req).await.unwrap();
+    assert_eq!(resp.status(), http::StatusCode::OK);
+
+    let bytes = test::read_body(resp).await;
+    let body = std::str::from_utf8(&bytes).unwrap();
+    assert!(body.contains("# TYPE geosuggest_country_requests counter\n"));
+    assert!(body.ends_with("# EOF\n"), "{body}");
+
+    Ok(())
+}
+
+#[test_log::test(ntex::test)]
+async fn api_trailing_slash_and_head() -> Result<(), Error> {
+    let app = test::init_service(App::new().configure(app_config)).await;
+
+    for uri in [
+        "/api/",
+        "/get/?id=472045",
+        "/reverse/?lat=51.6372&lng=39.1937",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = app.call(req).await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+    }
+
+    let req = test::TestRequest::get().uri("/api/").to_request();
+    let resp = app.call(req).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(test::read_body(resp).await.as_ref())?;
+    assert_eq!(result["openapi_url"], "/openapi3.yaml");
+
+    // server drops body of HEAD response, content length is kept
+    let srv = test::server(|| App::new().configure(app_config)).await;
+    for uri in ["/api/", "/schema/SuggestResult"] {
+        let body = srv.get(uri).send().await.unwrap().body().await.unwrap();
+        assert!(!body.is_empty(), "{uri}");
+
+        let mut resp = srv
+            .request(http::Method::HEAD, srv.url(uri))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK, "{uri}");
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_LENGTH).unwrap(),
+            body.len().to_string().as_str(),
+            "{uri}"
+        );
+        assert!(resp.body().await.unwrap().is_empty(), "{uri}");
+    }
+
+    Ok(())
+}
+
+#[test_log::test]
+fn query_log() {
+    use geosuggest_core::{QueryEvent, QueryHook};
+
+    let path = std::env::temp_dir().join("geosuggest-query-log.jsonl");
+    let _ = std::fs::remove_file(&path);
+
+    let query_log = super::QueryLog::open(path.to_str().unwrap()).unwrap();
+    query_log.on_query(&QueryEvent {
+        kind: "suggest",
+        pattern: Some("voronezh"),
+        loc: None,
+        countries: None,
+        bbox: None,
+        limit: 5,
+        results: 1,
+        elapsed: std::time::Duration::from_micros(1500),
+    });
+    // queued lines are written on drop
+    drop(query_log);
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(event["pattern"], "voronezh");
+    assert_eq!(event["elapsed_ms"], 1.5);
+}